@@ -0,0 +1,106 @@
+use std::error::Error;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use gif::{Encoder, Frame, Repeat};
+use opencv::core::{flip, Mat, Size};
+use opencv::imgproc::{cvt_color, COLOR_BGR2RGB};
+use opencv::prelude::*;
+use opencv::videoio::VideoWriter;
+
+use crate::camera::{resized, FrameSource};
+use crate::config::AnimationConfig;
+
+/// Grabs the configured number of mirrored BGR preview frames, `interval` apart
+pub fn capture_burst(
+    source: &mut dyn FrameSource,
+    config: &AnimationConfig,
+) -> Result<Vec<Mat>, Box<dyn Error>> {
+    let start = Instant::now();
+    let mut frames = Vec::with_capacity(config.frames as usize);
+    for i in 0..config.frames {
+        let due = start + config.interval() * i;
+        if let Some(wait) = due.checked_duration_since(Instant::now()) {
+            thread::sleep(wait);
+        }
+        let Some(frame) = source.read_preview()? else {
+            return Err(format!("Camera returned no frame {} of the burst", i + 1).into());
+        };
+        let mut flipped = Mat::default();
+        flip(&frame, &mut flipped, 1)?;
+        frames.push(flipped);
+    }
+    Ok(frames)
+}
+
+/// Scales the burst to the configured width and appends it reversed for a boomerang
+pub fn sequence(burst: &[Mat], config: &AnimationConfig) -> Result<Vec<Mat>, Box<dyn Error>> {
+    let first = burst.first().ok_or("Burst has no frames")?;
+    // Video encoders need even sizes
+    let width = config.width as i32 & !1;
+    let height = (first.rows() as f64 * width as f64 / first.cols() as f64).round() as i32;
+    let size = Size::new(width, (height & !1).max(2));
+
+    let mut frames = Vec::with_capacity(burst.len() * 2);
+    for frame in burst {
+        frames.push(resized(frame, size)?);
+    }
+    if config.boomerang && frames.len() > 2 {
+        // First and last frame are not repeated so the loop does not stutter
+        let back: Vec<Mat> = frames[1..frames.len() - 1].iter().rev().cloned().collect();
+        frames.extend(back);
+    }
+    Ok(frames)
+}
+
+pub fn to_rgb(frames: &[Mat]) -> Result<Vec<Mat>, Box<dyn Error>> {
+    frames
+        .iter()
+        .map(|frame| {
+            let mut rgb = Mat::default();
+            cvt_color(frame, &mut rgb, COLOR_BGR2RGB, 0)?;
+            Ok(rgb)
+        })
+        .collect()
+}
+
+/// Encodes RGB frames into an endlessly looping GIF
+pub fn encode_gif(frames: &[Mat], interval: Duration) -> Result<Vec<u8>, Box<dyn Error>> {
+    let first = frames
+        .first()
+        .ok_or("Can not encode a GIF without frames")?;
+    let (width, height) = (first.cols() as u16, first.rows() as u16);
+    let delay = (interval.as_secs_f32() * 100.).round() as u16;
+
+    let mut encoder = Encoder::new(Vec::new(), width, height, &[])?;
+    encoder.set_repeat(Repeat::Infinite)?;
+    for frame in frames {
+        let mut gif_frame = Frame::from_rgb_speed(width, height, frame.data_bytes()?, 10);
+        gif_frame.delay = delay;
+        encoder.write_frame(&gif_frame)?;
+    }
+    Ok(encoder.into_inner()?)
+}
+
+/// Writes BGR frames as MP4 video to `path`
+pub fn write_mp4(path: &Path, frames: &[Mat], interval: Duration) -> Result<(), Box<dyn Error>> {
+    let first = frames
+        .first()
+        .ok_or("Can not write a video without frames")?;
+    let mut writer = VideoWriter::new(
+        &path.to_string_lossy(),
+        VideoWriter::fourcc('m', 'p', '4', 'v')?,
+        1. / interval.as_secs_f64(),
+        first.size()?,
+        true,
+    )?;
+    if !writer.is_opened()? {
+        return Err(format!("Could not open video writer for {}", path.display()).into());
+    }
+    for frame in frames {
+        writer.write(frame)?;
+    }
+    writer.release()?;
+    Ok(())
+}
@@ -0,0 +1,463 @@
+use std::error::Error;
+use std::time::Duration;
+
+use opencv::core::{mean_std_dev, no_array, Mat, Rect, Size, Vector, BORDER_DEFAULT, CV_64F};
+use opencv::imgproc::{cvt_color, equalize_hist, laplacian, COLOR_BGR2GRAY};
+use opencv::objdetect::CascadeClassifier;
+use opencv::prelude::*;
+
+use crate::camera::resized;
+use crate::config::{BestShotConfig, FacesConfig};
+use crate::faces::FaceDetector;
+
+/// Stills are scaled down to this width before measuring the sharpness, so sensor noise does not count as detail
+const SHARPNESS_WIDTH: i32 = 640;
+/// Eye regions are scaled to this width before detection
+const EYE_REGION_WIDTH: i32 = 160;
+
+/// Picks the best still out of a burst that starts at the shutter moment
+pub struct BestShot {
+    frames: u32,
+    interval: Duration,
+    keep_rejects: bool,
+    /// Only loaded with face detection, eyes are searched inside faces
+    eyes: Option<Box<dyn EyeDetector>>,
+}
+
+/// Finds open eyes inside a face
+trait EyeDetector: Send {
+    /// Eyes found in the upper half of `face` in a grayscale image
+    fn count(&mut self, gray: &Mat, face: Rect) -> Result<usize, Box<dyn Error>>;
+}
+
+impl EyeDetector for CascadeClassifier {
+    fn count(&mut self, gray: &Mat, face: Rect) -> Result<usize, Box<dyn Error>> {
+        count_eyes(self, gray, face)
+    }
+}
+
+/// The kept still of a burst
+pub struct Pick {
+    pub best: Mat,
+    /// Faces in the kept still, `None` without face detection
+    pub faces: Option<Vec<Rect>>,
+    /// The other stills if rejects are kept
+    pub rejects: Vec<Mat>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Score {
+    /// Variance of the Laplacian, drops with motion blur and missed focus
+    sharpness: f64,
+    /// Share of the eyes of all faces that were found open, 0 without faces
+    open_eyes: f64,
+}
+
+impl BestShot {
+    /// Loads the eye cascade if faces are detected, returns `None` if best shot is disabled
+    pub fn from_config(
+        config: &BestShotConfig,
+        faces: &FacesConfig,
+    ) -> Result<Option<Self>, Box<dyn Error>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let eyes: Option<Box<dyn EyeDetector>> = if faces.enabled {
+            let path = config.eye_cascade.to_string_lossy();
+            let classifier = CascadeClassifier::new(&path)?;
+            if classifier.empty()? {
+                return Err(format!("Could not load eye cascade {path}").into());
+            }
+            Some(Box::new(classifier))
+        } else {
+            None
+        };
+        Ok(Some(Self {
+            frames: config.frames,
+            interval: config.interval(),
+            keep_rejects: config.keep_rejects,
+            eyes,
+        }))
+    }
+
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Scores the BGR stills and keeps the one with the most open eyes and the least blur
+    pub fn pick(
+        &mut self,
+        mut stills: Vec<Mat>,
+        mut detector: Option<&mut FaceDetector>,
+    ) -> Result<Pick, Box<dyn Error>> {
+        let mut scores = Vec::with_capacity(stills.len());
+        let mut faces = Vec::with_capacity(stills.len());
+        for still in &stills {
+            let found = match detector
+                .as_deref_mut()
+                .map(|detector| detector.detect(still))
+            {
+                Some(Ok(found)) => Some(found),
+                Some(Err(e)) => {
+                    eprintln!("Face detection failed: {e}");
+                    None
+                }
+                None => None,
+            };
+            scores.push(self.score(still, found.as_deref().unwrap_or_default())?);
+            faces.push(found);
+        }
+
+        let best = best_score(&scores).ok_or("Burst has no stills")?;
+        println!(
+            "Kept still {} of {} (sharpness {:.0}, {:.0}% eyes open)",
+            best + 1,
+            scores.len(),
+            scores[best].sharpness,
+            scores[best].open_eyes * 100.
+        );
+
+        let kept = stills.remove(best);
+        Ok(Pick {
+            best: kept,
+            faces: faces.swap_remove(best),
+            rejects: if self.keep_rejects {
+                stills
+            } else {
+                Vec::new()
+            },
+        })
+    }
+
+    fn score(&mut self, still: &Mat, faces: &[Rect]) -> Result<Score, Box<dyn Error>> {
+        let mut gray = Mat::default();
+        cvt_color(still, &mut gray, COLOR_BGR2GRAY, 0)?;
+        let open_eyes = match &mut self.eyes {
+            Some(eyes) if !faces.is_empty() => {
+                let mut found = 0;
+                for face in faces {
+                    found += eyes.count(&gray, *face)?.min(2);
+                }
+                found as f64 / (faces.len() * 2) as f64
+            }
+            _ => 0.,
+        };
+        Ok(Score {
+            sharpness: sharpness(&gray)?,
+            open_eyes,
+        })
+    }
+}
+
+/// Index of the best score, open eyes count as much as the sharpness relative to the sharpest still
+fn best_score(scores: &[Score]) -> Option<usize> {
+    // Sharpness depends on the scene, it only compares stills of the same burst
+    let sharpest = scores
+        .iter()
+        .map(|score| score.sharpness)
+        .fold(0., f64::max);
+    let rank = |score: &Score| {
+        let relative = if sharpest > 0. {
+            score.sharpness / sharpest
+        } else {
+            0.
+        };
+        score.open_eyes + relative
+    };
+    (0..scores.len()).max_by(|&a, &b| rank(&scores[a]).total_cmp(&rank(&scores[b])))
+}
+
+/// Variance of the Laplacian of a grayscale image
+fn sharpness(gray: &Mat) -> Result<f64, Box<dyn Error>> {
+    let small = if gray.cols() > SHARPNESS_WIDTH {
+        let height = (gray.rows() as f64 * SHARPNESS_WIDTH as f64 / gray.cols() as f64).round();
+        resized(gray, Size::new(SHARPNESS_WIDTH, height as i32))?
+    } else {
+        gray.clone()
+    };
+    let mut edges = Mat::default();
+    laplacian(&small, &mut edges, CV_64F, 1, 1., 0., BORDER_DEFAULT)?;
+    let mut mean = Vector::<f64>::new();
+    let mut stddev = Vector::<f64>::new();
+    mean_std_dev(&edges, &mut mean, &mut stddev, &no_array())?;
+    let stddev = stddev.get(0)?;
+    Ok(stddev * stddev)
+}
+
+/// Eyes found in the upper half of a face, the eye cascade rarely finds closed eyes
+fn count_eyes(
+    eyes: &mut CascadeClassifier,
+    gray: &Mat,
+    face: Rect,
+) -> Result<usize, Box<dyn Error>> {
+    let region = Rect::new(face.x, face.y, face.width, face.height / 2)
+        & Rect::new(0, 0, gray.cols(), gray.rows());
+    if region.width < 2 || region.height < 2 {
+        return Ok(0);
+    }
+    let scale = EYE_REGION_WIDTH as f64 / region.width as f64;
+    let size = Size::new(
+        EYE_REGION_WIDTH,
+        ((region.height as f64 * scale).round() as i32).max(1),
+    );
+    let region = resized(&Mat::roi(gray, region)?.try_clone()?, size)?;
+    let mut equalized = Mat::default();
+    equalize_hist(&region, &mut equalized)?;
+
+    let min_eye = EYE_REGION_WIDTH / 8;
+    let mut found = Vector::<Rect>::new();
+    eyes.detect_multi_scale(
+        &equalized,
+        &mut found,
+        1.1,
+        3,
+        0,
+        Size::new(min_eye, min_eye),
+        Size::default(),
+    )?;
+    Ok(found.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opencv::core::{Point, Scalar, CV_8UC3};
+    use opencv::imgproc::{circle, gaussian_blur, line, rectangle_points, LINE_8};
+
+    /// Black and white squares, full of hard edges
+    fn checkerboard() -> Mat {
+        let mut image =
+            Mat::new_size_with_default(Size::new(160, 120), CV_8UC3, Scalar::all(0.)).unwrap();
+        for y in (0..120).step_by(10) {
+            for x in (0..160).step_by(10) {
+                if (x + y) / 10 % 2 == 0 {
+                    rectangle_points(
+                        &mut image,
+                        Point::new(x, y),
+                        Point::new(x + 9, y + 9),
+                        Scalar::all(255.),
+                        -1,
+                        LINE_8,
+                        0,
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        image
+    }
+
+    fn blurred(image: &Mat) -> Mat {
+        let mut blurred = Mat::default();
+        gaussian_blur(image, &mut blurred, Size::new(0, 0), 3., 3., BORDER_DEFAULT).unwrap();
+        blurred
+    }
+
+    fn best_shot(keep_rejects: bool) -> BestShot {
+        BestShot {
+            frames: 3,
+            interval: Duration::from_millis(100),
+            keep_rejects,
+            eyes: None,
+        }
+    }
+
+    fn gray(image: &Mat) -> Mat {
+        let mut gray = Mat::default();
+        cvt_color(image, &mut gray, COLOR_BGR2GRAY, 0).unwrap();
+        gray
+    }
+
+    #[test]
+    fn blur_lowers_the_sharpness() {
+        let sharp = checkerboard();
+        let soft = blurred(&sharp);
+        assert!(sharpness(&gray(&sharp)).unwrap() > 4. * sharpness(&gray(&soft)).unwrap());
+        let flat =
+            Mat::new_size_with_default(Size::new(160, 120), CV_8UC3, Scalar::all(90.)).unwrap();
+        assert_eq!(sharpness(&gray(&flat)).unwrap(), 0.);
+    }
+
+    #[test]
+    fn pick_keeps_the_sharp_still() {
+        let sharp = checkerboard();
+        let soft = blurred(&sharp);
+        let pick = best_shot(true)
+            .pick(vec![soft.clone(), sharp.clone(), blurred(&soft)], None)
+            .unwrap();
+        assert_eq!(pick.best.data_bytes().unwrap(), sharp.data_bytes().unwrap());
+        assert!(pick.faces.is_none());
+        // The rejects stay in burst order
+        assert_eq!(pick.rejects.len(), 2);
+        assert_eq!(
+            pick.rejects[0].data_bytes().unwrap(),
+            soft.data_bytes().unwrap()
+        );
+
+        let pick = best_shot(false).pick(vec![soft, sharp], None).unwrap();
+        assert!(pick.rejects.is_empty());
+    }
+
+    #[test]
+    fn pick_needs_stills() {
+        assert!(best_shot(false).pick(Vec::new(), None).is_err());
+    }
+
+    fn score(sharpness: f64, open_eyes: f64) -> Score {
+        Score {
+            sharpness,
+            open_eyes,
+        }
+    }
+
+    #[test]
+    fn closed_eyes_outweigh_a_little_blur() {
+        // All eyes open in a slightly softer still beats the sharpest one with closed eyes
+        assert_eq!(
+            best_score(&[score(100., 0.), score(80., 1.), score(90., 0.5)]),
+            Some(1)
+        );
+        // Half the eyes open is worth half the sharpness
+        assert_eq!(best_score(&[score(100., 0.), score(40., 0.5)]), Some(0));
+        assert_eq!(best_score(&[score(100., 0.), score(60., 0.5)]), Some(1));
+    }
+
+    #[test]
+    fn without_faces_the_sharpest_still_wins() {
+        assert_eq!(
+            best_score(&[score(3., 0.), score(7., 0.), score(5., 0.)]),
+            Some(1)
+        );
+        assert_eq!(best_score(&[]), None);
+    }
+
+    /// Counts an eye as open in each upper quarter of the face holding a dark pupil, a closed lid is only a thin line
+    struct DarkPupils;
+
+    impl EyeDetector for DarkPupils {
+        fn count(&mut self, gray: &Mat, face: Rect) -> Result<usize, Box<dyn Error>> {
+            let half = face.width / 2;
+            let mut open = 0;
+            for x in [face.x, face.x + half] {
+                let eye = Mat::roi(gray, Rect::new(x, face.y, half, face.height / 2))?;
+                let mut dark = 0;
+                for y in 0..eye.rows() {
+                    for x in 0..eye.cols() {
+                        if *eye.at_2d::<u8>(y, x)? < 64 {
+                            dark += 1;
+                        }
+                    }
+                }
+                if dark >= 50 {
+                    open += 1;
+                }
+            }
+            Ok(open)
+        }
+    }
+
+    const FACE: Rect = Rect {
+        x: 40,
+        y: 40,
+        width: 120,
+        height: 120,
+    };
+
+    /// Skin colored face in front of the checkerboard, eyes open or shut
+    fn portrait(left_open: bool, right_open: bool) -> Mat {
+        let mut still = checkerboard();
+        rectangle_points(
+            &mut still,
+            Point::new(FACE.x, FACE.y),
+            Point::new(FACE.x + FACE.width - 1, FACE.y + FACE.height - 1),
+            Scalar::new(150., 170., 210., 0.),
+            -1,
+            LINE_8,
+            0,
+        )
+        .unwrap();
+        for (center, open) in [(70, left_open), (130, right_open)] {
+            if open {
+                circle(
+                    &mut still,
+                    Point::new(center, 80),
+                    8,
+                    Scalar::all(0.),
+                    -1,
+                    LINE_8,
+                    0,
+                )
+                .unwrap();
+            } else {
+                line(
+                    &mut still,
+                    Point::new(center - 8, 80),
+                    Point::new(center + 8, 80),
+                    Scalar::all(0.),
+                    1,
+                    LINE_8,
+                    0,
+                )
+                .unwrap();
+            }
+        }
+        still
+    }
+
+    fn eye_checker() -> BestShot {
+        BestShot {
+            eyes: Some(Box::new(DarkPupils)),
+            ..best_shot(false)
+        }
+    }
+
+    #[test]
+    fn open_eyes_are_counted_per_face() {
+        let mut checker = eye_checker();
+        let open_eyes = |best_shot: &mut BestShot, still: &Mat, faces: &[Rect]| {
+            best_shot.score(still, faces).unwrap().open_eyes
+        };
+        assert_eq!(open_eyes(&mut checker, &portrait(true, true), &[FACE]), 1.);
+        assert_eq!(
+            open_eyes(&mut checker, &portrait(true, false), &[FACE]),
+            0.5
+        );
+        assert_eq!(
+            open_eyes(&mut checker, &portrait(false, false), &[FACE]),
+            0.
+        );
+        // Without faces there are no eyes to check
+        assert_eq!(open_eyes(&mut checker, &portrait(true, true), &[]), 0.);
+        assert_eq!(
+            open_eyes(&mut best_shot(false), &portrait(true, true), &[FACE]),
+            0.
+        );
+    }
+
+    #[test]
+    fn open_eyes_win_over_a_sharper_blink() {
+        let mut checker = eye_checker();
+        let blink = portrait(false, false);
+        let open = blurred(&portrait(true, true));
+        let scores = [
+            checker.score(&blink, &[FACE]).unwrap(),
+            checker.score(&open, &[FACE]).unwrap(),
+        ];
+        // The blink is the sharper still, only the eyes decide
+        assert!(scores[0].sharpness > scores[1].sharpness);
+        assert_eq!(scores[1].open_eyes, 1.);
+        assert_eq!(best_score(&scores), Some(1));
+
+        // Without the eye check the sharper blink is kept
+        let mut plain = best_shot(false);
+        let scores = [
+            plain.score(&blink, &[FACE]).unwrap(),
+            plain.score(&open, &[FACE]).unwrap(),
+        ];
+        assert_eq!(best_score(&scores), Some(0));
+    }
+}
@@ -0,0 +1,440 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use opencv::core::Size;
+use opencv::imgcodecs::{imread, IMREAD_COLOR};
+use opencv::imgproc::{resize, INTER_AREA};
+use opencv::prelude::*;
+use opencv::videoio::{
+    VideoCapture, CAP_ANY, CAP_PROP_FPS, CAP_PROP_FRAME_HEIGHT, CAP_PROP_FRAME_WIDTH,
+    CAP_PROP_POS_FRAMES,
+};
+
+use crate::config::{CameraConfig, StillMode};
+
+const IMAGE_EXTENSIONS: [&str; 6] = ["jpg", "jpeg", "png", "bmp", "webp", "tiff"];
+
+/// Something the kiosk can take pictures with. All frames are returned as unflipped BGR `Mat`s.
+pub trait FrameSource: Send {
+    /// Reads the next frame at preview resolution, `None` if no frame is available right now
+    fn read_preview(&mut self) -> Result<Option<Mat>, Box<dyn Error>>;
+    /// Reads a full resolution still
+    fn read_still(&mut self) -> Result<Option<Mat>, Box<dyn Error>>;
+    /// Reads up to `count` full resolution stills `interval` apart, stops at the first missing frame
+    fn read_stills(&mut self, count: u32, interval: Duration) -> Result<Vec<Mat>, Box<dyn Error>> {
+        read_spaced(count, interval, || self.read_still())
+    }
+    /// Preview resolution as (width, height)
+    fn resolution(&self) -> (i32, i32);
+    fn fps(&self) -> f64;
+    /// How long the last `read_still` blocked the preview, if the source has to switch modes for stills
+    fn last_still_latency(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Opens the source selected in the config, the webcam unless `camera.replay` is set
+pub fn open_source(config: &CameraConfig) -> Result<Box<dyn FrameSource>, Box<dyn Error>> {
+    match &config.replay {
+        Some(path) => Ok(Box::new(ReplaySource::open(path, config)?)),
+        None => Ok(Box::new(Webcam::open(config)?)),
+    }
+}
+
+/// Webcam using a single device handle for preview and stills
+pub struct Webcam {
+    cap: VideoCapture,
+    mode: StillMode,
+    preview_size: Size,
+    still_size: Size,
+    flush_frames: u32,
+    last_still_latency: Option<Duration>,
+}
+
+impl Webcam {
+    pub fn open(config: &CameraConfig) -> Result<Self, Box<dyn Error>> {
+        let preview_size = Size::new(config.preview_width as i32, config.preview_height as i32);
+        let still_size = Size::new(config.record_width as i32, config.record_height as i32);
+
+        let mut cap = VideoCapture::new(config.index, CAP_ANY)?;
+        if !cap.is_opened()? {
+            return Err(format!("Could not open camera {}", config.index).into());
+        }
+        let stream_size = match config.still_mode {
+            StillMode::Switch => preview_size,
+            StillMode::Downscale => still_size,
+        };
+        set_resolution(&mut cap, stream_size);
+
+        Ok(Self {
+            cap,
+            mode: config.still_mode,
+            preview_size,
+            still_size,
+            flush_frames: config.flush_frames,
+            last_still_latency: None,
+        })
+    }
+
+    /// Switches the stream to the still resolution, lets `read` grab frames and switches back
+    fn read_switched<T, F>(&mut self, read: F) -> Result<T, Box<dyn Error>>
+    where
+        F: FnOnce(&mut VideoCapture) -> Result<T, Box<dyn Error>>,
+    {
+        let start = Instant::now();
+        set_resolution(&mut self.cap, self.still_size);
+        // Collected first so the preview resolution is restored on every path
+        let result = flush_and_read(&mut self.cap, self.flush_frames, read);
+        set_resolution(&mut self.cap, self.preview_size);
+        self.last_still_latency = Some(start.elapsed());
+        result
+    }
+}
+
+/// Grabs and drops `flush_frames` frames before calling `read`,
+/// the first frames after a mode switch are often dark or still in the old resolution
+fn flush_and_read<T, F>(
+    cap: &mut VideoCapture,
+    flush_frames: u32,
+    read: F,
+) -> Result<T, Box<dyn Error>>
+where
+    F: FnOnce(&mut VideoCapture) -> Result<T, Box<dyn Error>>,
+{
+    for _ in 0..flush_frames {
+        cap.grab()?;
+    }
+    read(cap)
+}
+
+/// Calls `read` up to `count` times, `interval` apart, until it returns no frame
+fn read_spaced<F>(count: u32, interval: Duration, mut read: F) -> Result<Vec<Mat>, Box<dyn Error>>
+where
+    F: FnMut() -> Result<Option<Mat>, Box<dyn Error>>,
+{
+    let start = Instant::now();
+    let mut frames = Vec::with_capacity(count as usize);
+    for i in 0..count {
+        let due = start + interval * i;
+        if let Some(wait) = due.checked_duration_since(Instant::now()) {
+            thread::sleep(wait);
+        }
+        let Some(frame) = read()? else {
+            break;
+        };
+        frames.push(frame);
+    }
+    Ok(frames)
+}
+
+fn set_resolution(cap: &mut VideoCapture, size: Size) {
+    let _ = cap.set(CAP_PROP_FRAME_WIDTH, size.width as f64);
+    let _ = cap.set(CAP_PROP_FRAME_HEIGHT, size.height as f64);
+}
+
+pub fn read_frame(cap: &mut VideoCapture) -> Result<Option<Mat>, Box<dyn Error>> {
+    let mut frame = Mat::default();
+    if !cap.read(&mut frame)? || frame.empty() {
+        return Ok(None);
+    }
+    Ok(Some(frame))
+}
+
+impl FrameSource for Webcam {
+    fn read_preview(&mut self) -> Result<Option<Mat>, Box<dyn Error>> {
+        let Some(frame) = read_frame(&mut self.cap)? else {
+            return Ok(None);
+        };
+        if frame.size()? == self.preview_size {
+            return Ok(Some(frame));
+        }
+        Ok(Some(resized(&frame, self.preview_size)?))
+    }
+
+    fn read_still(&mut self) -> Result<Option<Mat>, Box<dyn Error>> {
+        match self.mode {
+            StillMode::Switch => self.read_switched(read_frame),
+            StillMode::Downscale => read_frame(&mut self.cap),
+        }
+    }
+
+    /// Switches the mode only once for the whole burst
+    fn read_stills(&mut self, count: u32, interval: Duration) -> Result<Vec<Mat>, Box<dyn Error>> {
+        match self.mode {
+            StillMode::Switch => {
+                self.read_switched(|cap| read_spaced(count, interval, || read_frame(&mut *cap)))
+            }
+            StillMode::Downscale => read_spaced(count, interval, || read_frame(&mut self.cap)),
+        }
+    }
+
+    fn resolution(&self) -> (i32, i32) {
+        (self.preview_size.width, self.preview_size.height)
+    }
+
+    fn fps(&self) -> f64 {
+        self.cap.get(CAP_PROP_FPS).unwrap_or(0.)
+    }
+
+    fn last_still_latency(&self) -> Option<Duration> {
+        self.last_still_latency
+    }
+}
+
+enum Replay {
+    Images { images: Vec<Mat>, next: usize },
+    Video(VideoCapture),
+}
+
+/// Replays a still image, a directory of images or a video file in a loop
+pub struct ReplaySource {
+    replay: Replay,
+    preview_size: Size,
+    still_size: Size,
+    fps: f64,
+    last_frame: Instant,
+    current: Option<Mat>,
+}
+
+impl ReplaySource {
+    pub fn open(path: &Path, config: &CameraConfig) -> Result<Self, Box<dyn Error>> {
+        let replay = if path.is_dir() {
+            let mut files = fs::read_dir(path)?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| is_image(path))
+                .collect::<Vec<_>>();
+            files.sort();
+            let images = files
+                .iter()
+                .map(|file| load_image(file))
+                .collect::<Result<Vec<_>, _>>()?;
+            if images.is_empty() {
+                return Err(format!("No images found in {}", path.display()).into());
+            }
+            Replay::Images { images, next: 0 }
+        } else if is_image(path) {
+            Replay::Images {
+                images: vec![load_image(path)?],
+                next: 0,
+            }
+        } else {
+            let cap = VideoCapture::from_file(&path.to_string_lossy(), CAP_ANY)?;
+            if !cap.is_opened()? {
+                return Err(format!("Could not open video {}", path.display()).into());
+            }
+            Replay::Video(cap)
+        };
+
+        let fps = match &replay {
+            Replay::Video(cap) => cap.get(CAP_PROP_FPS).unwrap_or(0.),
+            Replay::Images { .. } => 0.,
+        };
+        let fps = if fps > 0. {
+            fps
+        } else {
+            config.replay_fps as f64
+        };
+
+        Ok(Self {
+            replay,
+            preview_size: Size::new(config.preview_width as i32, config.preview_height as i32),
+            still_size: Size::new(config.record_width as i32, config.record_height as i32),
+            fps,
+            last_frame: Instant::now(),
+            current: None,
+        })
+    }
+
+    fn next_frame(&mut self) -> Result<Mat, Box<dyn Error>> {
+        match &mut self.replay {
+            Replay::Images { images, next } => {
+                let image = images[*next].clone();
+                *next = (*next + 1) % images.len();
+                Ok(image)
+            }
+            Replay::Video(cap) => {
+                if let Some(frame) = read_frame(cap)? {
+                    return Ok(frame);
+                }
+                // End of the video, start over
+                cap.set(CAP_PROP_POS_FRAMES, 0.)?;
+                read_frame(cap)?.ok_or_else(|| "Video does not contain any frames".into())
+            }
+        }
+    }
+
+    /// Sleeps until the next frame is due so the replay runs at the source fps
+    fn wait_for_next_frame(&mut self) {
+        let frame_time = Duration::from_secs_f64(1. / self.fps);
+        if let Some(remaining) = frame_time.checked_sub(self.last_frame.elapsed()) {
+            thread::sleep(remaining);
+        }
+        self.last_frame = Instant::now();
+    }
+}
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| IMAGE_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+}
+
+pub fn load_image(path: &Path) -> Result<Mat, Box<dyn Error>> {
+    let image = imread(&path.to_string_lossy(), IMREAD_COLOR)?;
+    if image.empty() {
+        return Err(format!("Could not load image {}", path.display()).into());
+    }
+    Ok(image)
+}
+
+pub fn resized(frame: &Mat, size: Size) -> Result<Mat, Box<dyn Error>> {
+    let mut resized = Mat::default();
+    resize(frame, &mut resized, size, 0., 0., INTER_AREA)?;
+    Ok(resized)
+}
+
+impl FrameSource for ReplaySource {
+    fn read_preview(&mut self) -> Result<Option<Mat>, Box<dyn Error>> {
+        self.wait_for_next_frame();
+        let frame = self.next_frame()?;
+        let preview = resized(&frame, self.preview_size)?;
+        self.current = Some(frame);
+        Ok(Some(preview))
+    }
+
+    fn read_still(&mut self) -> Result<Option<Mat>, Box<dyn Error>> {
+        let frame = match self.current.take() {
+            Some(frame) => frame,
+            None => self.next_frame()?,
+        };
+        let still = resized(&frame, self.still_size)?;
+        self.current = Some(frame);
+        Ok(Some(still))
+    }
+
+    fn resolution(&self) -> (i32, i32) {
+        (self.preview_size.width, self.preview_size.height)
+    }
+
+    fn fps(&self) -> f64 {
+        self.fps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opencv::core::{Scalar, Vec3b, Vector, CV_8UC3};
+    use opencv::imgcodecs::imwrite;
+    use opencv::videoio::VideoWriter;
+    use std::path::PathBuf;
+
+    const RED: [u8; 3] = [0, 0, 255];
+    const GREEN: [u8; 3] = [0, 255, 0];
+    const BLUE: [u8; 3] = [255, 0, 0];
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("kiosk-replay-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn config() -> CameraConfig {
+        CameraConfig {
+            preview_width: 32,
+            preview_height: 24,
+            record_width: 64,
+            record_height: 48,
+            replay_fps: 1000.,
+            ..CameraConfig::default()
+        }
+    }
+
+    fn frame([b, g, r]: [u8; 3]) -> Mat {
+        let color = Scalar::new(b as f64, g as f64, r as f64, 0.);
+        Mat::new_rows_cols_with_default(60, 80, CV_8UC3, color).unwrap()
+    }
+
+    /// Strongest channel of the center pixel, survives lossy video codecs
+    fn color(image: &Mat) -> [u8; 3] {
+        let pixel = image
+            .at_2d::<Vec3b>(image.rows() / 2, image.cols() / 2)
+            .unwrap()
+            .0;
+        let max = *pixel.iter().max().unwrap();
+        pixel.map(|channel| if channel == max { 255 } else { 0 })
+    }
+
+    fn previews(source: &mut ReplaySource, count: usize) -> Vec<[u8; 3]> {
+        (0..count)
+            .map(|_| {
+                let preview = source.read_preview().unwrap().unwrap();
+                assert_eq!(preview.size().unwrap(), Size::new(32, 24));
+                color(&preview)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn image_directory_loops_in_name_order() {
+        let dir = temp_dir("images");
+        for (name, bgr) in [("b.png", GREEN), ("a.png", RED), ("c.jpg", BLUE)] {
+            imwrite(
+                &dir.join(name).to_string_lossy(),
+                &frame(bgr),
+                &Vector::new(),
+            )
+            .unwrap();
+        }
+        fs::write(dir.join("notes.txt"), b"not an image").unwrap();
+
+        let mut source = ReplaySource::open(&dir, &config()).unwrap();
+        assert_eq!(source.fps(), 1000.);
+        assert_eq!(previews(&mut source, 5), [RED, GREEN, BLUE, RED, GREEN]);
+        // Stills are taken from the frame shown last, at record resolution
+        let still = source.read_still().unwrap().unwrap();
+        assert_eq!(still.size().unwrap(), Size::new(64, 48));
+        assert_eq!(color(&still), GREEN);
+        assert_eq!(previews(&mut source, 1), [BLUE]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn video_starts_over_at_the_end() {
+        let dir = temp_dir("video");
+        let path = dir.join("replay.avi");
+        let mut writer = VideoWriter::new(
+            &path.to_string_lossy(),
+            VideoWriter::fourcc('M', 'J', 'P', 'G').unwrap(),
+            25.,
+            Size::new(80, 60),
+            true,
+        )
+        .unwrap();
+        for bgr in [RED, GREEN, BLUE] {
+            writer.write(&frame(bgr)).unwrap();
+        }
+        writer.release().unwrap();
+
+        let mut source = ReplaySource::open(&path, &config()).unwrap();
+        assert_eq!(source.fps(), 25.);
+        assert_eq!(
+            previews(&mut source, 7),
+            [RED, GREEN, BLUE, RED, GREEN, BLUE, RED]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn directory_without_images_is_an_error() {
+        let dir = temp_dir("empty");
+        fs::write(dir.join("notes.txt"), b"not an image").unwrap();
+        let error = ReplaySource::open(&dir, &config()).err().unwrap();
+        assert!(error.to_string().contains("No images found"), "{error}");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,339 @@
+use std::error::Error;
+use std::path::Path;
+
+use opencv::core::{
+    add_weighted, bitwise_or, in_range, max, merge, no_array, split, subtract, Mat, Scalar, Size,
+    Vec3b, Vector, BORDER_DEFAULT, CV_32F, CV_8UC3,
+};
+use opencv::imgproc::{blend_linear, cvt_color, gaussian_blur, COLOR_BGR2HSV};
+use opencv::prelude::*;
+
+use crate::camera::load_image;
+use crate::config::ChromaKeyConfig;
+use crate::draw::{bgr_scalar, cover};
+
+/// Replaces a colored backdrop with a background image
+pub struct ChromaKey {
+    /// Hue of the key color in OpenCV units, 0 to 180
+    hue: f64,
+    /// Hue distance in OpenCV units
+    tolerance: f64,
+    min_saturation: f64,
+    min_value: f64,
+    spill: f64,
+    /// BGR channel the backdrop color is strongest in
+    spill_channel: usize,
+    feather: f32,
+    backgrounds: Vec<Mat>,
+    /// Backgrounds scaled to the size of the last image they were used for
+    scaled: Vec<Option<Mat>>,
+}
+
+impl ChromaKey {
+    /// Loads the backgrounds, returns `None` if the chroma key is disabled
+    pub fn from_config(config: &ChromaKeyConfig) -> Result<Option<Self>, Box<dyn Error>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let backgrounds = config
+            .backgrounds
+            .iter()
+            .map(|path| load_image(path))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let key = Mat::new_rows_cols_with_default(1, 1, CV_8UC3, bgr_scalar(config.key_color))?;
+        let mut hsv = Mat::default();
+        cvt_color(&key, &mut hsv, COLOR_BGR2HSV, 0)?;
+        let hue = hsv.at_2d::<Vec3b>(0, 0)?[0] as f64;
+        let bgr = key.at_2d::<Vec3b>(0, 0)?;
+        let spill_channel = (0..3).max_by_key(|&channel| bgr[channel]).unwrap_or(1);
+
+        Ok(Some(Self {
+            hue,
+            // OpenCV hues are halved to fit into 8 bits
+            tolerance: config.tolerance as f64 / 2.,
+            min_saturation: config.min_saturation as f64,
+            min_value: config.min_value as f64,
+            spill: config.spill as f64,
+            spill_channel,
+            feather: config.feather,
+            scaled: vec![None; backgrounds.len()],
+            backgrounds,
+        }))
+    }
+
+    /// Composites the guests in front of a BGR image onto the background with the given index
+    pub fn apply(&mut self, image: &Mat, background: usize) -> Result<Mat, Box<dyn Error>> {
+        let backdrop = self.backdrop_mask(image)?;
+        let mut background_weights = Mat::default();
+        backdrop.convert_to(&mut background_weights, CV_32F, 1. / 255., 0.)?;
+        let mut image_weights = Mat::default();
+        background_weights.convert_to(&mut image_weights, CV_32F, -1., 1.)?;
+
+        let foreground = self.suppress_spill(image)?;
+        let background = self.background(background, image.size()?)?;
+        let mut keyed = Mat::default();
+        blend_linear(
+            &foreground,
+            background,
+            &image_weights,
+            &background_weights,
+            &mut keyed,
+        )?;
+        Ok(keyed)
+    }
+
+    /// 255 where the backdrop is visible, with feathered edges
+    fn backdrop_mask(&self, image: &Mat) -> Result<Mat, Box<dyn Error>> {
+        let mut hsv = Mat::default();
+        cvt_color(image, &mut hsv, COLOR_BGR2HSV, 0)?;
+
+        let lower = self.hue - self.tolerance;
+        let upper = self.hue + self.tolerance;
+        let mut mask = self.hue_range(&hsv, lower.max(0.), upper.min(179.))?;
+        // Hues wrap around at 180, red keys need a second range
+        let wrapped = if lower < 0. {
+            Some((lower + 180., 179.))
+        } else if upper > 179. {
+            Some((0., upper - 180.))
+        } else {
+            None
+        };
+        if let Some((lower, upper)) = wrapped {
+            let extra = self.hue_range(&hsv, lower, upper)?;
+            let mut combined = Mat::default();
+            bitwise_or(&mask, &extra, &mut combined, &no_array())?;
+            mask = combined;
+        }
+
+        let sigma = (image.cols() as f32 * self.feather) as f64;
+        if sigma <= 0. {
+            return Ok(mask);
+        }
+        let mut feathered = Mat::default();
+        gaussian_blur(
+            &mask,
+            &mut feathered,
+            Size::new(0, 0),
+            sigma,
+            sigma,
+            BORDER_DEFAULT,
+        )?;
+        Ok(feathered)
+    }
+
+    fn hue_range(&self, hsv: &Mat, lower: f64, upper: f64) -> Result<Mat, Box<dyn Error>> {
+        let mut mask = Mat::default();
+        in_range(
+            hsv,
+            &Scalar::new(lower, self.min_saturation, self.min_value, 0.),
+            &Scalar::new(upper, 255., 255., 0.),
+            &mut mask,
+        )?;
+        Ok(mask)
+    }
+
+    /// Limits the key channel to the brighter of the other two channels,
+    /// removes the backdrop color reflected onto hair and skin
+    fn suppress_spill(&self, image: &Mat) -> Result<Mat, Box<dyn Error>> {
+        if self.spill <= 0. {
+            return Ok(image.clone());
+        }
+        let mut channels = Vector::<Mat>::new();
+        split(image, &mut channels)?;
+        let key = channels.get(self.spill_channel)?;
+        let others = (0..3)
+            .filter(|&channel| channel != self.spill_channel)
+            .map(|channel| channels.get(channel))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut limit = Mat::default();
+        max(&others[0], &others[1], &mut limit)?;
+        // Saturates at 0 where the key channel is not the strongest
+        let mut excess = Mat::default();
+        subtract(&key, &limit, &mut excess, &no_array(), -1)?;
+        let mut reduced = Mat::default();
+        add_weighted(&key, 1., &excess, -self.spill, 0., &mut reduced, -1)?;
+        channels.set(self.spill_channel, reduced)?;
+
+        let mut despilled = Mat::default();
+        merge(&channels, &mut despilled)?;
+        Ok(despilled)
+    }
+
+    fn background(&mut self, index: usize, size: Size) -> Result<&Mat, Box<dyn Error>> {
+        let index = index % self.backgrounds.len();
+        let current = matches!(&self.scaled[index], Some(scaled) if scaled.size()? == size);
+        if !current {
+            self.scaled[index] = Some(cover(&self.backgrounds[index], size)?);
+        }
+        Ok(self.scaled[index]
+            .as_ref()
+            .expect("Background was just scaled"))
+    }
+}
+
+/// Name shown on the background button
+pub fn background_label(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BACKDROP: [u8; 3] = [64, 177, 0];
+    const MAGENTA: [u8; 3] = [255, 0, 255];
+    const SKIN: [u8; 3] = [90, 130, 200];
+
+    /// BGR image of `width` columns from its pixels
+    fn image(width: i32, pixels: &[[u8; 3]]) -> Mat {
+        let height = pixels.len() as i32 / width;
+        let mut image =
+            Mat::new_size_with_default(Size::new(width, height), CV_8UC3, Scalar::all(0.)).unwrap();
+        image
+            .data_bytes_mut()
+            .unwrap()
+            .copy_from_slice(pixels.as_flattened());
+        image
+    }
+
+    fn pixels(image: &Mat) -> Vec<[u8; 3]> {
+        image
+            .data_bytes()
+            .unwrap()
+            .chunks_exact(3)
+            .map(|pixel| [pixel[0], pixel[1], pixel[2]])
+            .collect()
+    }
+
+    /// Chroma key with a plain magenta background and no feathering unless `config` sets it
+    fn chroma_key(config: ChromaKeyConfig) -> ChromaKey {
+        let mut key = ChromaKey::from_config(&ChromaKeyConfig {
+            enabled: true,
+            ..config
+        })
+        .unwrap()
+        .unwrap();
+        key.backgrounds.push(image(1, &[MAGENTA]));
+        key.scaled.push(None);
+        key
+    }
+
+    fn no_feather() -> ChromaKeyConfig {
+        ChromaKeyConfig {
+            feather: 0.,
+            ..ChromaKeyConfig::default()
+        }
+    }
+
+    fn mask(key: &ChromaKey, image: &Mat) -> Vec<u8> {
+        key.backdrop_mask(image)
+            .unwrap()
+            .data_bytes()
+            .unwrap()
+            .to_vec()
+    }
+
+    #[test]
+    fn green_backdrop_is_replaced() {
+        let mut key = chroma_key(no_feather());
+        // Backdrop, pure green, skin, a too dark green and a too grey one
+        let input = image(
+            5,
+            &[BACKDROP, [0, 255, 0], SKIN, [0, 30, 0], [110, 140, 110]],
+        );
+        assert_eq!(mask(&key, &input), [255, 255, 0, 0, 0]);
+        assert_eq!(
+            pixels(&key.apply(&input, 0).unwrap()),
+            // Kept greens lose 60% of their spill
+            [MAGENTA, MAGENTA, SKIN, [0, 12, 0], [110, 122, 110]]
+        );
+    }
+
+    #[test]
+    fn red_key_wraps_around_the_hue_circle() {
+        let key = chroma_key(ChromaKeyConfig {
+            key_color: 0xFF0000FF,
+            ..no_feather()
+        });
+        // Red, hues of 346 and 14 degrees on both sides of 0, orange, yellow and magenta
+        let input = image(
+            6,
+            &[
+                [0, 0, 255],
+                [60, 0, 255],
+                [0, 60, 255],
+                [0, 128, 255],
+                [0, 255, 255],
+                MAGENTA,
+            ],
+        );
+        assert_eq!(mask(&key, &input), [255, 255, 255, 255, 0, 0]);
+
+        let narrow = chroma_key(ChromaKeyConfig {
+            key_color: 0xFF0000FF,
+            tolerance: 10.,
+            ..no_feather()
+        });
+        assert_eq!(mask(&narrow, &input), [255, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn spill_is_limited_to_the_other_channels() {
+        let input = image(3, &[[100, 200, 50], [200, 100, 50], [90, 90, 90]]);
+        let full = chroma_key(ChromaKeyConfig {
+            spill: 1.,
+            ..no_feather()
+        });
+        assert_eq!(
+            pixels(&full.suppress_spill(&input).unwrap()),
+            [[100, 100, 50], [200, 100, 50], [90, 90, 90]]
+        );
+        let half = chroma_key(ChromaKeyConfig {
+            spill: 0.5,
+            ..no_feather()
+        });
+        assert_eq!(
+            pixels(&half.suppress_spill(&input).unwrap())[0],
+            [100, 150, 50]
+        );
+        let off = chroma_key(ChromaKeyConfig {
+            spill: 0.,
+            ..no_feather()
+        });
+        assert_eq!(pixels(&off.suppress_spill(&input).unwrap()), pixels(&input));
+    }
+
+    #[test]
+    fn feathering_softens_the_mask_edge() {
+        // Backdrop on the left half, skin on the right
+        let row: Vec<[u8; 3]> = (0..40)
+            .map(|x| if x < 20 { BACKDROP } else { SKIN })
+            .collect();
+        let input = image(40, &row);
+        assert_eq!(
+            mask(&chroma_key(no_feather()), &input)[18..22],
+            [255, 255, 0, 0]
+        );
+
+        let mut key = chroma_key(ChromaKeyConfig {
+            feather: 0.05,
+            spill: 0.,
+            ..ChromaKeyConfig::default()
+        });
+        let feathered = mask(&key, &input);
+        assert_eq!((feathered[0], feathered[39]), (255, 0));
+        assert!(feathered.windows(2).all(|pair| pair[0] >= pair[1]));
+        assert!(feathered[19] > 128 && feathered[19] < 255, "{feathered:?}");
+        assert!(feathered[20] < 128 && feathered[20] > 0, "{feathered:?}");
+
+        // The edge blends the skin into the background
+        let keyed = pixels(&key.apply(&input, 0).unwrap());
+        assert_eq!((keyed[0], keyed[39]), (MAGENTA, SKIN));
+        let edge = keyed[20];
+        assert!(edge != SKIN && edge != MAGENTA, "{edge:?}");
+        assert!(edge[0] > SKIN[0] && edge[1] < SKIN[1], "{edge:?}");
+    }
+}
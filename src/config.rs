@@ -0,0 +1,1639 @@
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::format::{Item, StrftimeItems};
+use serde::Deserialize;
+
+use crate::ipp::IppPrinter;
+
+/// Environment variable that is checked for a config path when none is given on the command line
+pub const CONFIG_ENV: &str = "PHOTO_KIOSK_CONFIG";
+/// Longest accepted duration, a day, so durations and their sums always fit into a `Duration`
+const MAX_SECS: f32 = 86_400.;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KioskConfig {
+    pub camera: CameraConfig,
+    pub window: WindowConfig,
+    pub timing: TimingConfig,
+    pub countdown: CountdownConfig,
+    pub feedback: FeedbackConfig,
+    pub gpio: GpioConfig,
+    pub remote: RemoteConfig,
+    pub strip: StripConfig,
+    pub animation: AnimationConfig,
+    pub video: VideoConfig,
+    pub overlay: OverlayConfig,
+    pub filters: FilterConfig,
+    pub chroma_key: ChromaKeyConfig,
+    pub faces: FacesConfig,
+    pub props: PropsConfig,
+    pub best_shot: BestShotConfig,
+    pub print: PrintConfig,
+    pub output: OutputConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraConfig {
+    /// Device index passed to `VideoCapture::new`
+    pub index: i32,
+    pub preview_width: u32,
+    pub preview_height: u32,
+    pub record_width: u32,
+    pub record_height: u32,
+    /// How full resolution stills are taken with the single camera handle
+    pub still_mode: StillMode,
+    /// Frames dropped after switching to the still resolution
+    pub flush_frames: u32,
+    /// Still image, directory of images or video file that is replayed instead of using the webcam
+    pub replay: Option<PathBuf>,
+    /// Frame rate used when replaying images
+    pub replay_fps: f32,
+    /// Failed reads in a row after which the camera is reopened
+    pub max_failures: u32,
+    /// Seconds without a frame after which the camera is reopened, also the longest a preview read may block.
+    /// Still reads in switch mode get it once per resolution change and flushed frame.
+    pub stall_timeout_secs: f32,
+    /// Delay before the first reconnect attempt, doubled on every failed attempt
+    pub reconnect_initial_secs: f32,
+    /// Upper bound for the delay between reconnect attempts
+    pub reconnect_max_secs: f32,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        Self {
+            index: 0,
+            preview_width: 1024,
+            preview_height: 576,
+            record_width: 1920,
+            record_height: 1080,
+            still_mode: StillMode::Switch,
+            flush_frames: 3,
+            replay: None,
+            replay_fps: 30.,
+            max_failures: 10,
+            stall_timeout_secs: 3.,
+            reconnect_initial_secs: 0.5,
+            reconnect_max_secs: 10.,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StillMode {
+    /// Stream at preview resolution and switch to the record resolution for a still
+    Switch,
+    /// Stream at record resolution and downscale every frame for the preview
+    Downscale,
+}
+
+impl CameraConfig {
+    pub fn stall_timeout(&self) -> Duration {
+        Duration::from_secs_f32(self.stall_timeout_secs)
+    }
+
+    /// Longest a still read may block, switching takes two resolution changes and the flushed frames
+    pub fn still_timeout(&self) -> Duration {
+        match self.still_mode {
+            StillMode::Switch => self.stall_timeout() * (self.flush_frames + 3),
+            StillMode::Downscale => self.stall_timeout(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowConfig {
+    pub width: u32,
+    pub height: u32,
+    pub title: String,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            width: 1024,
+            height: 600,
+            title: "Susi".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimingConfig {
+    /// Seconds between the trigger and the capture
+    pub countdown_secs: f32,
+    /// Seconds the captured photo is shown for review before it is kept automatically
+    pub presenting_secs: f32,
+    /// Seconds to wait for the full resolution still before giving up, bursts, animations and videos get their length on top
+    pub capture_timeout_secs: f32,
+}
+
+impl Default for TimingConfig {
+    fn default() -> Self {
+        Self {
+            countdown_secs: 5.,
+            presenting_secs: 5.,
+            capture_timeout_secs: 10.,
+        }
+    }
+}
+
+impl TimingConfig {
+    pub fn countdown(&self) -> Duration {
+        Duration::from_secs_f32(self.countdown_secs)
+    }
+    pub fn presenting(&self) -> Duration {
+        Duration::from_secs_f32(self.presenting_secs)
+    }
+    pub fn capture_timeout(&self) -> Duration {
+        Duration::from_secs_f32(self.capture_timeout_secs)
+    }
+}
+
+/// Look of the countdown overlay, colors are given as `0xRRGGBBAA`
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CountdownConfig {
+    pub font_size: i32,
+    pub text_color: u32,
+    /// Outer radius of the ring around the number, shrunk to fit small screens
+    pub ring_radius: f32,
+    pub ring_thickness: f32,
+    pub ring_color: u32,
+    pub ring_background: u32,
+    /// Shown instead of the number for the last `smile_secs` before the capture
+    pub smile_text: String,
+    pub smile_secs: f32,
+}
+
+impl Default for CountdownConfig {
+    fn default() -> Self {
+        Self {
+            font_size: 160,
+            text_color: 0xFFFFFFFF,
+            ring_radius: 140.,
+            ring_thickness: 14.,
+            ring_color: 0xE62937FF,
+            ring_background: 0x00000060,
+            smile_text: "Smile!".to_string(),
+            smile_secs: 0.7,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeedbackConfig {
+    /// Seconds the white flash takes to fade out after the still was taken, 0 disables it
+    pub flash_secs: f32,
+    /// Sound played when the still is taken
+    pub shutter_sound: Option<PathBuf>,
+    /// Seconds after which a spinner is shown while the still is being processed
+    pub processing_threshold_secs: f32,
+}
+
+impl Default for FeedbackConfig {
+    fn default() -> Self {
+        Self {
+            flash_secs: 0.4,
+            shutter_sound: None,
+            processing_threshold_secs: 0.8,
+        }
+    }
+}
+
+impl FeedbackConfig {
+    pub fn flash(&self) -> Duration {
+        Duration::from_secs_f32(self.flash_secs)
+    }
+    pub fn processing_threshold(&self) -> Duration {
+        Duration::from_secs_f32(self.processing_threshold_secs)
+    }
+}
+
+/// Physical trigger button on a GPIO line
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GpioConfig {
+    /// GPIO character device like `/dev/gpiochip0`, the button is disabled if not set
+    pub chip: Option<PathBuf>,
+    /// Line offset on the chip
+    pub line: u32,
+    /// Set when the button pulls the line to ground
+    pub active_low: bool,
+    pub debounce_secs: f32,
+    /// Holding the button this long retakes the presented photo instead of triggering
+    pub long_press_secs: f32,
+    /// Simulates the button with the space key, for development without the hardware
+    pub simulate: bool,
+}
+
+impl Default for GpioConfig {
+    fn default() -> Self {
+        Self {
+            chip: None,
+            line: 17,
+            active_low: true,
+            debounce_secs: 0.03,
+            long_press_secs: 1.5,
+            simulate: false,
+        }
+    }
+}
+
+impl GpioConfig {
+    pub fn debounce(&self) -> Duration {
+        Duration::from_secs_f32(self.debounce_secs)
+    }
+    pub fn long_press(&self) -> Duration {
+        Duration::from_secs_f32(self.long_press_secs)
+    }
+}
+
+/// HTTP API to trigger and control the kiosk from another device
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RemoteConfig {
+    /// Address like `0.0.0.0:8080`, the API is disabled if not set
+    pub bind: Option<String>,
+    /// Required as `Authorization: Bearer <token>` header if set
+    pub token: Option<String>,
+}
+
+/// Multi-shot sessions composed into one photo strip or grid
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StripConfig {
+    /// Shots taken per trigger, 1 takes single photos without a strip
+    pub shots: u32,
+    /// Seconds of countdown before every shot after the first
+    pub shot_countdown_secs: f32,
+    pub layout: StripLayout,
+    /// Size of the composed image in pixels, defaults to 600x1800 for a strip and 1800x1200 for a grid
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Space around the cells
+    pub margin: u32,
+    /// Space between the cells
+    pub spacing: u32,
+    pub background_color: u32,
+    /// Image stretched over the whole strip before the cells are drawn
+    pub background: Option<PathBuf>,
+    pub caption: Option<String>,
+    pub caption_color: u32,
+    /// Height of the caption area at the bottom, only reserved if a caption is set
+    pub caption_height: u32,
+    /// Cell positions for the custom layout, shots are repeated if there are more cells than shots
+    pub cells: Vec<CellConfig>,
+}
+
+impl Default for StripConfig {
+    fn default() -> Self {
+        Self {
+            shots: 1,
+            shot_countdown_secs: 3.,
+            layout: StripLayout::Strip,
+            width: None,
+            height: None,
+            margin: 30,
+            spacing: 20,
+            background_color: 0xFFFFFFFF,
+            background: None,
+            caption: None,
+            caption_color: 0x000000FF,
+            caption_height: 160,
+            cells: Vec::new(),
+        }
+    }
+}
+
+impl StripConfig {
+    pub fn shot_countdown(&self) -> Duration {
+        Duration::from_secs_f32(self.shot_countdown_secs)
+    }
+
+    /// Size of the composed image
+    pub fn size(&self) -> (u32, u32) {
+        let (width, height) = match self.layout {
+            StripLayout::Grid => (1800, 1200),
+            StripLayout::Strip | StripLayout::Custom => (600, 1800),
+        };
+        (self.width.unwrap_or(width), self.height.unwrap_or(height))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StripLayout {
+    /// All shots below each other
+    Strip,
+    /// Shots in rows of up to `ceil(sqrt(shots))`
+    Grid,
+    /// Shots placed into `cells`
+    Custom,
+}
+
+/// Rectangle in pixels of the composed image
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CellConfig {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Animated GIF capture from a burst of preview frames
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnimationConfig {
+    /// Captures animations instead of photos
+    pub enabled: bool,
+    /// Frames in the burst
+    pub frames: u32,
+    /// Seconds between the frames of the burst, also used as frame time of the animation
+    pub interval_secs: f32,
+    /// Plays the burst forwards and backwards
+    pub boomerang: bool,
+    /// Also saves the animation as MP4 next to the GIF
+    pub mp4: bool,
+    /// Width of the saved animation, the height keeps the aspect ratio of the camera
+    pub width: u32,
+}
+
+impl Default for AnimationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            frames: 12,
+            interval_secs: 0.15,
+            boomerang: true,
+            mp4: false,
+            width: 640,
+        }
+    }
+}
+
+impl AnimationConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs_f32(self.interval_secs)
+    }
+}
+
+/// Guestbook video messages recorded at preview resolution, without audio
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VideoConfig {
+    /// Records videos instead of photos
+    pub enabled: bool,
+    pub duration_secs: f32,
+    pub fps: f32,
+    /// Four character code of the codec passed to `VideoWriter`
+    pub fourcc: String,
+    /// Extension of the saved video, selects the container
+    pub extension: String,
+}
+
+impl Default for VideoConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            duration_secs: 10.,
+            fps: 25.,
+            fourcc: "mp4v".to_string(),
+            extension: "mp4".to_string(),
+        }
+    }
+}
+
+impl VideoConfig {
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f32(self.duration_secs)
+    }
+}
+
+/// Branding blended onto every photo before it is saved and drawn over the live preview.
+/// Strips use their own background and caption, animations and videos are not branded.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OverlayConfig {
+    /// PNG with alpha channel stretched over the whole photo, like a logo or a decorative frame
+    pub image: Option<PathBuf>,
+    /// Text drawn on the photo, `{event}` and `{date}` are replaced
+    pub text: Option<String>,
+    pub text_color: u32,
+    pub text_position: TextPosition,
+    /// Height of the text band relative to the photo height
+    pub text_height: f32,
+    /// strftime format used for `{date}`
+    pub date_format: String,
+    /// Draws the overlay over the live preview so guests can frame themselves
+    pub preview: bool,
+}
+
+impl Default for OverlayConfig {
+    fn default() -> Self {
+        Self {
+            image: None,
+            text: None,
+            text_color: 0xFFFFFFFF,
+            text_position: TextPosition::Bottom,
+            text_height: 0.1,
+            date_format: "%Y-%m-%d".to_string(),
+            preview: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextPosition {
+    Top,
+    Bottom,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterConfig {
+    /// Names of the filters guests can cycle through, the first one is selected on start.
+    /// "none", "bw", "sepia", "blur" and "vintage" are built in.
+    pub available: Vec<String>,
+    /// Additional filters that can be listed in `available`
+    pub custom: Vec<CustomFilterConfig>,
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        Self {
+            available: ["none", "bw", "sepia", "blur", "vintage"]
+                .map(String::from)
+                .to_vec(),
+            custom: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomFilterConfig {
+    pub name: String,
+    /// Shown on the filter button, defaults to the name
+    pub label: Option<String>,
+    /// Applied in order
+    pub steps: Vec<FilterStep>,
+}
+
+/// Operation of a custom filter
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum FilterStep {
+    Grayscale,
+    /// Mixes in the sepia tone, 1 is full sepia
+    Sepia {
+        #[serde(default = "default_strength")]
+        strength: f32,
+    },
+    /// Scales the colors around mid gray, then adds `brightness` (-255 to 255)
+    Contrast {
+        #[serde(default = "default_contrast")]
+        contrast: f32,
+        #[serde(default)]
+        brightness: f32,
+    },
+    /// Darkens the corners, 1 makes them black
+    Vignette {
+        #[serde(default = "default_vignette")]
+        strength: f32,
+    },
+    /// Gaussian blur, the sigma is relative to the image width
+    Blur {
+        #[serde(default = "default_blur_sigma")]
+        sigma: f32,
+    },
+    /// Color grading with a 3D lookup table from a .cube file
+    Lut {
+        path: PathBuf,
+    },
+}
+
+fn default_strength() -> f32 {
+    1.
+}
+
+fn default_contrast() -> f32 {
+    1.
+}
+
+fn default_vignette() -> f32 {
+    0.5
+}
+
+fn default_blur_sigma() -> f32 {
+    0.004
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChromaKeyConfig {
+    /// Replaces the backdrop of photos with one of the backgrounds
+    pub enabled: bool,
+    /// Color of the backdrop as `0xRRGGBBAA`, alpha is ignored
+    pub key_color: u32,
+    /// Hue distance from the key color in degrees that still counts as backdrop
+    pub tolerance: f32,
+    /// Darker or greyer pixels are never keyed out, 0 to 255
+    pub min_saturation: u8,
+    pub min_value: u8,
+    /// How much of the backdrop color reflected onto the guests is removed, 0 to 1
+    pub spill: f32,
+    /// Softness of the mask edge as blur sigma relative to the photo width
+    pub feather: f32,
+    /// Images guests can choose from, the first one is selected on start
+    pub backgrounds: Vec<PathBuf>,
+    /// Keys the live preview too, costs frame rate on slow devices
+    pub preview: bool,
+}
+
+impl Default for ChromaKeyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            key_color: 0x00B140FF,
+            tolerance: 40.,
+            min_saturation: 70,
+            min_value: 40,
+            spill: 0.6,
+            feather: 0.002,
+            backgrounds: Vec::new(),
+            preview: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FacesConfig {
+    /// Detects faces in the preview and in captured stills
+    pub enabled: bool,
+    /// Haar cascade XML file, OpenCV ships them in `share/opencv4/haarcascades`
+    pub cascade: PathBuf,
+    /// Frames are scaled down to this width before detection
+    pub detection_width: u32,
+    /// Seconds between two detections on the preview
+    pub interval_secs: f32,
+    /// Higher values find fewer faces with fewer false positives
+    pub min_neighbors: u32,
+    /// Smallest face that is detected, relative to the frame width
+    pub min_face_size: f32,
+    /// Starts the countdown once faces were in frame for this many seconds, 0 disables it
+    pub auto_trigger_secs: f32,
+    /// Shows a warning if a captured still has no faces
+    pub warn_empty: bool,
+}
+
+impl Default for FacesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cascade: PathBuf::from(
+                "/usr/share/opencv4/haarcascades/haarcascade_frontalface_default.xml",
+            ),
+            detection_width: 320,
+            interval_secs: 0.2,
+            min_neighbors: 5,
+            min_face_size: 0.08,
+            auto_trigger_secs: 0.,
+            warn_empty: true,
+        }
+    }
+}
+
+impl FacesConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs_f32(self.interval_secs)
+    }
+
+    /// `None` if auto triggering is disabled
+    pub fn auto_trigger(&self) -> Option<Duration> {
+        (self.enabled && self.auto_trigger_secs > 0.)
+            .then(|| Duration::from_secs_f32(self.auto_trigger_secs))
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PropsConfig {
+    /// Lets guests put props on their faces, needs `faces.enabled`
+    pub enabled: bool,
+    /// Props in the order of the palette
+    pub items: Vec<PropConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PropConfig {
+    pub name: String,
+    /// PNG with alpha channel
+    pub image: PathBuf,
+    /// Point of the face the prop is attached to
+    #[serde(default)]
+    pub anchor: FaceAnchor,
+    /// Point of the image that is put onto the anchor, as fractions of the image size
+    #[serde(default = "default_pivot")]
+    pub pivot: [f32; 2],
+    /// Width of the prop relative to the width of the face
+    #[serde(default = "default_prop_scale")]
+    pub scale: f32,
+    /// Shift from the anchor relative to the width and height of the face
+    #[serde(default)]
+    pub offset: [f32; 2],
+}
+
+/// Points on the horizontal center line of a face
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FaceAnchor {
+    /// Top edge of the face box, around the hairline
+    Top,
+    #[default]
+    Eyes,
+    Nose,
+    Mouth,
+    /// Bottom edge of the face box
+    Chin,
+}
+
+impl FaceAnchor {
+    /// Height of the point as fraction of a Haar cascade face box
+    pub fn height(self) -> f32 {
+        match self {
+            FaceAnchor::Top => 0.,
+            FaceAnchor::Eyes => 0.38,
+            FaceAnchor::Nose => 0.6,
+            FaceAnchor::Mouth => 0.78,
+            FaceAnchor::Chin => 1.,
+        }
+    }
+}
+
+fn default_pivot() -> [f32; 2] {
+    [0.5, 0.5]
+}
+
+fn default_prop_scale() -> f32 {
+    1.
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BestShotConfig {
+    /// Takes a burst of stills for every photo and keeps the best one, the burst starts at the shutter moment
+    pub enabled: bool,
+    /// Stills in the burst
+    pub frames: u32,
+    /// Seconds between the stills of the burst
+    pub interval_secs: f32,
+    /// Haar cascade for eyes, stills with open eyes are preferred when `faces.enabled` is set
+    pub eye_cascade: PathBuf,
+    /// Saves the other stills of the burst into a `rejects` directory next to the photos
+    pub keep_rejects: bool,
+}
+
+impl Default for BestShotConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            frames: 4,
+            interval_secs: 0.1,
+            eye_cascade: PathBuf::from(
+                "/usr/share/opencv4/haarcascades/haarcascade_eye_tree_eyeglasses.xml",
+            ),
+            keep_rejects: false,
+        }
+    }
+}
+
+impl BestShotConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs_f32(self.interval_secs)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PrintConfig {
+    /// Lets guests print the presented photo, the Print button is only shown for `timing.presenting_secs`
+    pub enabled: bool,
+    /// IPP URI of the CUPS queue, TLS is not supported
+    pub printer_uri: String,
+    pub paper: Paper,
+    /// Turns the paper so its long side is horizontal
+    pub landscape: bool,
+    /// Portrait size in inches of the custom paper
+    pub paper_width_in: f32,
+    pub paper_height_in: f32,
+    pub dpi: u32,
+    /// Added around the page for borderless printing, filled with the mirrored page edges
+    pub bleed_in: f32,
+    /// Space between the page edges and the cells, texts and logo
+    pub margin_in: f32,
+    pub background_color: u32,
+    /// Photo positions on the page, one cell filling the page inside the margin if empty.
+    /// With several cells a strip is printed as its shots, repeated if there are more cells than shots.
+    pub cells: Vec<PrintCellConfig>,
+    pub texts: Vec<PrintTextConfig>,
+    pub logo: Option<PrintLogoConfig>,
+    /// Rendered pages and the queue file, jobs in it are resumed after a restart
+    pub spool_dir: PathBuf,
+    /// Submissions of a job before it is given up
+    pub max_attempts: u32,
+    /// Seconds between two attempts of a job
+    pub retry_secs: f32,
+    /// Prints guests may order of one session, 0 for no limit
+    pub max_per_session: u32,
+}
+
+impl Default for PrintConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            printer_uri: "ipp://localhost:631/printers/photo".to_string(),
+            paper: Paper::Photo4x6,
+            landscape: true,
+            paper_width_in: 4.,
+            paper_height_in: 6.,
+            dpi: 300,
+            bleed_in: 0.,
+            margin_in: 0.,
+            background_color: 0xFFFFFFFF,
+            cells: Vec::new(),
+            texts: Vec::new(),
+            logo: None,
+            spool_dir: PathBuf::from("print-spool"),
+            max_attempts: 5,
+            retry_secs: 10.,
+            max_per_session: 2,
+        }
+    }
+}
+
+impl PrintConfig {
+    pub fn retry(&self) -> Duration {
+        Duration::from_secs_f32(self.retry_secs)
+    }
+
+    /// Size in inches the layout is drawn on as (width, height), one strip for 2x6 paper
+    pub fn template_size_in(&self) -> (f32, f32) {
+        let (width, height) = match self.paper {
+            Paper::Photo4x6 => (4., 6.),
+            Paper::Photo5x7 => (5., 7.),
+            Paper::Strip2x6 => (2., 6.),
+            Paper::Custom => (self.paper_width_in, self.paper_height_in),
+        };
+        if self.landscape {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Paper {
+    #[serde(rename = "4x6")]
+    Photo4x6,
+    #[serde(rename = "5x7")]
+    Photo5x7,
+    /// Two strips side by side on 4x6 paper, cut by the printer
+    #[serde(rename = "2x6")]
+    Strip2x6,
+    /// `paper_width_in` x `paper_height_in`
+    #[serde(rename = "custom")]
+    Custom,
+}
+
+/// Photo position as fractions of the page inside the margin
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PrintCellConfig {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    /// Clockwise degrees, 0, 90, 180 or 270. If unset photos are turned to the orientation of the cell.
+    pub rotation: Option<u32>,
+}
+
+/// Line of text on the page, `{event}` and `{date}` are replaced
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PrintTextConfig {
+    pub text: String,
+    /// Fractions of the page inside the margin, the text is scaled to fit
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    #[serde(default = "default_print_text_color")]
+    pub color: u32,
+}
+
+fn default_print_text_color() -> u32 {
+    0x000000FF
+}
+
+/// Image with alpha fitted into a rectangle of the page
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PrintLogoConfig {
+    pub image: PathBuf,
+    /// Fractions of the page inside the margin, the logo keeps its aspect ratio
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    /// Directory all captures are saved under
+    pub root: PathBuf,
+    /// Name of the event, used as sub directory of `root`
+    pub event: String,
+    pub format: ImageFormat,
+    /// JPEG and WebP quality from 1 to 100
+    pub quality: u8,
+    /// PNG compression level from 0 to 9
+    pub png_compression: u8,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            root: PathBuf::from("photos"),
+            event: "default".to_string(),
+            format: ImageFormat::Jpeg,
+            quality: 95,
+            png_compression: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Webp,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Png => "png",
+            ImageFormat::Webp => "webp",
+        }
+    }
+}
+
+impl KioskConfig {
+    /// Loads the config from the path given as first command line argument or in `PHOTO_KIOSK_CONFIG`.
+    /// Falls back to the defaults if neither is set.
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        let path = env::args_os()
+            .nth(1)
+            .or_else(|| env::var_os(CONFIG_ENV))
+            .map(PathBuf::from);
+
+        match path {
+            Some(path) => Self::from_file(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read config {}: {}", path.display(), e))?;
+        let config = Self::from_toml(&content)
+            .map_err(|e| format!("Invalid config {}: {}", path.display(), e))?;
+        Ok(config)
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self, Box<dyn Error>> {
+        let config: Self = toml::from_str(content)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if self.camera.index < 0 {
+            return Err(format!(
+                "camera.index must not be negative, got {}",
+                self.camera.index
+            )
+            .into());
+        }
+        check_size(
+            "camera.preview",
+            self.camera.preview_width,
+            self.camera.preview_height,
+        )?;
+        check_size(
+            "camera.record",
+            self.camera.record_width,
+            self.camera.record_height,
+        )?;
+        if !self.camera.replay_fps.is_finite() || self.camera.replay_fps <= 0. {
+            return Err(format!(
+                "camera.replay_fps must be greater than 0, got {}",
+                self.camera.replay_fps
+            )
+            .into());
+        }
+        if self.camera.max_failures == 0 {
+            return Err("camera.max_failures must be greater than 0".into());
+        }
+        check_secs("camera.stall_timeout_secs", self.camera.stall_timeout_secs)?;
+        check_secs(
+            "camera.reconnect_initial_secs",
+            self.camera.reconnect_initial_secs,
+        )?;
+        check_secs("camera.reconnect_max_secs", self.camera.reconnect_max_secs)?;
+        for (name, secs) in [
+            ("camera.stall_timeout_secs", self.camera.stall_timeout_secs),
+            (
+                "camera.reconnect_initial_secs",
+                self.camera.reconnect_initial_secs,
+            ),
+        ] {
+            if secs == 0. {
+                return Err(format!("{name} must be greater than 0").into());
+            }
+        }
+        if self.camera.reconnect_max_secs < self.camera.reconnect_initial_secs {
+            return Err(format!(
+                "camera.reconnect_max_secs must be at least camera.reconnect_initial_secs, got {}",
+                self.camera.reconnect_max_secs
+            )
+            .into());
+        }
+        check_size("window", self.window.width, self.window.height)?;
+        if self.window.title.is_empty() {
+            return Err("window.title must not be empty".into());
+        }
+        if self.window.title.contains('\0') {
+            return Err("window.title must not contain NUL characters".into());
+        }
+        check_secs("timing.countdown_secs", self.timing.countdown_secs)?;
+        check_secs("timing.presenting_secs", self.timing.presenting_secs)?;
+        check_secs(
+            "timing.capture_timeout_secs",
+            self.timing.capture_timeout_secs,
+        )?;
+        if self.timing.capture_timeout_secs == 0. {
+            return Err("timing.capture_timeout_secs must be greater than 0".into());
+        }
+        if self.countdown.font_size <= 0 {
+            return Err(format!(
+                "countdown.font_size must be greater than 0, got {}",
+                self.countdown.font_size
+            )
+            .into());
+        }
+        if self.countdown.ring_radius.is_nan() || self.countdown.ring_radius <= 0. {
+            return Err(format!(
+                "countdown.ring_radius must be greater than 0, got {}",
+                self.countdown.ring_radius
+            )
+            .into());
+        }
+        if self.countdown.ring_thickness.is_nan()
+            || self.countdown.ring_thickness <= 0.
+            || self.countdown.ring_thickness > self.countdown.ring_radius
+        {
+            return Err(format!(
+                "countdown.ring_thickness must be between 0 and countdown.ring_radius, got {}",
+                self.countdown.ring_thickness
+            )
+            .into());
+        }
+        if self.countdown.smile_text.contains('\0') {
+            return Err("countdown.smile_text must not contain NUL characters".into());
+        }
+        check_secs("countdown.smile_secs", self.countdown.smile_secs)?;
+        check_secs("feedback.flash_secs", self.feedback.flash_secs)?;
+        check_secs(
+            "feedback.processing_threshold_secs",
+            self.feedback.processing_threshold_secs,
+        )?;
+        if let Some(path) = &self.feedback.shutter_sound {
+            if !path.is_file() {
+                return Err(
+                    format!("feedback.shutter_sound {} does not exist", path.display()).into(),
+                );
+            }
+        }
+        check_secs("gpio.debounce_secs", self.gpio.debounce_secs)?;
+        check_secs("gpio.long_press_secs", self.gpio.long_press_secs)?;
+        if self.gpio.long_press_secs <= self.gpio.debounce_secs {
+            return Err(format!(
+                "gpio.long_press_secs must be longer than gpio.debounce_secs, got {}",
+                self.gpio.long_press_secs
+            )
+            .into());
+        }
+        if let Some(bind) = &self.remote.bind {
+            if bind.parse::<SocketAddr>().is_err() {
+                return Err(format!(
+                    "remote.bind must be an address like 0.0.0.0:8080, got {bind:?}"
+                )
+                .into());
+            }
+        }
+        if self
+            .remote
+            .token
+            .as_ref()
+            .is_some_and(|token| token.is_empty())
+        {
+            return Err("remote.token must not be empty".into());
+        }
+        self.validate_strip()?;
+        if self.animation.frames < 2 {
+            return Err(format!(
+                "animation.frames must be at least 2, got {}",
+                self.animation.frames
+            )
+            .into());
+        }
+        // GIF frame delays are stored in hundredths of a second
+        if self.animation.interval_secs.is_nan()
+            || self.animation.interval_secs < 0.01
+            || self.animation.interval_secs > 10.
+        {
+            return Err(format!(
+                "animation.interval_secs must be between 0.01 and 10, got {}",
+                self.animation.interval_secs
+            )
+            .into());
+        }
+        if self.animation.width < 2 || self.animation.width > u16::MAX as u32 {
+            return Err(format!(
+                "animation.width must be between 2 and {}, got {}",
+                u16::MAX,
+                self.animation.width
+            )
+            .into());
+        }
+        if self.animation.enabled && self.strip.shots > 1 {
+            return Err("animation.enabled can not be combined with strip.shots > 1".into());
+        }
+        self.validate_video()?;
+        if let Some(path) = &self.overlay.image {
+            if !path.is_file() {
+                return Err(format!("overlay.image {} does not exist", path.display()).into());
+            }
+        }
+        if self.overlay.text_height.is_nan()
+            || self.overlay.text_height <= 0.
+            || self.overlay.text_height > 1.
+        {
+            return Err(format!(
+                "overlay.text_height must be between 0 and 1, got {}",
+                self.overlay.text_height
+            )
+            .into());
+        }
+        if StrftimeItems::new(&self.overlay.date_format).any(|item| item == Item::Error) {
+            return Err(format!(
+                "overlay.date_format is not a valid strftime format: {:?}",
+                self.overlay.date_format
+            )
+            .into());
+        }
+        self.validate_filters()?;
+        self.validate_chroma_key()?;
+        self.validate_faces()?;
+        self.validate_props()?;
+        self.validate_best_shot()?;
+        self.validate_print()?;
+        if self.output.root.as_os_str().is_empty() {
+            return Err("output.root must not be empty".into());
+        }
+        if self.output.event.is_empty()
+            || self.output.event == "."
+            || self.output.event == ".."
+            || self.output.event.contains(['/', '\\'])
+        {
+            return Err(format!(
+                "output.event must be a plain directory name, got {:?}",
+                self.output.event
+            )
+            .into());
+        }
+        if !(1..=100).contains(&self.output.quality) {
+            return Err(format!(
+                "output.quality must be between 1 and 100, got {}",
+                self.output.quality
+            )
+            .into());
+        }
+        if self.output.png_compression > 9 {
+            return Err(format!(
+                "output.png_compression must be between 0 and 9, got {}",
+                self.output.png_compression
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    fn validate_filters(&self) -> Result<(), Box<dyn Error>> {
+        if self.filters.available.is_empty() {
+            return Err("filters.available must contain at least one filter".into());
+        }
+        for (i, filter) in self.filters.custom.iter().enumerate() {
+            if filter.name.is_empty() {
+                return Err("filters.custom names must not be empty".into());
+            }
+            if self.filters.custom[..i]
+                .iter()
+                .any(|other| other.name == filter.name)
+            {
+                return Err(format!("filters.custom contains {:?} twice", filter.name).into());
+            }
+            if filter.steps.is_empty() {
+                return Err(format!("filter {:?} has no steps", filter.name).into());
+            }
+            for step in &filter.steps {
+                let (value, range, field) = match step {
+                    FilterStep::Grayscale => continue,
+                    FilterStep::Sepia { strength } => (*strength, 0.0..=1., "sepia strength"),
+                    FilterStep::Contrast {
+                        contrast,
+                        brightness,
+                    } => {
+                        if brightness.is_nan() || !(-255.0..=255.).contains(brightness) {
+                            return Err(format!(
+                                "filter {:?}: brightness must be between -255 and 255, got {brightness}",
+                                filter.name
+                            )
+                            .into());
+                        }
+                        (*contrast, 0.0..=10., "contrast")
+                    }
+                    FilterStep::Vignette { strength } => (*strength, 0.0..=1., "vignette strength"),
+                    FilterStep::Blur { sigma } => (*sigma, 0.0..=0.1, "blur sigma"),
+                    FilterStep::Lut { path } => {
+                        if !path.is_file() {
+                            return Err(format!(
+                                "filter {:?}: LUT {} does not exist",
+                                filter.name,
+                                path.display()
+                            )
+                            .into());
+                        }
+                        continue;
+                    }
+                };
+                if value.is_nan() || !range.contains(&value) {
+                    return Err(format!(
+                        "filter {:?}: {field} must be between {} and {}, got {value}",
+                        filter.name,
+                        range.start(),
+                        range.end()
+                    )
+                    .into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_chroma_key(&self) -> Result<(), Box<dyn Error>> {
+        let chroma_key = &self.chroma_key;
+        if !chroma_key.enabled {
+            return Ok(());
+        }
+        if chroma_key.backgrounds.is_empty() {
+            return Err("chroma_key.backgrounds must not be empty".into());
+        }
+        for path in &chroma_key.backgrounds {
+            if !path.is_file() {
+                return Err(
+                    format!("chroma_key background {} does not exist", path.display()).into(),
+                );
+            }
+        }
+        if chroma_key.tolerance.is_nan() || !(0.0..=180.).contains(&chroma_key.tolerance) {
+            return Err(format!(
+                "chroma_key.tolerance must be between 0 and 180, got {}",
+                chroma_key.tolerance
+            )
+            .into());
+        }
+        if chroma_key.spill.is_nan() || !(0.0..=1.).contains(&chroma_key.spill) {
+            return Err(format!(
+                "chroma_key.spill must be between 0 and 1, got {}",
+                chroma_key.spill
+            )
+            .into());
+        }
+        if chroma_key.feather.is_nan() || !(0.0..=0.1).contains(&chroma_key.feather) {
+            return Err(format!(
+                "chroma_key.feather must be between 0 and 0.1, got {}",
+                chroma_key.feather
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    fn validate_faces(&self) -> Result<(), Box<dyn Error>> {
+        let faces = &self.faces;
+        if !faces.enabled {
+            return Ok(());
+        }
+        if !faces.cascade.is_file() {
+            return Err(format!("faces.cascade {} does not exist", faces.cascade.display()).into());
+        }
+        if !(80..=1920).contains(&faces.detection_width) {
+            return Err(format!(
+                "faces.detection_width must be between 80 and 1920, got {}",
+                faces.detection_width
+            )
+            .into());
+        }
+        if faces.interval_secs.is_nan() || !(0.0..=10.).contains(&faces.interval_secs) {
+            return Err(format!(
+                "faces.interval_secs must be between 0 and 10, got {}",
+                faces.interval_secs
+            )
+            .into());
+        }
+        if faces.min_face_size.is_nan() || faces.min_face_size <= 0. || faces.min_face_size > 1. {
+            return Err(format!(
+                "faces.min_face_size must be between 0 and 1, got {}",
+                faces.min_face_size
+            )
+            .into());
+        }
+        if faces.auto_trigger_secs.is_nan() || !(0.0..=60.).contains(&faces.auto_trigger_secs) {
+            return Err(format!(
+                "faces.auto_trigger_secs must be between 0 and 60, got {}",
+                faces.auto_trigger_secs
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    fn validate_props(&self) -> Result<(), Box<dyn Error>> {
+        let props = &self.props;
+        if !props.enabled {
+            return Ok(());
+        }
+        if !self.faces.enabled {
+            return Err("props.enabled needs faces.enabled".into());
+        }
+        if props.items.is_empty() {
+            return Err("props.items must contain at least one prop".into());
+        }
+        for (i, prop) in props.items.iter().enumerate() {
+            if prop.name.is_empty() {
+                return Err("props.items names must not be empty".into());
+            }
+            if props.items[..i].iter().any(|other| other.name == prop.name) {
+                return Err(format!("props.items contains {:?} twice", prop.name).into());
+            }
+            if !prop.image.is_file() {
+                return Err(format!(
+                    "prop {:?}: image {} does not exist",
+                    prop.name,
+                    prop.image.display()
+                )
+                .into());
+            }
+            if prop.scale.is_nan() || prop.scale <= 0. || prop.scale > 10. {
+                return Err(format!(
+                    "prop {:?}: scale must be between 0 and 10, got {}",
+                    prop.name, prop.scale
+                )
+                .into());
+            }
+            if prop
+                .pivot
+                .iter()
+                .chain(&prop.offset)
+                .any(|value| !value.is_finite())
+            {
+                return Err(
+                    format!("prop {:?}: pivot and offset must be finite", prop.name).into(),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_best_shot(&self) -> Result<(), Box<dyn Error>> {
+        let best_shot = &self.best_shot;
+        if !best_shot.enabled {
+            return Ok(());
+        }
+        if !(2..=10).contains(&best_shot.frames) {
+            return Err(format!(
+                "best_shot.frames must be between 2 and 10, got {}",
+                best_shot.frames
+            )
+            .into());
+        }
+        if best_shot.interval_secs.is_nan() || !(0.0..=1.).contains(&best_shot.interval_secs) {
+            return Err(format!(
+                "best_shot.interval_secs must be between 0 and 1, got {}",
+                best_shot.interval_secs
+            )
+            .into());
+        }
+        if self.faces.enabled && !best_shot.eye_cascade.is_file() {
+            return Err(format!(
+                "best_shot.eye_cascade {} does not exist",
+                best_shot.eye_cascade.display()
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    fn validate_print(&self) -> Result<(), Box<dyn Error>> {
+        let print = &self.print;
+        if !print.enabled {
+            return Ok(());
+        }
+        IppPrinter::new(&print.printer_uri).map_err(|e| format!("print.printer_uri: {e}"))?;
+        if print.paper == Paper::Custom {
+            for (name, inches) in [
+                ("print.paper_width_in", print.paper_width_in),
+                ("print.paper_height_in", print.paper_height_in),
+            ] {
+                if inches.is_nan() || !(1.0..=20.).contains(&inches) {
+                    return Err(format!("{name} must be between 1 and 20, got {inches}").into());
+                }
+            }
+        }
+        if !(72..=1200).contains(&print.dpi) {
+            return Err(format!("print.dpi must be between 72 and 1200, got {}", print.dpi).into());
+        }
+        if print.bleed_in.is_nan() || !(0.0..=0.5).contains(&print.bleed_in) {
+            return Err(format!(
+                "print.bleed_in must be between 0 and 0.5, got {}",
+                print.bleed_in
+            )
+            .into());
+        }
+        let (width, height) = print.template_size_in();
+        if print.margin_in.is_nan()
+            || print.margin_in < 0.
+            || 2. * print.margin_in >= width.min(height)
+        {
+            return Err(format!(
+                "print.margin_in must be at least 0 and leave room on the {width}x{height} page, got {}",
+                print.margin_in
+            )
+            .into());
+        }
+        for (i, cell) in print.cells.iter().enumerate() {
+            let name = format!("print.cells[{i}]");
+            check_page_rect(&name, cell.x, cell.y, cell.width, cell.height)?;
+            if let Some(rotation) = cell.rotation {
+                if rotation % 90 != 0 || rotation >= 360 {
+                    return Err(format!(
+                        "{name}.rotation must be 0, 90, 180 or 270, got {rotation}"
+                    )
+                    .into());
+                }
+            }
+        }
+        for (i, text) in print.texts.iter().enumerate() {
+            let name = format!("print.texts[{i}]");
+            check_page_rect(&name, text.x, text.y, text.width, text.height)?;
+            if text.text.is_empty() {
+                return Err(format!("{name}.text must not be empty").into());
+            }
+        }
+        if let Some(logo) = &print.logo {
+            check_page_rect("print.logo", logo.x, logo.y, logo.width, logo.height)?;
+            if !logo.image.is_file() {
+                return Err(
+                    format!("print.logo.image {} does not exist", logo.image.display()).into(),
+                );
+            }
+        }
+        if print.spool_dir.as_os_str().is_empty() {
+            return Err("print.spool_dir must not be empty".into());
+        }
+        if print.max_attempts == 0 {
+            return Err("print.max_attempts must be greater than 0".into());
+        }
+        check_secs("print.retry_secs", print.retry_secs)?;
+        if self.video.enabled || self.animation.enabled {
+            return Err("print.enabled can not be combined with videos or animations".into());
+        }
+        Ok(())
+    }
+
+    fn validate_video(&self) -> Result<(), Box<dyn Error>> {
+        let video = &self.video;
+        if video.duration_secs.is_nan() || video.duration_secs <= 0. || video.duration_secs > 600. {
+            return Err(format!(
+                "video.duration_secs must be between 0 and 600, got {}",
+                video.duration_secs
+            )
+            .into());
+        }
+        if video.fps.is_nan() || video.fps < 1. || video.fps > 120. {
+            return Err(format!("video.fps must be between 1 and 120, got {}", video.fps).into());
+        }
+        if video.fourcc.chars().count() != 4 || !video.fourcc.is_ascii() {
+            return Err(format!(
+                "video.fourcc must be four ASCII characters, got {:?}",
+                video.fourcc
+            )
+            .into());
+        }
+        if video.extension.is_empty() || !video.extension.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            return Err(format!(
+                "video.extension must be a plain file extension, got {:?}",
+                video.extension
+            )
+            .into());
+        }
+        if video.enabled && (self.animation.enabled || self.strip.shots > 1) {
+            return Err(
+                "video.enabled can not be combined with animation.enabled or strip.shots > 1"
+                    .into(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Longest time a capture may take before it is considered failed, includes the recording and burst time
+    pub fn capture_timeout(&self) -> Duration {
+        let mut timeout = self.timing.capture_timeout();
+        if self.best_shot.enabled {
+            timeout += self.best_shot.interval() * self.best_shot.frames;
+        }
+        if self.animation.enabled {
+            timeout += self.animation.interval() * self.animation.frames;
+        }
+        if self.video.enabled {
+            timeout += self.video.duration();
+        }
+        timeout
+    }
+
+    fn validate_strip(&self) -> Result<(), Box<dyn Error>> {
+        let strip = &self.strip;
+        if strip.shots == 0 {
+            return Err("strip.shots must be greater than 0".into());
+        }
+        check_secs("strip.shot_countdown_secs", strip.shot_countdown_secs)?;
+        let (width, height) = strip.size();
+        check_size("strip", width, height)?;
+        if let Some(path) = &strip.background {
+            if !path.is_file() {
+                return Err(format!("strip.background {} does not exist", path.display()).into());
+            }
+        }
+        if strip.caption.is_some() && strip.caption_height >= height {
+            return Err(format!(
+                "strip.caption_height must be smaller than the strip height {height}, got {}",
+                strip.caption_height
+            )
+            .into());
+        }
+        if strip.layout == StripLayout::Custom && strip.cells.is_empty() {
+            return Err("strip.cells must not be empty with the custom layout".into());
+        }
+        for (i, cell) in strip.cells.iter().enumerate() {
+            check_size(&format!("strip.cells[{i}]"), cell.width, cell.height)?;
+            if cell.x as u64 + cell.width as u64 > width as u64
+                || cell.y as u64 + cell.height as u64 > height as u64
+            {
+                return Err(
+                    format!("strip.cells[{i}] is outside of the {width}x{height} strip").into(),
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_size(name: &str, width: u32, height: u32) -> Result<(), Box<dyn Error>> {
+    if width == 0 || height == 0 || width > i32::MAX as u32 || height > i32::MAX as u32 {
+        return Err(format!(
+            "{name} size must be positive and fit into an i32, got {width}x{height}"
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Checks a rectangle given as fractions of the page
+fn check_page_rect(
+    name: &str,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+) -> Result<(), Box<dyn Error>> {
+    let inside =
+        |start: f32, length: f32| start >= 0. && length > 0. && start + length <= 1. + f32::EPSILON;
+    if !inside(x, width) || !inside(y, height) {
+        return Err(format!(
+            "{name} must be inside the page, as fractions from 0 to 1, got x {x}, y {y}, width {width}, height {height}"
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn check_secs(name: &str, secs: f32) -> Result<(), Box<dyn Error>> {
+    if !secs.is_finite() || !(0.0..=MAX_SECS).contains(&secs) {
+        return Err(format!(
+            "{name} must be a number of seconds between 0 and {MAX_SECS}, got {secs}"
+        )
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = include_str!("../kiosk.example.toml");
+
+    fn with_timing(timing: &str) -> String {
+        format!("[timing]\n{timing}\n")
+    }
+
+    #[test]
+    fn example_matches_the_defaults() {
+        let example = KioskConfig::from_toml(EXAMPLE).unwrap();
+        assert_eq!(
+            format!("{example:?}"),
+            format!("{:?}", KioskConfig::default())
+        );
+        KioskConfig::default().validate().unwrap();
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let error = KioskConfig::from_toml(&with_timing("countdwon_secs = 3.0")).unwrap_err();
+        assert!(error.to_string().contains("countdwon_secs"), "{error}");
+        assert!(KioskConfig::from_toml("[timeing]\ncountdown_secs = 3.0\n").is_err());
+    }
+
+    #[test]
+    fn durations_must_be_between_zero_and_a_day() {
+        assert!(check_secs("t", 0.).is_ok());
+        assert!(check_secs("t", 2.5).is_ok());
+        assert!(check_secs("t", MAX_SECS).is_ok());
+        for secs in [-1., -0.001, f32::NAN, f32::INFINITY, MAX_SECS + 1.] {
+            assert!(check_secs("t", secs).is_err(), "{secs} was accepted");
+        }
+        for timing in [
+            "countdown_secs = -1.0",
+            "presenting_secs = nan",
+            "capture_timeout_secs = 90000.0",
+        ] {
+            assert!(
+                KioskConfig::from_toml(&with_timing(timing)).is_err(),
+                "{timing}"
+            );
+        }
+    }
+
+    #[test]
+    fn capture_timeout_must_not_be_zero() {
+        let error = KioskConfig::from_toml(&with_timing("capture_timeout_secs = 0.0")).unwrap_err();
+        assert!(
+            error.to_string().contains("capture_timeout_secs"),
+            "{error}"
+        );
+        KioskConfig::from_toml(&with_timing("capture_timeout_secs = 0.5")).unwrap();
+    }
+}
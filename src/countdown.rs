@@ -0,0 +1,109 @@
+use std::time::Duration;
+
+use crate::config::CountdownConfig;
+use crate::raylib::color::Color;
+use crate::raylib::*;
+
+/// Part of every second in which the number shrinks from its pop-in size to normal size
+const POP_IN: f32 = 0.25;
+/// Part of every second in which the number fades out
+const FADE_OUT: f32 = 0.2;
+/// Size of the number when it appears, relative to its normal size
+const POP_SCALE: f32 = 1.6;
+
+/// Draws the countdown centered on the screen.
+/// `elapsed` is the time since the countdown started, `total` its full length.
+pub fn draw_countdown(
+    elapsed: Duration,
+    total: Duration,
+    config: &CountdownConfig,
+    screen_size: Vector2,
+) {
+    let remaining = (total.as_secs_f32() - elapsed.as_secs_f32()).max(0.);
+    let center = screen_size / 2.;
+
+    // Shrink the ring on screens too small for the configured radius
+    let outer_radius = config
+        .ring_radius
+        .min(f32::min(screen_size.0, screen_size.1) / 2. * 0.9);
+    let inner_radius = (outer_radius - config.ring_thickness).max(0.);
+    draw_ring(
+        center,
+        inner_radius,
+        outer_radius,
+        0.,
+        360.,
+        72,
+        Color::from(config.ring_background),
+    );
+    if total > Duration::ZERO {
+        let progress = remaining / total.as_secs_f32();
+        // Starts at 12 o'clock and shrinks clockwise
+        draw_ring(
+            center,
+            inner_radius,
+            outer_radius,
+            -90.,
+            -90. + 360. * progress,
+            72,
+            Color::from(config.ring_color),
+        );
+    }
+
+    if remaining <= config.smile_secs {
+        let shown_for = config.smile_secs - remaining;
+        let scale = pop_scale(shown_for / config.smile_secs.max(f32::EPSILON));
+        draw_centered_text(
+            &config.smile_text,
+            center,
+            config.font_size as f32 * 0.6 * scale,
+            Color::from(config.text_color),
+        );
+        return;
+    }
+
+    let number = remaining.ceil();
+    // Progress through the current second from 0 to 1
+    let second_progress = 1. - (remaining - (number - 1.));
+    let alpha = if second_progress > 1. - FADE_OUT {
+        (1. - second_progress) / FADE_OUT
+    } else {
+        1.
+    };
+    draw_centered_text(
+        &format!("{}", number as u32),
+        center,
+        config.font_size as f32 * pop_scale(second_progress),
+        Color::from(config.text_color).fade(alpha),
+    );
+}
+
+/// Draws which shot of a multi-shot session is counted down to at the top of the screen
+pub fn draw_shot_label(shot: u32, shots: u32, config: &CountdownConfig, screen_size: Vector2) {
+    let font_size = (config.font_size as f32 * 0.3).max(20.);
+    draw_centered_text(
+        &format!("Photo {} of {}", shot + 1, shots),
+        Vector2(screen_size.0 / 2., font_size),
+        font_size,
+        Color::from(config.text_color),
+    );
+}
+
+/// Scale that eases from `POP_SCALE` down to 1 during the first `POP_IN` of `progress`
+fn pop_scale(progress: f32) -> f32 {
+    let t = (progress / POP_IN).clamp(0., 1.);
+    let eased = 1. - (1. - t) * (1. - t);
+    POP_SCALE + (1. - POP_SCALE) * eased
+}
+
+fn draw_centered_text(text: &str, center: Vector2, font_size: f32, color: Color) {
+    let font_size = font_size.round().max(1.) as i32;
+    let width = measure_text(text, font_size);
+    draw_text(
+        text,
+        (center.0 - width as f32 / 2.).round() as i32,
+        (center.1 - font_size as f32 / 2.).round() as i32,
+        font_size,
+        color,
+    );
+}
@@ -0,0 +1,73 @@
+use std::error::Error;
+
+use opencv::core::{Mat, Point, Rect, Scalar, Size};
+use opencv::imgproc::{get_text_size, put_text, FONT_HERSHEY_DUPLEX, LINE_AA};
+use opencv::prelude::*;
+
+use crate::camera::resized;
+
+/// Converts a `0xRRGGBBAA` color into a BGR scalar, alpha is ignored
+pub fn bgr_scalar(color: u32) -> Scalar {
+    let [r, g, b, _] = color.to_be_bytes();
+    Scalar::new(b as f64, g as f64, r as f64, 0.)
+}
+
+/// Converts a `0xRRGGBBAA` color into a BGRA scalar
+pub fn bgra_scalar(color: u32) -> Scalar {
+    let [r, g, b, a] = color.to_be_bytes();
+    Scalar::new(b as f64, g as f64, r as f64, a as f64)
+}
+
+/// Crops the center of `image` to the aspect ratio of `size` and scales it to `size`
+pub fn cover(image: &Mat, size: Size) -> Result<Mat, Box<dyn Error>> {
+    let scale = f64::max(
+        size.width as f64 / image.cols() as f64,
+        size.height as f64 / image.rows() as f64,
+    );
+    let crop_width = ((size.width as f64 / scale).round() as i32).min(image.cols());
+    let crop_height = ((size.height as f64 / scale).round() as i32).min(image.rows());
+    let crop = Rect::new(
+        (image.cols() - crop_width) / 2,
+        (image.rows() - crop_height) / 2,
+        crop_width,
+        crop_height,
+    );
+    let cropped = Mat::roi(image, crop)?.try_clone()?;
+    resized(&cropped, size)
+}
+
+/// Draws `text` centered in `rect`, scaled down until it fits
+pub fn draw_caption(
+    image: &mut Mat,
+    text: &str,
+    rect: Rect,
+    color: Scalar,
+) -> Result<(), Box<dyn Error>> {
+    let thickness = 2;
+    let mut baseline = 0;
+    let unit = get_text_size(text, FONT_HERSHEY_DUPLEX, 1., thickness, &mut baseline)?;
+    if unit.width <= 0 || unit.height <= 0 {
+        return Ok(());
+    }
+    let scale = f64::min(
+        rect.width as f64 * 0.9 / unit.width as f64,
+        rect.height as f64 * 0.6 / unit.height as f64,
+    );
+    let size = get_text_size(text, FONT_HERSHEY_DUPLEX, scale, thickness, &mut baseline)?;
+    let origin = Point::new(
+        rect.x + (rect.width - size.width) / 2,
+        rect.y + (rect.height + size.height) / 2,
+    );
+    put_text(
+        image,
+        text,
+        origin,
+        FONT_HERSHEY_DUPLEX,
+        scale,
+        color,
+        thickness,
+        LINE_AA,
+        false,
+    )?;
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use opencv::core::{Mat, Rect, Size, Vector};
+use opencv::imgproc::{cvt_color, equalize_hist, COLOR_BGR2GRAY};
+use opencv::objdetect::CascadeClassifier;
+use opencv::prelude::*;
+
+use crate::camera::resized;
+use crate::config::FacesConfig;
+
+/// Detections may miss a face for this long before the faces count as gone
+const MISSING_GRACE: Duration = Duration::from_secs(1);
+
+/// Haar cascade face detector working on downscaled frames
+pub struct FaceDetector {
+    classifier: CascadeClassifier,
+    width: i32,
+    min_neighbors: i32,
+    min_face_size: f32,
+}
+
+impl FaceDetector {
+    /// Loads the cascade, returns `None` if face detection is disabled
+    pub fn from_config(config: &FacesConfig) -> Result<Option<Self>, Box<dyn Error>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let path = config.cascade.to_string_lossy();
+        let classifier = CascadeClassifier::new(&path)?;
+        if classifier.empty()? {
+            return Err(format!("Could not load face cascade {path}").into());
+        }
+        Ok(Some(Self {
+            classifier,
+            width: config.detection_width as i32,
+            min_neighbors: config.min_neighbors as i32,
+            min_face_size: config.min_face_size,
+        }))
+    }
+
+    /// Faces in a BGR frame, in coordinates of the frame
+    pub fn detect(&mut self, frame: &Mat) -> Result<Vec<Rect>, Box<dyn Error>> {
+        let scale = (self.width as f32 / frame.cols() as f32).min(1.);
+        let size = Size::new(
+            (frame.cols() as f32 * scale).round() as i32,
+            (frame.rows() as f32 * scale).round() as i32,
+        );
+        let small = if scale < 1. {
+            resized(frame, size)?
+        } else {
+            frame.clone()
+        };
+        let mut gray = Mat::default();
+        cvt_color(&small, &mut gray, COLOR_BGR2GRAY, 0)?;
+        let mut equalized = Mat::default();
+        equalize_hist(&gray, &mut equalized)?;
+
+        let min_size = (size.width as f32 * self.min_face_size).round() as i32;
+        let mut faces = Vector::<Rect>::new();
+        self.classifier.detect_multi_scale(
+            &equalized,
+            &mut faces,
+            1.1,
+            self.min_neighbors,
+            0,
+            Size::new(min_size, min_size),
+            Size::default(),
+        )?;
+        Ok(faces
+            .iter()
+            .map(|face| {
+                Rect::new(
+                    (face.x as f32 / scale).round() as i32,
+                    (face.y as f32 / scale).round() as i32,
+                    (face.width as f32 / scale).round() as i32,
+                    (face.height as f32 / scale).round() as i32,
+                )
+            })
+            .collect())
+    }
+}
+
+/// Tracks since when faces are in frame, tolerating single missed detections
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Presence {
+    since: Option<Instant>,
+    last_seen: Option<Instant>,
+}
+
+impl Presence {
+    pub fn update(&mut self, now: Instant, faces: usize) {
+        if faces > 0 {
+            self.since.get_or_insert(now);
+            self.last_seen = Some(now);
+        } else if matches!(self.last_seen, Some(last_seen) if now - last_seen > MISSING_GRACE) {
+            *self = Self::default();
+        }
+    }
+
+    /// When the faces that are in frame right now appeared, `None` if nobody is in frame
+    pub fn since(&self) -> Option<Instant> {
+        self.since
+    }
+}
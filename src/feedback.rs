@@ -0,0 +1,172 @@
+use std::time::{Duration, Instant};
+
+use crate::raylib::color::{Color, WHITE};
+use crate::raylib::*;
+
+/// Degrees the processing spinner turns per second
+const SPINNER_SPEED: f32 = 360.;
+const SPINNER_RADIUS: f32 = 36.;
+/// Share of the screen width and height the print preview may take
+const PRINT_PREVIEW_SIZE: f32 = 0.3;
+const PRINT_PREVIEW_MARGIN: f32 = 24.;
+const PRINT_PREVIEW_FRAME: f32 = 4.;
+
+/// Full-screen white flash fading out over `duration` after the still was exposed
+pub fn draw_flash(exposed_at: Option<Instant>, duration: Duration, screen_size: Vector2) {
+    let Some(exposed_at) = exposed_at else {
+        return;
+    };
+    let elapsed = exposed_at.elapsed();
+    if duration.is_zero() || elapsed >= duration {
+        return;
+    }
+    let alpha = 1. - elapsed.as_secs_f32() / duration.as_secs_f32();
+    draw_rectangle(
+        0,
+        0,
+        screen_size.0 as i32,
+        screen_size.1 as i32,
+        WHITE.fade(alpha),
+    );
+}
+
+/// Spinner with a "Processing..." label, `elapsed` drives the rotation
+pub fn draw_processing(elapsed: Duration, screen_size: Vector2) {
+    let center = screen_size / 2.;
+    let start_angle = elapsed.as_secs_f32() * SPINNER_SPEED % 360.;
+    draw_ring(
+        center,
+        SPINNER_RADIUS - 8.,
+        SPINNER_RADIUS,
+        0.,
+        360.,
+        48,
+        Color::from(0x00000060),
+    );
+    draw_ring(
+        center,
+        SPINNER_RADIUS - 8.,
+        SPINNER_RADIUS,
+        start_angle,
+        start_angle + 90.,
+        24,
+        WHITE,
+    );
+
+    let text = "Processing...";
+    let font_size = 30;
+    let width = measure_text(text, font_size);
+    draw_text(
+        text,
+        (center.0 - width as f32 / 2.).round() as i32,
+        (center.1 + SPINNER_RADIUS + 16.).round() as i32,
+        font_size,
+        WHITE,
+    );
+}
+
+/// Blinking "REC" indicator and a bar with the remaining recording time
+pub fn draw_recording(elapsed: Duration, duration: Duration, screen_size: Vector2) {
+    let red = Color::from(0xE62937FF);
+    // Blinks once per second
+    if elapsed.as_millis() % 1000 < 600 {
+        draw_ring(Vector2(36., 36.), 0., 14., 0., 360., 36, red);
+    }
+    draw_text("REC", 60, 22, 30, WHITE);
+
+    let remaining = duration.saturating_sub(elapsed);
+    let text = format!("{}s", remaining.as_secs_f32().ceil() as u32);
+    let width = measure_text(&text, 30);
+    draw_text(&text, screen_size.0 as i32 - width - 22, 22, 30, WHITE);
+
+    let progress = if duration.is_zero() {
+        1.
+    } else {
+        (elapsed.as_secs_f32() / duration.as_secs_f32()).min(1.)
+    };
+    let bar_height = 10;
+    let top = screen_size.1 as i32 - bar_height;
+    draw_rectangle(
+        0,
+        top,
+        screen_size.0 as i32,
+        bar_height,
+        Color::from(0x00000060),
+    );
+    draw_rectangle(
+        0,
+        top,
+        (screen_size.0 * (1. - progress)).round() as i32,
+        bar_height,
+        red,
+    );
+}
+
+/// Banner at the top of the screen, shown for `duration` after `since`
+pub fn draw_warning(text: &str, since: Option<Instant>, duration: Duration, screen_size: Vector2) {
+    let Some(since) = since else {
+        return;
+    };
+    if since.elapsed() >= duration {
+        return;
+    }
+    draw_banner(text, Color::from(0xE62937C0), screen_size);
+}
+
+/// Banner at the top of the screen while print jobs are waiting
+pub fn draw_printing(queued: usize, screen_size: Vector2) {
+    if queued == 0 {
+        return;
+    }
+    // The default font has no ellipsis
+    draw_banner(
+        &format!("Printing... ({queued} in queue)"),
+        Color::from(0x0052ACC0),
+        screen_size,
+    );
+}
+
+/// Thumbnail of the page as it will come out of the printer, at the right edge of the screen
+pub fn draw_print_preview(preview: &Texture, screen_size: Vector2) {
+    let scale = f32::min(
+        screen_size.0 * PRINT_PREVIEW_SIZE / preview.width as f32,
+        screen_size.1 * PRINT_PREVIEW_SIZE / preview.height as f32,
+    );
+    let width = preview.width as f32 * scale;
+    let height = preview.height as f32 * scale;
+    let x = screen_size.0 - width - PRINT_PREVIEW_MARGIN;
+    let y = (screen_size.1 - height) / 2.;
+    preview.draw_ex(Vector2(x, y), 0., scale, WHITE);
+    draw_rectangle_lines(
+        Rectangle {
+            x: x - PRINT_PREVIEW_FRAME,
+            y: y - PRINT_PREVIEW_FRAME,
+            width: width + 2. * PRINT_PREVIEW_FRAME,
+            height: height + 2. * PRINT_PREVIEW_FRAME,
+        },
+        PRINT_PREVIEW_FRAME,
+        WHITE,
+    );
+    let font_size = 20;
+    draw_text(
+        "Print preview",
+        x.round() as i32,
+        (y - PRINT_PREVIEW_FRAME - 8.).round() as i32 - font_size,
+        font_size,
+        WHITE,
+    );
+}
+
+fn draw_banner(text: &str, color: Color, screen_size: Vector2) {
+    let font_size = 40;
+    let height = font_size + 32;
+    draw_rectangle(0, 0, screen_size.0 as i32, height, color);
+    let width = measure_text(text, font_size);
+    draw_text(
+        text,
+        ((screen_size.0 - width as f32) / 2.).round() as i32,
+        16,
+        font_size,
+        WHITE,
+    );
+}
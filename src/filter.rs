@@ -0,0 +1,727 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use opencv::core::{add_weighted, transform, Mat, Size, BORDER_DEFAULT};
+use opencv::imgproc::{cvt_color, gaussian_blur, COLOR_BGR2GRAY, COLOR_GRAY2BGR};
+use opencv::prelude::*;
+
+use crate::config::{FilterConfig, FilterStep};
+use crate::raylib::{Shader, ShaderMode, Texture, Vector2};
+
+/// Blur sigma of the built-in blur relative to the image width, the preview shader is tuned to it
+const BLUR_SIGMA: f32 = 0.004;
+
+const BUILTIN_FILTERS: [&str; 5] = ["none", "bw", "sepia", "blur", "vintage"];
+
+/// Declarations shared by all filter shaders, the DRM build only has OpenGL ES 2
+#[cfg(target_arch = "arm")]
+const SHADER_HEADER: &str = "#version 100
+precision mediump float;
+varying vec2 fragTexCoord;
+varying vec4 fragColor;
+uniform sampler2D texture0;
+uniform vec4 colDiffuse;
+#define TEXTURE texture2D
+#define OUT gl_FragColor
+";
+#[cfg(not(target_arch = "arm"))]
+const SHADER_HEADER: &str = "#version 330
+in vec2 fragTexCoord;
+in vec4 fragColor;
+uniform sampler2D texture0;
+uniform vec4 colDiffuse;
+out vec4 finalColor;
+#define TEXTURE texture
+#define OUT finalColor
+";
+
+/// Look that is applied to the saved photo and shown in the preview
+#[derive(Debug, Clone)]
+pub struct PhotoFilter {
+    pub name: String,
+    /// Shown on the filter button
+    pub label: String,
+    steps: Vec<Step>,
+    /// Fragment shader showing the same look in the preview
+    shader: Option<&'static str>,
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+    Grayscale,
+    Sepia(f32),
+    Contrast { contrast: f32, brightness: f32 },
+    Vignette(f32),
+    Blur(f32),
+    Lut(Arc<Lut>),
+}
+
+impl PhotoFilter {
+    fn builtin(name: &str) -> Option<Self> {
+        let (label, steps, shader) = match name {
+            "none" => ("No filter", vec![], None),
+            "bw" => (
+                "B&W",
+                vec![Step::Grayscale],
+                Some(include_str!("shader/grayscale.fs")),
+            ),
+            "sepia" => (
+                "Sepia",
+                vec![Step::Sepia(1.)],
+                Some(include_str!("shader/sepia.fs")),
+            ),
+            "blur" => (
+                "Blur",
+                vec![Step::Blur(BLUR_SIGMA)],
+                Some(include_str!("shader/blur.fs")),
+            ),
+            "vintage" => (
+                "Vintage",
+                vec![
+                    Step::Sepia(0.5),
+                    // Multiplies by 0.85 and adds 0.08 like the shader
+                    Step::Contrast {
+                        contrast: 0.85,
+                        brightness: 1.2,
+                    },
+                    Step::Vignette(0.5),
+                ],
+                Some(include_str!("shader/vintage.fs")),
+            ),
+            _ => return None,
+        };
+        Some(Self {
+            name: name.to_string(),
+            label: label.to_string(),
+            steps,
+            shader,
+        })
+    }
+
+    /// Whether the filter leaves the image unchanged
+    pub fn is_identity(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Applies all steps to a BGR image
+    pub fn apply(&self, image: &Mat) -> Result<Mat, Box<dyn Error>> {
+        let mut image = image.clone();
+        for step in &self.steps {
+            image = step.apply(&image)?;
+        }
+        Ok(image)
+    }
+}
+
+impl Step {
+    fn apply(&self, image: &Mat) -> Result<Mat, Box<dyn Error>> {
+        match self {
+            Step::Grayscale => grayscale(image),
+            Step::Sepia(strength) => {
+                let toned = sepia(image)?;
+                let mut mixed = Mat::default();
+                let strength = *strength as f64;
+                add_weighted(image, 1. - strength, &toned, strength, 0., &mut mixed, -1)?;
+                Ok(mixed)
+            }
+            Step::Contrast {
+                contrast,
+                brightness,
+            } => {
+                let mut adjusted = Mat::default();
+                let offset = 128. * (1. - contrast) + brightness;
+                image.convert_to(&mut adjusted, -1, *contrast as f64, offset as f64)?;
+                Ok(adjusted)
+            }
+            Step::Vignette(strength) => vignette(image, *strength),
+            Step::Blur(sigma) => {
+                let sigma = (image.cols() as f32 * sigma) as f64;
+                if sigma <= 0. {
+                    return Ok(image.clone());
+                }
+                let mut blurred = Mat::default();
+                gaussian_blur(
+                    image,
+                    &mut blurred,
+                    Size::new(0, 0),
+                    sigma,
+                    sigma,
+                    BORDER_DEFAULT,
+                )?;
+                Ok(blurred)
+            }
+            Step::Lut(lut) => lut.apply(image),
+        }
+    }
+}
+
+fn grayscale(image: &Mat) -> Result<Mat, Box<dyn Error>> {
+    let mut gray = Mat::default();
+    cvt_color(image, &mut gray, COLOR_BGR2GRAY, 0)?;
+    let mut bgr = Mat::default();
+    cvt_color(&gray, &mut bgr, COLOR_GRAY2BGR, 0)?;
+    Ok(bgr)
+}
+
+fn sepia(image: &Mat) -> Result<Mat, Box<dyn Error>> {
+    // Rows produce B, G and R from B, G and R
+    let kernel = Mat::from_slice_2d(&[
+        [0.131f32, 0.534, 0.272],
+        [0.168, 0.686, 0.349],
+        [0.189, 0.769, 0.393],
+    ])?;
+    let mut toned = Mat::default();
+    transform(image, &mut toned, &kernel)?;
+    Ok(toned)
+}
+
+fn vignette(image: &Mat, strength: f32) -> Result<Mat, Box<dyn Error>> {
+    let mut darkened = image.try_clone()?;
+    let (width, height) = (darkened.cols() as usize, darkened.rows() as usize);
+    let channels = darkened.channels() as usize;
+    let pixels = darkened.data_bytes_mut()?;
+    for y in 0..height {
+        let dy = (y as f32 + 0.5) / height as f32 - 0.5;
+        for x in 0..width {
+            let dx = (x as f32 + 0.5) / width as f32 - 0.5;
+            // 1 in the corners
+            let distance = (dx * dx + dy * dy).sqrt() * std::f32::consts::SQRT_2;
+            let factor = 1. - strength * smoothstep(0.4, 1., distance);
+            let start = (y * width + x) * channels;
+            for value in &mut pixels[start..start + channels] {
+                *value = (*value as f32 * factor).round() as u8;
+            }
+        }
+    }
+    Ok(darkened)
+}
+
+/// Same as GLSL `smoothstep`
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0., 1.);
+    t * t * (3. - 2. * t)
+}
+
+/// 3D color lookup table from a .cube file
+#[derive(Debug)]
+pub struct Lut {
+    size: usize,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+    /// RGB output, red changes fastest
+    table: Vec<[f32; 3]>,
+}
+
+impl Lut {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read LUT {}: {e}", path.display()))?;
+        Self::parse(&content).map_err(|e| format!("Invalid LUT {}: {e}", path.display()).into())
+    }
+
+    pub fn parse(content: &str) -> Result<Self, Box<dyn Error>> {
+        let mut size = None;
+        let mut domain_min = [0.; 3];
+        let mut domain_max = [1.; 3];
+        let mut table = Vec::new();
+        for (number, line) in content.lines().enumerate() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let values = |words: &[&str]| -> Result<[f32; 3], Box<dyn Error>> {
+                let values = words
+                    .iter()
+                    .map(|word| word.parse::<f32>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| format!("line {}: {e}", number + 1))?;
+                values
+                    .try_into()
+                    .map_err(|_| format!("line {}: expected 3 values", number + 1).into())
+            };
+            match words.as_slice() {
+                [] => {}
+                [comment, ..] if comment.starts_with('#') => {}
+                ["LUT_3D_SIZE", value] => {
+                    size = Some(
+                        value
+                            .parse::<usize>()
+                            .map_err(|e| format!("line {}: {e}", number + 1))?,
+                    );
+                }
+                ["LUT_1D_SIZE", ..] => return Err("1D LUTs are not supported".into()),
+                ["DOMAIN_MIN", rest @ ..] => domain_min = values(rest)?,
+                ["DOMAIN_MAX", rest @ ..] => domain_max = values(rest)?,
+                // TITLE and vendor specific keywords
+                [keyword, ..] if keyword.starts_with(char::is_alphabetic) => {}
+                entry => table.push(values(entry)?),
+            }
+        }
+
+        let size = size.ok_or("LUT_3D_SIZE is missing")?;
+        if !(2..=256).contains(&size) {
+            return Err(format!("LUT_3D_SIZE must be between 2 and 256, got {size}").into());
+        }
+        if table.len() != size.pow(3) {
+            return Err(format!(
+                "expected {} entries for LUT_3D_SIZE {size}, got {}",
+                size.pow(3),
+                table.len()
+            )
+            .into());
+        }
+        if (0..3).any(|i| domain_max[i] <= domain_min[i]) {
+            return Err("DOMAIN_MAX must be greater than DOMAIN_MIN".into());
+        }
+        Ok(Self {
+            size,
+            domain_min,
+            domain_max,
+            table,
+        })
+    }
+
+    /// Maps every BGR pixel through the table with trilinear interpolation
+    fn apply(&self, image: &Mat) -> Result<Mat, Box<dyn Error>> {
+        // Lower table index and weight of the upper one for each 8 bit value, per RGB channel
+        let positions: [Vec<(usize, f32)>; 3] = std::array::from_fn(|channel| {
+            let (min, max) = (self.domain_min[channel], self.domain_max[channel]);
+            (0..256)
+                .map(|value| {
+                    let normalized = ((value as f32 / 255. - min) / (max - min)).clamp(0., 1.);
+                    let position = normalized * (self.size - 1) as f32;
+                    let lower = (position.floor() as usize).min(self.size - 2);
+                    (lower, position - lower as f32)
+                })
+                .collect()
+        });
+
+        let mut graded = image.try_clone()?;
+        let channels = graded.channels() as usize;
+        if channels < 3 {
+            return Err("LUTs can only be applied to color images".into());
+        }
+        let size = self.size;
+        for pixel in graded.data_bytes_mut()?.chunks_exact_mut(channels) {
+            let (r, fr) = positions[0][pixel[2] as usize];
+            let (g, fg) = positions[1][pixel[1] as usize];
+            let (b, fb) = positions[2][pixel[0] as usize];
+            let mut rgb = [0.; 3];
+            for (db, wb) in [(0, 1. - fb), (1, fb)] {
+                for (dg, wg) in [(0, 1. - fg), (1, fg)] {
+                    for (dr, wr) in [(0, 1. - fr), (1, fr)] {
+                        let entry = self.table[(b + db) * size * size + (g + dg) * size + r + dr];
+                        let weight = wb * wg * wr;
+                        for (sum, value) in rgb.iter_mut().zip(entry) {
+                            *sum += value * weight;
+                        }
+                    }
+                }
+            }
+            for (channel, value) in [rgb[2], rgb[1], rgb[0]].into_iter().enumerate() {
+                pixel[channel] = (value * 255.).round().clamp(0., 255.) as u8;
+            }
+        }
+        Ok(graded)
+    }
+}
+
+/// Built-in filters and the custom ones from the config, by name
+pub struct FilterRegistry {
+    filters: Vec<PhotoFilter>,
+}
+
+impl FilterRegistry {
+    /// Builds the custom filters and loads their LUTs
+    pub fn from_config(config: &FilterConfig) -> Result<Self, Box<dyn Error>> {
+        let mut filters: Vec<PhotoFilter> = BUILTIN_FILTERS
+            .iter()
+            .filter_map(|name| PhotoFilter::builtin(name))
+            .collect();
+        for custom in &config.custom {
+            if BUILTIN_FILTERS.contains(&custom.name.as_str()) {
+                return Err(
+                    format!("Custom filter {:?} shadows a built-in filter", custom.name).into(),
+                );
+            }
+            let steps = custom
+                .steps
+                .iter()
+                .map(|step| {
+                    Ok(match step {
+                        FilterStep::Grayscale => Step::Grayscale,
+                        FilterStep::Sepia { strength } => Step::Sepia(*strength),
+                        FilterStep::Contrast {
+                            contrast,
+                            brightness,
+                        } => Step::Contrast {
+                            contrast: *contrast,
+                            brightness: *brightness,
+                        },
+                        FilterStep::Vignette { strength } => Step::Vignette(*strength),
+                        FilterStep::Blur { sigma } => Step::Blur(*sigma),
+                        FilterStep::Lut { path } => Step::Lut(Arc::new(Lut::load(path)?)),
+                    })
+                })
+                .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+            filters.push(PhotoFilter {
+                name: custom.name.clone(),
+                label: custom.label.clone().unwrap_or_else(|| custom.name.clone()),
+                steps,
+                shader: None,
+            });
+        }
+        Ok(Self { filters })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PhotoFilter> {
+        self.filters.iter().find(|filter| filter.name == name)
+    }
+
+    /// Looks up the filters guests can choose from, in order
+    pub fn select(&self, names: &[String]) -> Result<Vec<PhotoFilter>, Box<dyn Error>> {
+        names
+            .iter()
+            .map(|name| {
+                self.get(name).cloned().ok_or_else(|| {
+                    let known: Vec<&str> = self
+                        .filters
+                        .iter()
+                        .map(|filter| filter.name.as_str())
+                        .collect();
+                    format!(
+                        "Unknown filter {name:?}, known filters are {}",
+                        known.join(", ")
+                    )
+                    .into()
+                })
+            })
+            .collect()
+    }
+}
+
+/// Compiled shaders for the live preview
+pub struct PreviewFilters {
+    shaders: Vec<(String, Shader)>,
+}
+
+impl PreviewFilters {
+    /// Compiles the shaders of `filters`, the window has to be initialized
+    pub fn load(filters: &[PhotoFilter]) -> Self {
+        let mut shaders = Vec::new();
+        for filter in filters {
+            let Some(fragment) = filter.shader else {
+                continue;
+            };
+            match Shader::new_from_memory(None, Some(&format!("{SHADER_HEADER}{fragment}"))) {
+                Ok(shader) => shaders.push((filter.name.clone(), shader)),
+                Err(e) => eprintln!("Could not load the {} preview filter: {e}", filter.label),
+            }
+        }
+        Self { shaders }
+    }
+
+    /// Whether the preview frames have to be filtered on the CPU because there is no shader for the filter
+    pub fn needs_cpu(&self, filter: &PhotoFilter) -> bool {
+        !filter.is_identity() && !self.shaders.iter().any(|(name, _)| *name == filter.name)
+    }
+
+    /// Draws with the filter's shader until the returned guard is dropped, `None` draws unfiltered
+    pub fn begin(&self, filter: &PhotoFilter, texture: &Texture) -> Option<ShaderMode<'_>> {
+        let (_, shader) = self.shaders.iter().find(|(name, _)| *name == filter.name)?;
+        // The shader takes 5x5 samples with a sigma of sqrt(2) samples
+        let step = BLUR_SIGMA / std::f32::consts::SQRT_2;
+        shader.set_vec2(
+            "blurStep",
+            Vector2(step, step * texture.width as f32 / texture.height as f32),
+        );
+        Some(shader.begin())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opencv::core::{Scalar, CV_8UC3};
+
+    /// BGR image of `width` columns from its pixels
+    fn image(width: i32, pixels: &[[u8; 3]]) -> Mat {
+        let height = pixels.len() as i32 / width;
+        let mut image =
+            Mat::new_size_with_default(Size::new(width, height), CV_8UC3, Scalar::all(0.)).unwrap();
+        image
+            .data_bytes_mut()
+            .unwrap()
+            .copy_from_slice(pixels.as_flattened());
+        image
+    }
+
+    fn pixels(image: &Mat) -> Vec<[u8; 3]> {
+        image
+            .data_bytes()
+            .unwrap()
+            .chunks_exact(3)
+            .map(|pixel| [pixel[0], pixel[1], pixel[2]])
+            .collect()
+    }
+
+    fn apply(step: Step, input: &Mat) -> Vec<[u8; 3]> {
+        pixels(&step.apply(input).unwrap())
+    }
+
+    /// Blue, green, red, white and a dark gray
+    fn swatches() -> Mat {
+        image(
+            5,
+            &[
+                [255, 0, 0],
+                [0, 255, 0],
+                [0, 0, 255],
+                [255, 255, 255],
+                [10, 20, 30],
+            ],
+        )
+    }
+
+    #[test]
+    fn grayscale_uses_luma_weights() {
+        assert_eq!(
+            apply(Step::Grayscale, &swatches()),
+            [
+                [29, 29, 29],
+                [150, 150, 150],
+                [76, 76, 76],
+                [255, 255, 255],
+                [22, 22, 22]
+            ]
+        );
+    }
+
+    #[test]
+    fn sepia_tones_and_mixes() {
+        let gray = image(2, &[[100, 100, 100], [255, 255, 255]]);
+        assert_eq!(
+            apply(Step::Sepia(1.), &gray),
+            [[94, 120, 135], [239, 255, 255]]
+        );
+        assert_eq!(apply(Step::Sepia(0.), &gray), pixels(&gray));
+        assert_eq!(apply(Step::Sepia(0.5), &gray)[0], [97, 110, 118]);
+    }
+
+    #[test]
+    fn contrast_scales_around_mid_gray() {
+        let input = image(3, &[[50, 100, 200], [128, 128, 128], [0, 0, 255]]);
+        assert_eq!(
+            apply(
+                Step::Contrast {
+                    contrast: 2.,
+                    brightness: 0.
+                },
+                &input
+            ),
+            [[0, 72, 255], [128, 128, 128], [0, 0, 255]]
+        );
+        assert_eq!(
+            apply(
+                Step::Contrast {
+                    contrast: 1.,
+                    brightness: -10.
+                },
+                &input
+            ),
+            [[40, 90, 190], [118, 118, 118], [0, 0, 245]]
+        );
+    }
+
+    #[test]
+    fn vignette_darkens_the_corners() {
+        let input = image(4, &[[200, 200, 200]; 16]);
+        let rows: Vec<u8> = apply(Step::Vignette(1.), &input)
+            .iter()
+            .map(|pixel| pixel[0])
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                75, 165, 165, 75, //
+                165, 200, 200, 165, //
+                165, 200, 200, 165, //
+                75, 165, 165, 75,
+            ]
+        );
+        assert_eq!(apply(Step::Vignette(0.), &input), pixels(&input));
+    }
+
+    #[test]
+    fn blur_spreads_a_point_symmetrically() {
+        let mut point = [[0; 3]; 81];
+        point[40] = [255; 3];
+        // Sigma of 1 pixel on a 9 pixel wide image
+        let blurred = apply(Step::Blur(1. / 9.), &image(9, &point));
+        let at = |x: usize, y: usize| blurred[y * 9 + x][0];
+        assert!(at(4, 4) < 255 && at(4, 4) > at(3, 4));
+        assert_eq!(at(3, 4), at(5, 4));
+        assert_eq!(at(3, 4), at(4, 3));
+        assert_eq!(at(3, 3), at(5, 5));
+        assert!(at(3, 4) > at(2, 4) && at(2, 4) > at(0, 4));
+        let total: u32 = blurred.iter().map(|pixel| pixel[0] as u32).sum();
+        assert!((245..=265).contains(&total), "{total}");
+
+        let flat = image(9, &[[90, 120, 150]; 81]);
+        assert_eq!(apply(Step::Blur(1. / 9.), &flat), pixels(&flat));
+        assert_eq!(apply(Step::Blur(0.), &image(9, &point)), point);
+    }
+
+    /// Size 2 LUT, `entry` maps the red, green and blue index of each corner to its RGB output
+    fn cube(entry: impl Fn(usize, usize, usize) -> [f32; 3]) -> String {
+        let mut cube = String::from("LUT_3D_SIZE 2\n");
+        for b in 0..2 {
+            for g in 0..2 {
+                for r in 0..2 {
+                    let [r, g, b] = entry(r, g, b);
+                    cube.push_str(&format!("{r} {g} {b}\n"));
+                }
+            }
+        }
+        cube
+    }
+
+    fn lut(content: &str) -> Step {
+        Step::Lut(Arc::new(Lut::parse(content).unwrap()))
+    }
+
+    #[test]
+    fn identity_lut_keeps_the_colors() {
+        let identity = cube(|r, g, b| [r as f32, g as f32, b as f32]);
+        assert_eq!(apply(lut(&identity), &swatches()), pixels(&swatches()));
+    }
+
+    #[test]
+    fn lut_hits_the_corners_exactly() {
+        // Every corner gets its own gray, red index changes fastest
+        let corners = cube(|r, g, b| [(b * 4 + g * 2 + r) as f32 / 10.; 3]);
+        let mut input = Vec::new();
+        let mut expected = Vec::new();
+        for b in 0..2 {
+            for g in 0..2 {
+                for r in 0..2 {
+                    input.push([b as u8 * 255, g as u8 * 255, r as u8 * 255]);
+                    let value = ((b * 4 + g * 2 + r) as f32 / 10. * 255.).round() as u8;
+                    expected.push([value; 3]);
+                }
+            }
+        }
+        assert_eq!(apply(lut(&corners), &image(8, &input)), expected);
+    }
+
+    #[test]
+    fn lut_interpolates_trilinearly_between_the_corners() {
+        let inverted = cube(|r, g, b| [1. - r as f32, 1. - g as f32, 1. - b as f32]);
+        assert_eq!(
+            apply(lut(&inverted), &image(2, &[[128, 64, 192], [0, 255, 1]])),
+            [[127, 191, 63], [255, 0, 254]]
+        );
+
+        // Only the white corner is red, so red is the product of the three weights
+        let white_corner = cube(|r, g, b| [(r * g * b) as f32, 0., 0.]);
+        let output = apply(lut(&white_corner), &image(1, &[[128, 128, 128]]));
+        // (128 / 255)^3 * 255
+        assert_eq!(output, [[0, 0, 32]]);
+    }
+
+    #[test]
+    fn lut_parser_reads_size_domain_and_skips_comments() {
+        let content = "# Created by hand\nTITLE \"test\"\n\nLUT_3D_SIZE 2\nDOMAIN_MIN 0 0 0\nDOMAIN_MAX 2 2 2\n# entries\n0 0 0\n2 0 0\n0 2 0\n2 2 0\n0 0 2\n2 0 2\n0 2 2\n2 2 2\n";
+        let parsed = Lut::parse(content).unwrap();
+        assert_eq!(parsed.size, 2);
+        assert_eq!(parsed.domain_min, [0.; 3]);
+        assert_eq!(parsed.domain_max, [2.; 3]);
+        assert_eq!(parsed.table.len(), 8);
+        assert_eq!(parsed.table[1], [2., 0., 0.]);
+        // 255 lies halfway through the 0 to 2 domain, where the table reads 1
+        assert_eq!(
+            apply(lut(content), &image(2, &[[255, 255, 255], [0, 0, 0]])),
+            [[255, 255, 255], [0, 0, 0]]
+        );
+    }
+
+    #[test]
+    fn lut_parser_rejects_broken_files() {
+        let error = |content: &str| Lut::parse(content).unwrap_err().to_string();
+        let entries = "0 0 0\n".repeat(7);
+        assert!(error(&format!("LUT_3D_SIZE 2\n{entries}"))
+            .contains("expected 8 entries for LUT_3D_SIZE 2, got 7"));
+        assert!(error(&entries).contains("LUT_3D_SIZE is missing"));
+        assert!(error("LUT_3D_SIZE 1\n0 0 0\n").contains("between 2 and 256"));
+        assert!(error("LUT_1D_SIZE 2\n").contains("1D LUTs"));
+        assert!(error("LUT_3D_SIZE 2\n0 0\n").contains("line 2: expected 3 values"));
+        assert!(error("LUT_3D_SIZE 2\n0 x 0\n").starts_with("line 2:"));
+        let inverted_domain = format!(
+            "LUT_3D_SIZE 2\nDOMAIN_MIN 1 1 1\nDOMAIN_MAX 0 0 0\n{}",
+            "0 0 0\n".repeat(8)
+        );
+        assert!(error(&inverted_domain).contains("DOMAIN_MAX"));
+    }
+
+    /// What `shader/vintage.fs` computes for one RGB pixel at the texture coordinate `uv`
+    fn vintage_shader(rgb: [f32; 3], uv: [f32; 2]) -> [f32; 3] {
+        let dot = |weights: [f32; 3]| (0..3).map(|i| rgb[i] * weights[i]).sum::<f32>().min(1.);
+        let sepia = [
+            dot([0.393, 0.769, 0.189]),
+            dot([0.349, 0.686, 0.168]),
+            dot([0.272, 0.534, 0.131]),
+        ];
+        let distance =
+            ((uv[0] - 0.5).powi(2) + (uv[1] - 0.5).powi(2)).sqrt() * std::f32::consts::SQRT_2;
+        let vignette = 1. - 0.5 * smoothstep(0.4, 1., distance);
+        std::array::from_fn(|i| {
+            let faded = (rgb[i] * 0.5 + sepia[i] * 0.5) * 0.85 + 0.08;
+            (faded * vignette).clamp(0., 1.)
+        })
+    }
+
+    #[test]
+    fn vintage_matches_its_preview_shader() {
+        let (width, height) = (8, 6);
+        let input: Vec<[u8; 3]> = (0..width * height)
+            .map(|i| {
+                let i = i as u32;
+                [
+                    (i * 37 % 256) as u8,
+                    (i * 91 % 256) as u8,
+                    (i * 53 % 256) as u8,
+                ]
+            })
+            .collect();
+        let vintage = PhotoFilter::builtin("vintage").unwrap();
+        let output = pixels(&vintage.apply(&image(width as i32, &input)).unwrap());
+
+        for (i, (bgr, filtered)) in input.iter().zip(&output).enumerate() {
+            let uv = [
+                ((i % width) as f32 + 0.5) / width as f32,
+                ((i / width) as f32 + 0.5) / height as f32,
+            ];
+            let rgb = [bgr[2], bgr[1], bgr[0]].map(|value| value as f32 / 255.);
+            let [r, g, b] = vintage_shader(rgb, uv).map(|value| value * 255.);
+            for (cpu, gpu) in filtered.iter().zip([b, g, r]) {
+                // The CPU rounds to 8 bit after every step
+                assert!(
+                    (*cpu as f32 - gpu).abs() <= 2.,
+                    "pixel {i}: CPU {filtered:?}, shader {:?}",
+                    [b, g, r]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn registry_resolves_names() {
+        let registry = FilterRegistry::from_config(&FilterConfig::default()).unwrap();
+        let selected = registry
+            .select(&["vintage".to_string(), "none".to_string()])
+            .unwrap();
+        assert_eq!(selected[0].label, "Vintage");
+        assert!(selected[1].is_identity());
+        let error = registry.select(&["nope".to_string()]).unwrap_err();
+        assert!(error.to_string().contains("none, bw, sepia, blur, vintage"));
+    }
+}
@@ -0,0 +1,581 @@
+use std::error::Error;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+const IPP_VERSION: [u8; 2] = [2, 0];
+const DEFAULT_PORT: u16 = 631;
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+const OPERATION_PRINT_JOB: u16 = 0x0002;
+const OPERATION_GET_JOB_ATTRIBUTES: u16 = 0x0009;
+
+const TAG_OPERATION_ATTRIBUTES: u8 = 0x01;
+const TAG_END_OF_ATTRIBUTES: u8 = 0x03;
+const TAG_INTEGER: u8 = 0x21;
+const TAG_ENUM: u8 = 0x23;
+const TAG_NAME: u8 = 0x42;
+const TAG_KEYWORD: u8 = 0x44;
+const TAG_URI: u8 = 0x45;
+const TAG_CHARSET: u8 = 0x47;
+const TAG_NATURAL_LANGUAGE: u8 = 0x48;
+const TAG_MIME_MEDIA_TYPE: u8 = 0x49;
+
+const USER_NAME: &str = "photo-kiosk";
+
+/// State of a job on the printer, RFC 8011 section 5.3.7
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobState {
+    Pending,
+    Held,
+    Processing,
+    Stopped,
+    Canceled,
+    Aborted,
+    Completed,
+}
+
+impl JobState {
+    fn from_enum(value: i32) -> Option<Self> {
+        Some(match value {
+            3 => JobState::Pending,
+            4 => JobState::Held,
+            5 => JobState::Processing,
+            6 => JobState::Stopped,
+            7 => JobState::Canceled,
+            8 => JobState::Aborted,
+            9 => JobState::Completed,
+            _ => return None,
+        })
+    }
+}
+
+/// Printer queue reachable over IPP without TLS, like a CUPS queue at `ipp://localhost:631/printers/name`
+#[derive(Debug, Clone)]
+pub struct IppPrinter {
+    uri: String,
+    /// `host:port` to connect to
+    address: String,
+    host: String,
+    path: String,
+}
+
+impl IppPrinter {
+    /// Accepts `ipp://` and `http://` URIs, the port defaults to 631
+    pub fn new(uri: &str) -> Result<Self, Box<dyn Error>> {
+        let rest = uri
+            .strip_prefix("ipp://")
+            .or_else(|| uri.strip_prefix("http://"))
+            .ok_or_else(|| format!("Printer URI must start with ipp:// or http://, got {uri:?}"))?;
+        let (host, path) = match rest.find('/') {
+            Some(slash) => rest.split_at(slash),
+            None => (rest, "/"),
+        };
+        if host.is_empty() {
+            return Err(format!("Printer URI has no host: {uri:?}").into());
+        }
+        // IPv6 hosts are bracketed and contain colons themselves
+        let has_port = host
+            .rfind(':')
+            .is_some_and(|colon| colon > host.rfind(']').unwrap_or(0));
+        let address = if has_port {
+            host.to_string()
+        } else {
+            format!("{host}:{DEFAULT_PORT}")
+        };
+        Ok(Self {
+            uri: uri.to_string(),
+            address,
+            host: host.to_string(),
+            path: path.to_string(),
+        })
+    }
+
+    /// Submits a JPEG document, returns the job id assigned by the printer
+    pub fn print_jpeg(&self, job_name: &str, jpeg: &[u8]) -> Result<i32, Box<dyn Error>> {
+        let mut request = self.request(OPERATION_PRINT_JOB, 1);
+        write_attribute(&mut request, TAG_NAME, "job-name", job_name.as_bytes());
+        write_attribute(
+            &mut request,
+            TAG_MIME_MEDIA_TYPE,
+            "document-format",
+            b"image/jpeg",
+        );
+        request.push(TAG_END_OF_ATTRIBUTES);
+        request.extend_from_slice(jpeg);
+
+        let response = self.send(&request)?;
+        match response.integer("job-id") {
+            Some(job_id) => Ok(job_id),
+            None => Err("Printer accepted the job without a job id".into()),
+        }
+    }
+
+    /// Asks the printer how far a job is
+    pub fn job_state(&self, job_id: i32) -> Result<JobState, Box<dyn Error>> {
+        let mut request = self.request(OPERATION_GET_JOB_ATTRIBUTES, 2);
+        write_attribute(&mut request, TAG_INTEGER, "job-id", &job_id.to_be_bytes());
+        write_attribute(
+            &mut request,
+            TAG_KEYWORD,
+            "requested-attributes",
+            b"job-state",
+        );
+        request.push(TAG_END_OF_ATTRIBUTES);
+
+        let response = self.send(&request)?;
+        let state = response
+            .integer("job-state")
+            .ok_or("Printer did not report the job state")?;
+        JobState::from_enum(state).ok_or_else(|| format!("Unknown job state {state}").into())
+    }
+
+    /// Header and the operation attributes every request starts with
+    fn request(&self, operation: u16, request_id: u32) -> Vec<u8> {
+        let mut request = Vec::new();
+        request.extend_from_slice(&IPP_VERSION);
+        request.extend_from_slice(&operation.to_be_bytes());
+        request.extend_from_slice(&request_id.to_be_bytes());
+        request.push(TAG_OPERATION_ATTRIBUTES);
+        write_attribute(&mut request, TAG_CHARSET, "attributes-charset", b"utf-8");
+        write_attribute(
+            &mut request,
+            TAG_NATURAL_LANGUAGE,
+            "attributes-natural-language",
+            b"en",
+        );
+        write_attribute(&mut request, TAG_URI, "printer-uri", self.uri.as_bytes());
+        write_attribute(
+            &mut request,
+            TAG_NAME,
+            "requesting-user-name",
+            USER_NAME.as_bytes(),
+        );
+        request
+    }
+
+    /// Posts the request over HTTP and parses the response, fails on unsuccessful status codes
+    fn send(&self, request: &[u8]) -> Result<Response, Box<dyn Error>> {
+        let mut stream = TcpStream::connect(&self.address)
+            .map_err(|e| format!("Could not connect to printer {}: {e}", self.address))?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/ipp\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.path,
+            self.host,
+            request.len()
+        )?;
+        stream.write_all(request)?;
+        stream.flush()?;
+
+        let mut raw = Vec::new();
+        stream.read_to_end(&mut raw)?;
+        let body = http_body(&raw)?;
+        let response = Response::parse(&body)?;
+        // 0x0000 to 0x00ff are successful, some with ignored or substituted attributes
+        if response.status > 0x00ff {
+            let message = response
+                .text("status-message")
+                .unwrap_or_else(|| "no message".to_string());
+            return Err(format!(
+                "Printer answered with status 0x{:04x}: {message}",
+                response.status
+            )
+            .into());
+        }
+        Ok(response)
+    }
+}
+
+fn write_attribute(buf: &mut Vec<u8>, tag: u8, name: &str, value: &[u8]) {
+    buf.push(tag);
+    buf.extend_from_slice(&(name.len() as u16).to_be_bytes());
+    buf.extend_from_slice(name.as_bytes());
+    buf.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    buf.extend_from_slice(value);
+}
+
+/// Body of a raw HTTP response, fails on non 200 responses
+fn http_body(raw: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let header_end = raw
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or("Printer sent an incomplete HTTP response")?;
+    let head = String::from_utf8_lossy(&raw[..header_end]);
+    let body = &raw[header_end + 4..];
+
+    let mut lines = head.lines();
+    let status_line = lines.next().unwrap_or_default();
+    let status = status_line.split_whitespace().nth(1).unwrap_or_default();
+    if status != "200" {
+        return Err(format!("Printer answered with HTTP {status_line:?}").into());
+    }
+    let chunked = lines.any(|line| {
+        let line = line.to_ascii_lowercase();
+        line.starts_with("transfer-encoding:") && line.contains("chunked")
+    });
+    if chunked {
+        dechunk(body)
+    } else {
+        Ok(body.to_vec())
+    }
+}
+
+/// Joins the chunks of a chunked transfer encoded body
+fn dechunk(mut body: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut data = Vec::new();
+    loop {
+        let line_end = body
+            .windows(2)
+            .position(|window| window == b"\r\n")
+            .ok_or("Printer sent a broken chunk")?;
+        let size = String::from_utf8_lossy(&body[..line_end]);
+        let size = size.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16)
+            .map_err(|_| format!("Printer sent a broken chunk size {size:?}"))?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Ok(data);
+        }
+        let chunk = body.get(..size).ok_or("Printer sent a truncated chunk")?;
+        data.extend_from_slice(chunk);
+        body = body.get(size + 2..).unwrap_or_default();
+    }
+}
+
+/// Status code and the attributes of an IPP response, without their groups
+struct Response {
+    status: u16,
+    attributes: Vec<(String, u8, Vec<u8>)>,
+}
+
+impl Response {
+    fn parse(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut reader = Reader { data, position: 0 };
+        reader.take(2)?; // version
+        let status = u16::from_be_bytes(reader.take(2)?.try_into()?);
+        reader.take(4)?; // request id
+
+        let mut attributes: Vec<(String, u8, Vec<u8>)> = Vec::new();
+        loop {
+            let tag = reader.take(1)?[0];
+            if tag == TAG_END_OF_ATTRIBUTES {
+                break;
+            }
+            // Group delimiters only separate the attributes
+            if tag < 0x10 {
+                continue;
+            }
+            let name_length = u16::from_be_bytes(reader.take(2)?.try_into()?) as usize;
+            let name = String::from_utf8_lossy(reader.take(name_length)?).into_owned();
+            let value_length = u16::from_be_bytes(reader.take(2)?.try_into()?) as usize;
+            let value = reader.take(value_length)?.to_vec();
+            // Additional values of a multi valued attribute come without a name
+            let name = match (name.is_empty(), attributes.last()) {
+                (true, Some((previous, _, _))) => previous.clone(),
+                _ => name,
+            };
+            attributes.push((name, tag, value));
+        }
+        Ok(Self { status, attributes })
+    }
+
+    fn value(&self, name: &str) -> Option<(u8, &[u8])> {
+        self.attributes
+            .iter()
+            .find(|(attribute, _, _)| attribute == name)
+            .map(|(_, tag, value)| (*tag, value.as_slice()))
+    }
+
+    fn integer(&self, name: &str) -> Option<i32> {
+        match self.value(name)? {
+            (TAG_INTEGER | TAG_ENUM, value) => Some(i32::from_be_bytes(value.try_into().ok()?)),
+            _ => None,
+        }
+    }
+
+    fn text(&self, name: &str) -> Option<String> {
+        self.value(name)
+            .map(|(_, value)| String::from_utf8_lossy(value).into_owned())
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], Box<dyn Error>> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + length)
+            .ok_or("Printer sent a truncated IPP response")?;
+        self.position += length;
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::sync::mpsc::{channel, Receiver};
+    use std::thread;
+
+    const TAG_JOB_ATTRIBUTES: u8 = 0x02;
+    const TAG_UNSUPPORTED_ATTRIBUTES: u8 = 0x05;
+
+    /// IPP printer on a local port that answers each connection with the next scripted reply
+    pub(crate) struct FakePrinter {
+        pub uri: String,
+        requests: Receiver<Vec<u8>>,
+    }
+
+    impl FakePrinter {
+        /// An empty reply closes the connection without answering, once all replies are sent the port is closed
+        pub fn start(replies: Vec<Vec<u8>>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let uri = format!("ipp://{}/printers/test", listener.local_addr().unwrap());
+            let (tx, rx) = channel();
+            thread::spawn(move || {
+                for reply in replies {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let _ = tx.send(read_request(&mut stream));
+                    let _ = stream.write_all(&reply);
+                }
+            });
+            Self { uri, requests: rx }
+        }
+
+        /// IPP bodies of the requests received so far
+        pub fn requests(&self) -> Vec<Vec<u8>> {
+            self.requests.try_iter().collect()
+        }
+    }
+
+    /// Body of an HTTP request with a `Content-Length`
+    fn read_request(stream: &mut TcpStream) -> Vec<u8> {
+        let mut raw = Vec::new();
+        let mut buf = [0; 4096];
+        loop {
+            if let Some(end) = raw.windows(4).position(|window| window == b"\r\n\r\n") {
+                let head = String::from_utf8_lossy(&raw[..end]).to_ascii_lowercase();
+                let length: usize = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length:"))
+                    .and_then(|length| length.trim().parse().ok())
+                    .unwrap_or(0);
+                if raw.len() >= end + 4 + length {
+                    return raw[end + 4..end + 4 + length].to_vec();
+                }
+            }
+            match stream.read(&mut buf) {
+                Ok(0) | Err(_) => return Vec::new(),
+                Ok(read) => raw.extend_from_slice(&buf[..read]),
+            }
+        }
+    }
+
+    /// Operation of an IPP request
+    pub(crate) fn operation(request: &[u8]) -> u16 {
+        u16::from_be_bytes([request[2], request[3]])
+    }
+
+    pub(crate) fn ipp_response(status: u16, attributes: &[(u8, &str, &[u8])]) -> Vec<u8> {
+        let mut response = vec![2, 0];
+        response.extend_from_slice(&status.to_be_bytes());
+        response.extend_from_slice(&1u32.to_be_bytes());
+        response.push(TAG_OPERATION_ATTRIBUTES);
+        for (tag, name, value) in attributes {
+            write_attribute(&mut response, *tag, name, value);
+        }
+        response.push(TAG_END_OF_ATTRIBUTES);
+        response
+    }
+
+    pub(crate) fn http_ok(body: &[u8]) -> Vec<u8> {
+        let mut raw = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/ipp\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        )
+        .into_bytes();
+        raw.extend_from_slice(body);
+        raw
+    }
+
+    pub(crate) fn http_error() -> Vec<u8> {
+        b"HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\n\r\n".to_vec()
+    }
+
+    /// Reply to a Print-Job request
+    pub(crate) fn job_created(job_id: i32) -> Vec<u8> {
+        http_ok(&ipp_response(
+            0,
+            &[(TAG_INTEGER, "job-id", &job_id.to_be_bytes())],
+        ))
+    }
+
+    /// Reply to a Get-Job-Attributes request
+    pub(crate) fn job_in_state(state: i32) -> Vec<u8> {
+        http_ok(&ipp_response(
+            0,
+            &[(TAG_ENUM, "job-state", &state.to_be_bytes())],
+        ))
+    }
+
+    #[test]
+    fn uri_defaults_to_the_ipp_port() {
+        let printer = IppPrinter::new("ipp://localhost/printers/photo").unwrap();
+        assert_eq!(printer.address, "localhost:631");
+        assert_eq!(printer.path, "/printers/photo");
+        let printer = IppPrinter::new("http://[::1]:8631").unwrap();
+        assert_eq!(printer.address, "[::1]:8631");
+        assert_eq!(printer.path, "/");
+        assert_eq!(
+            IppPrinter::new("http://[::1]").unwrap().address,
+            "[::1]:631"
+        );
+        assert!(IppPrinter::new("ipps://localhost/printers/photo").is_err());
+        assert!(IppPrinter::new("ipp:///printers/photo").is_err());
+    }
+
+    #[test]
+    fn response_attributes_span_groups() {
+        let mut data = ipp_response(
+            0x0001,
+            &[
+                (TAG_CHARSET, "attributes-charset", b"utf-8"),
+                (TAG_NAME, "status-message", b"successful-ok-ignored"),
+            ],
+        );
+        // Replace the end tag with more groups
+        data.pop();
+        data.push(TAG_UNSUPPORTED_ATTRIBUTES);
+        write_attribute(&mut data, TAG_KEYWORD, "media", b"a4");
+        data.push(TAG_JOB_ATTRIBUTES);
+        write_attribute(&mut data, TAG_INTEGER, "job-id", &42i32.to_be_bytes());
+        write_attribute(&mut data, TAG_KEYWORD, "job-state-reasons", b"none");
+        write_attribute(&mut data, TAG_KEYWORD, "", b"job-printing");
+        data.push(TAG_END_OF_ATTRIBUTES);
+
+        let response = Response::parse(&data).unwrap();
+        assert_eq!(response.status, 0x0001);
+        assert_eq!(response.integer("job-id"), Some(42));
+        assert_eq!(response.text("media").as_deref(), Some("a4"));
+        assert_eq!(
+            response.text("status-message").as_deref(),
+            Some("successful-ok-ignored")
+        );
+        // Additional values are named after the attribute they belong to
+        let reasons: Vec<&[u8]> = response
+            .attributes
+            .iter()
+            .filter(|(name, _, _)| name == "job-state-reasons")
+            .map(|(_, _, value)| value.as_slice())
+            .collect();
+        assert_eq!(reasons, [&b"none"[..], b"job-printing"]);
+        // Only integers and enums are read as numbers
+        assert_eq!(response.integer("media"), None);
+        assert_eq!(response.integer("missing"), None);
+    }
+
+    #[test]
+    fn truncated_responses_are_rejected() {
+        let data = ipp_response(0, &[(TAG_ENUM, "job-state", &9i32.to_be_bytes())]);
+        for length in 0..data.len() {
+            let error = Response::parse(&data[..length]).err().unwrap();
+            assert!(error.to_string().contains("truncated"), "{length}: {error}");
+        }
+        assert!(Response::parse(&data).is_ok());
+    }
+
+    #[test]
+    fn http_body_reads_plain_and_chunked_bodies() {
+        assert_eq!(http_body(&http_ok(b"ipp")).unwrap(), b"ipp");
+        let chunked = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: Chunked\r\n\r\n4;name=value\r\nabcd\r\na\r\n0123456789\r\n0\r\n\r\n";
+        assert_eq!(http_body(chunked).unwrap(), b"abcd0123456789");
+
+        let error = http_body(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("404 Not Found"), "{error}");
+        assert!(http_body(b"HTTP/1.1 200 OK\r\nContent-Length: 3\r\n")
+            .unwrap_err()
+            .to_string()
+            .contains("incomplete"));
+    }
+
+    #[test]
+    fn broken_chunks_are_rejected() {
+        let error = |body: &[u8]| dechunk(body).unwrap_err().to_string();
+        assert!(error(b"zz\r\nabcd\r\n0\r\n\r\n").contains("broken chunk size \"zz\""));
+        assert!(error(b"10\r\nabcd\r\n0\r\n\r\n").contains("truncated chunk"));
+        assert!(error(b"4\r\nabcd\r\n").contains("broken chunk"));
+        assert!(error(b"4").contains("broken chunk"));
+        assert_eq!(dechunk(b"0\r\n\r\n").unwrap(), b"");
+    }
+
+    #[test]
+    fn print_jpeg_posts_the_document() {
+        let fake = FakePrinter::start(vec![job_created(17)]);
+        let printer = IppPrinter::new(&fake.uri).unwrap();
+        assert_eq!(printer.print_jpeg("0001.jpg", b"\xff\xd8jpeg").unwrap(), 17);
+
+        let requests = fake.requests();
+        assert_eq!(requests.len(), 1);
+        let request = &requests[0];
+        assert_eq!(operation(request), OPERATION_PRINT_JOB);
+        assert!(request
+            .windows(fake.uri.len())
+            .any(|window| window == fake.uri.as_bytes()));
+        assert!(request.windows(8).any(|window| window == b"0001.jpg"));
+        // The document follows the attributes
+        assert!(request.ends_with(&[TAG_END_OF_ATTRIBUTES, 0xff, 0xd8, b'j', b'p', b'e', b'g']));
+    }
+
+    #[test]
+    fn job_state_is_read_from_plain_and_chunked_responses() {
+        let body = ipp_response(0, &[(TAG_ENUM, "job-state", &5i32.to_be_bytes())]);
+        let mut chunked = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+        for chunk in body.chunks(7) {
+            chunked.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+            chunked.extend_from_slice(chunk);
+            chunked.extend_from_slice(b"\r\n");
+        }
+        chunked.extend_from_slice(b"0\r\n\r\n");
+
+        let fake = FakePrinter::start(vec![job_in_state(9), chunked, job_in_state(42)]);
+        let printer = IppPrinter::new(&fake.uri).unwrap();
+        assert_eq!(printer.job_state(3).unwrap(), JobState::Completed);
+        assert_eq!(printer.job_state(3).unwrap(), JobState::Processing);
+        assert!(printer
+            .job_state(3)
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown job state 42"));
+        let requests = fake.requests();
+        assert!(requests
+            .iter()
+            .all(|request| operation(request) == OPERATION_GET_JOB_ATTRIBUTES));
+    }
+
+    #[test]
+    fn unsuccessful_answers_are_errors() {
+        let not_found = http_ok(&ipp_response(
+            0x0406,
+            &[(TAG_NAME, "status-message", b"The printer is gone")],
+        ));
+        let fake = FakePrinter::start(vec![not_found, http_error(), Vec::new()]);
+        let printer = IppPrinter::new(&fake.uri).unwrap();
+        let error = |result: Result<i32, Box<dyn Error>>| result.unwrap_err().to_string();
+        let message = error(printer.print_jpeg("a", b"jpeg"));
+        assert!(message.contains("0x0406: The printer is gone"), "{message}");
+        assert!(error(printer.print_jpeg("a", b"jpeg")).contains("HTTP \"HTTP/1.1 500"));
+        // The connection closed before an answer came
+        assert!(error(printer.print_jpeg("a", b"jpeg")).contains("incomplete"));
+        assert_eq!(fake.requests().len(), 3);
+    }
+}
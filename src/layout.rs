@@ -0,0 +1,525 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use opencv::core::{
+    copy_make_border, hconcat, rotate, vconcat, Mat, Rect, Scalar, Size, Vector, BORDER_REFLECT,
+    CV_8UC3, ROTATE_180, ROTATE_90_CLOCKWISE, ROTATE_90_COUNTERCLOCKWISE,
+};
+use opencv::prelude::*;
+
+use crate::camera::load_image;
+use crate::config::{Paper, PrintConfig};
+use crate::draw::{bgr_scalar, cover, draw_caption};
+use crate::overlay::{blend_into, load_bgra};
+
+/// Resolution of the print preview on the review screen
+pub const PREVIEW_DPI: f32 = 50.;
+
+/// Rectangle as fractions of the page inside the margin as `[x, y, width, height]`
+type PageRect = [f32; 4];
+
+/// Where photos, texts and a logo go on the printed page
+#[derive(Clone)]
+pub struct PrintLayout {
+    /// Trimmed size in inches the layout is drawn on
+    template_in: (f32, f32),
+    /// Templates next to each other on the paper, two strips on 2x6 paper
+    copies: usize,
+    dpi: f32,
+    bleed_in: f32,
+    margin_in: f32,
+    background: Scalar,
+    /// Rotation in clockwise degrees, `None` turns photos to the orientation of the cell
+    cells: Vec<(PageRect, Option<u32>)>,
+    texts: Vec<(String, PageRect, Scalar)>,
+    /// BGRA
+    logo: Option<(Mat, PageRect)>,
+    event: String,
+    date_format: String,
+}
+
+impl PrintLayout {
+    /// Loads the logo, `date_format` is used for `{date}` in texts
+    pub fn from_config(
+        config: &PrintConfig,
+        event: &str,
+        date_format: &str,
+    ) -> Result<Self, Box<dyn Error>> {
+        let cells = if config.cells.is_empty() {
+            vec![([0., 0., 1., 1.], None)]
+        } else {
+            config
+                .cells
+                .iter()
+                .map(|cell| ([cell.x, cell.y, cell.width, cell.height], cell.rotation))
+                .collect()
+        };
+        let logo = match &config.logo {
+            Some(logo) => Some((
+                load_bgra(&logo.image)?,
+                [logo.x, logo.y, logo.width, logo.height],
+            )),
+            None => None,
+        };
+        Ok(Self {
+            template_in: config.template_size_in(),
+            copies: if config.paper == Paper::Strip2x6 {
+                2
+            } else {
+                1
+            },
+            dpi: config.dpi as f32,
+            bleed_in: config.bleed_in,
+            margin_in: config.margin_in,
+            background: bgr_scalar(config.background_color),
+            cells,
+            texts: config
+                .texts
+                .iter()
+                .map(|text| {
+                    (
+                        text.text.clone(),
+                        [text.x, text.y, text.width, text.height],
+                        bgr_scalar(text.color),
+                    )
+                })
+                .collect(),
+            logo,
+            event: event.to_string(),
+            date_format: date_format.to_string(),
+        })
+    }
+
+    /// Loads the images to print for a saved capture, the shots of a strip if the layout has cells for them
+    pub fn load_photos(&self, photo: &Path, shots: &[PathBuf]) -> Result<Vec<Mat>, Box<dyn Error>> {
+        if self.cells.len() > 1 && !shots.is_empty() {
+            shots.iter().map(|shot| load_image(shot)).collect()
+        } else {
+            Ok(vec![load_image(photo)?])
+        }
+    }
+
+    /// Print-ready BGR page at the configured DPI, including the bleed
+    pub fn render(&self, photos: &[Mat]) -> Result<Mat, Box<dyn Error>> {
+        let page = self.render_trimmed(photos, self.dpi)?;
+        let bleed = inches_to_px(self.bleed_in, self.dpi);
+        if bleed == 0 {
+            return Ok(page);
+        }
+        let mut bled = Mat::default();
+        copy_make_border(
+            &page,
+            &mut bled,
+            bleed,
+            bleed,
+            bleed,
+            bleed,
+            BORDER_REFLECT,
+            Scalar::default(),
+        )?;
+        Ok(bled)
+    }
+
+    /// Small BGR page as it looks after trimming, for the review screen
+    pub fn render_preview(&self, photos: &[Mat]) -> Result<Mat, Box<dyn Error>> {
+        self.render_trimmed(photos, PREVIEW_DPI)
+    }
+
+    /// Page without bleed at `dpi`, copies of the template are put along its short side
+    fn render_trimmed(&self, photos: &[Mat], dpi: f32) -> Result<Mat, Box<dyn Error>> {
+        let template = self.render_template(photos, dpi)?;
+        if self.copies == 1 {
+            return Ok(template);
+        }
+        let copies = Vector::<Mat>::from_iter((0..self.copies).map(|_| template.clone()));
+        let mut page = Mat::default();
+        if template.cols() < template.rows() {
+            hconcat(&copies, &mut page)?;
+        } else {
+            vconcat(&copies, &mut page)?;
+        }
+        Ok(page)
+    }
+
+    fn render_template(&self, photos: &[Mat], dpi: f32) -> Result<Mat, Box<dyn Error>> {
+        if photos.is_empty() {
+            return Err("Can not lay out a page without photos".into());
+        }
+        let size = Size::new(
+            inches_to_px(self.template_in.0, dpi),
+            inches_to_px(self.template_in.1, dpi),
+        );
+        let margin = inches_to_px(self.margin_in, dpi);
+        let area = Rect::new(
+            margin,
+            margin,
+            size.width - 2 * margin,
+            size.height - 2 * margin,
+        );
+        let mut page = Mat::new_size_with_default(size, CV_8UC3, self.background)?;
+
+        for ((rect, rotation), photo) in self.cells.iter().zip(photos.iter().cycle()) {
+            let cell = place(area, *rect);
+            if cell.width <= 0 || cell.height <= 0 {
+                continue;
+            }
+            let turned = turn(photo, *rotation, cell.size())?;
+            let filled = cover(&turned, cell.size())?;
+            let mut roi = Mat::roi_mut(&mut page, cell)?;
+            filled.copy_to(&mut roi)?;
+        }
+        if !self.texts.is_empty() {
+            let date = Local::now().format(&self.date_format).to_string();
+            for (text, rect, color) in &self.texts {
+                let text = text
+                    .replace("{event}", &self.event)
+                    .replace("{date}", &date);
+                draw_caption(&mut page, &text, place(area, *rect), *color)?;
+            }
+        }
+        if let Some((logo, rect)) = &self.logo {
+            let rect = contain(place(area, *rect), logo.size()?);
+            if rect.width > 0 && rect.height > 0 {
+                blend_into(&mut page, logo, rect)?;
+            }
+        }
+        Ok(page)
+    }
+}
+
+fn inches_to_px(inches: f32, dpi: f32) -> i32 {
+    (inches * dpi).round() as i32
+}
+
+/// Pixel rectangle of a fractional rectangle in `area`, neighbouring rectangles share their edges
+fn place(area: Rect, [x, y, width, height]: PageRect) -> Rect {
+    let left = area.x + (x * area.width as f32).round() as i32;
+    let top = area.y + (y * area.height as f32).round() as i32;
+    let right = area.x + ((x + width) * area.width as f32).round() as i32;
+    let bottom = area.y + ((y + height) * area.height as f32).round() as i32;
+    Rect::new(left, top, right - left, bottom - top)
+}
+
+/// Largest rectangle of the aspect ratio of `size` centered in `rect`
+fn contain(rect: Rect, size: Size) -> Rect {
+    let scale = f32::min(
+        rect.width as f32 / size.width as f32,
+        rect.height as f32 / size.height as f32,
+    );
+    let width = (size.width as f32 * scale).round() as i32;
+    let height = (size.height as f32 * scale).round() as i32;
+    Rect::new(
+        rect.x + (rect.width - width) / 2,
+        rect.y + (rect.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+/// Rotates the photo clockwise by `rotation` degrees, or to the orientation of `cell` if unset
+fn turn(photo: &Mat, rotation: Option<u32>, cell: Size) -> Result<Mat, Box<dyn Error>> {
+    let rotation = rotation.unwrap_or(
+        if (photo.cols() > photo.rows()) != (cell.width > cell.height) {
+            90
+        } else {
+            0
+        },
+    );
+    let code = match rotation {
+        90 => ROTATE_90_CLOCKWISE,
+        180 => ROTATE_180,
+        270 => ROTATE_90_COUNTERCLOCKWISE,
+        _ => return Ok(photo.clone()),
+    };
+    let mut rotated = Mat::default();
+    rotate(photo, &mut rotated, code)?;
+    Ok(rotated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PrintCellConfig;
+    use opencv::core::{Vec3b, CV_8UC1};
+
+    const RED: [u8; 3] = [0, 0, 255];
+    const GREEN: [u8; 3] = [0, 255, 0];
+    const BLUE: [u8; 3] = [255, 0, 0];
+    const WHITE: [u8; 3] = [255, 255, 255];
+
+    fn layout(config: &PrintConfig) -> PrintLayout {
+        PrintLayout::from_config(config, "", "%Y").unwrap()
+    }
+
+    /// Landscape BGR photo of one color
+    fn solid([b, g, r]: [u8; 3]) -> Mat {
+        Mat::new_size_with_default(
+            Size::new(30, 20),
+            CV_8UC3,
+            Scalar::new(b as f64, g as f64, r as f64, 0.),
+        )
+        .unwrap()
+    }
+
+    /// Landscape photo getting brighter to the right and greener downwards
+    fn gradient() -> Mat {
+        let mut photo = solid([0; 3]);
+        for y in 0..20 {
+            for x in 0..30 {
+                *photo.at_2d_mut::<Vec3b>(y, x).unwrap() =
+                    Vec3b::from([(x * 8) as u8, (y * 12) as u8, 100]);
+            }
+        }
+        photo
+    }
+
+    fn pixel(image: &Mat, x: i32, y: i32) -> [u8; 3] {
+        image.at_2d::<Vec3b>(y, x).unwrap().0
+    }
+
+    fn bytes(image: &Mat, rect: Rect) -> Vec<u8> {
+        Mat::roi(image, rect)
+            .unwrap()
+            .try_clone()
+            .unwrap()
+            .data_bytes()
+            .unwrap()
+            .to_vec()
+    }
+
+    /// Three cells stacked on a 2x6 strip inside a margin of 0.1 in
+    fn strip_config(dpi: u32) -> PrintConfig {
+        PrintConfig {
+            paper: Paper::Strip2x6,
+            landscape: false,
+            dpi,
+            margin_in: 0.1,
+            cells: (0..3)
+                .map(|i| PrintCellConfig {
+                    x: 0.,
+                    y: i as f32 / 3.,
+                    width: 1.,
+                    height: 1. / 3.,
+                    rotation: None,
+                })
+                .collect(),
+            ..PrintConfig::default()
+        }
+    }
+
+    #[test]
+    fn page_size_is_paper_times_dpi() {
+        let photos = [solid(RED)];
+        let size = |config: PrintConfig| layout(&config).render(&photos).unwrap().size().unwrap();
+        assert_eq!(size(PrintConfig::default()), Size::new(1800, 1200));
+        assert_eq!(
+            size(PrintConfig {
+                paper: Paper::Photo5x7,
+                landscape: false,
+                dpi: 100,
+                ..PrintConfig::default()
+            }),
+            Size::new(500, 700)
+        );
+        assert_eq!(
+            size(PrintConfig {
+                paper: Paper::Custom,
+                paper_width_in: 3.5,
+                paper_height_in: 5.,
+                dpi: 150,
+                ..PrintConfig::default()
+            }),
+            Size::new(750, 525)
+        );
+        // 0.1 in of bleed on every side
+        assert_eq!(
+            size(PrintConfig {
+                dpi: 100,
+                bleed_in: 0.1,
+                ..PrintConfig::default()
+            }),
+            Size::new(620, 420)
+        );
+    }
+
+    #[test]
+    fn bleed_mirrors_the_page_edges() {
+        let layout = layout(&PrintConfig {
+            dpi: 100,
+            bleed_in: 0.1,
+            ..PrintConfig::default()
+        });
+        let photos = [gradient()];
+        let page = layout.render_trimmed(&photos, 100.).unwrap();
+        let bled = layout.render(&photos).unwrap();
+        let (width, height) = (page.cols(), page.rows());
+        assert_eq!(
+            bytes(&bled, Rect::new(10, 10, width, height)),
+            page.data_bytes().unwrap()
+        );
+        // BORDER_REFLECT repeats the edge pixel, fedcba|abcdef
+        for i in 0..10 {
+            for y in [0, height / 2, height - 1] {
+                assert_eq!(pixel(&bled, 9 - i, y + 10), pixel(&page, i, y));
+                assert_eq!(
+                    pixel(&bled, width + 10 + i, y + 10),
+                    pixel(&page, width - 1 - i, y)
+                );
+            }
+            for x in [0, width / 2, width - 1] {
+                assert_eq!(pixel(&bled, x + 10, 9 - i), pixel(&page, x, i));
+                assert_eq!(
+                    pixel(&bled, x + 10, height + 10 + i),
+                    pixel(&page, x, height - 1 - i)
+                );
+            }
+            assert_eq!(pixel(&bled, 9 - i, 9 - i), pixel(&page, i, i));
+        }
+    }
+
+    #[test]
+    fn strips_are_placed_twice_on_4x6() {
+        let photos = [solid(RED), solid(GREEN), solid(BLUE)];
+        let page = layout(&strip_config(100)).render(&photos).unwrap();
+        assert_eq!(page.size().unwrap(), Size::new(400, 600));
+        // Both strips are the same, side by side
+        assert_eq!(
+            bytes(&page, Rect::new(0, 0, 200, 600)),
+            bytes(&page, Rect::new(200, 0, 200, 600))
+        );
+        // Inside the 10 px margin the cells split 580 px at 203 and 397
+        for x in [10, 100, 189, 210, 389] {
+            assert_eq!(pixel(&page, x, 9), WHITE);
+            assert_eq!(pixel(&page, x, 10), RED);
+            assert_eq!(pixel(&page, x, 202), RED);
+            assert_eq!(pixel(&page, x, 203), GREEN);
+            assert_eq!(pixel(&page, x, 396), GREEN);
+            assert_eq!(pixel(&page, x, 397), BLUE);
+            assert_eq!(pixel(&page, x, 589), BLUE);
+            assert_eq!(pixel(&page, x, 590), WHITE);
+        }
+        for x in [9, 190, 209, 390] {
+            assert_eq!(pixel(&page, x, 300), WHITE);
+        }
+
+        // Landscape strips are stacked instead
+        let page = layout(&PrintConfig {
+            landscape: true,
+            cells: Vec::new(),
+            ..strip_config(100)
+        })
+        .render(&photos)
+        .unwrap();
+        assert_eq!(page.size().unwrap(), Size::new(600, 400));
+        assert_eq!(
+            bytes(&page, Rect::new(0, 0, 600, 200)),
+            bytes(&page, Rect::new(0, 200, 600, 200))
+        );
+    }
+
+    #[test]
+    fn preview_has_the_print_geometry() {
+        let photos = [solid(RED), solid(GREEN), solid(BLUE)];
+        let layout = layout(&PrintConfig {
+            bleed_in: 0.1,
+            ..strip_config(300)
+        });
+        let page = layout.render(&photos).unwrap();
+        let preview = layout.render_preview(&photos).unwrap();
+        assert_eq!(page.size().unwrap(), Size::new(1260, 1860));
+        assert_eq!(preview.size().unwrap(), Size::new(200, 300));
+        // Every preview pixel has the color of the print at its center, 6 print pixels per preview pixel
+        let bleed = 30;
+        for y in 0..300 {
+            for x in 0..200 {
+                assert_eq!(
+                    pixel(&preview, x, y),
+                    pixel(&page, bleed + x * 6 + 3, bleed + y * 6 + 3),
+                    "{x}, {y}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn place_shares_edges_between_neighbours() {
+        let area = Rect::new(10, 20, 100, 50);
+        assert_eq!(place(area, [0., 0., 1., 1.]), area);
+        assert_eq!(
+            place(area, [0.25, 0.5, 0.5, 0.5]),
+            Rect::new(35, 45, 50, 25)
+        );
+        let left = place(area, [0., 0., 1. / 3., 1.]);
+        let middle = place(area, [1. / 3., 0., 1. / 3., 1.]);
+        let right = place(area, [2. / 3., 0., 1. / 3., 1.]);
+        assert_eq!(left, Rect::new(10, 20, 33, 50));
+        assert_eq!(middle.x, left.x + left.width);
+        assert_eq!(right.x, middle.x + middle.width);
+        assert_eq!(right.x + right.width, 110);
+    }
+
+    #[test]
+    fn contain_letterboxes() {
+        // Wide logo in a square, bars above and below
+        assert_eq!(
+            contain(Rect::new(0, 0, 100, 100), Size::new(200, 100)),
+            Rect::new(0, 25, 100, 50)
+        );
+        // Square logo in a wide rectangle, bars left and right
+        assert_eq!(
+            contain(Rect::new(10, 10, 100, 50), Size::new(10, 10)),
+            Rect::new(35, 10, 50, 50)
+        );
+        // Small logos are scaled up
+        assert_eq!(
+            contain(Rect::new(0, 0, 40, 40), Size::new(4, 2)),
+            Rect::new(0, 10, 40, 20)
+        );
+    }
+
+    #[test]
+    fn turn_rotates_clockwise() {
+        // 3 x 2 with the pixels numbered row by row
+        let mut photo =
+            Mat::new_size_with_default(Size::new(3, 2), CV_8UC1, Scalar::all(0.)).unwrap();
+        photo
+            .data_bytes_mut()
+            .unwrap()
+            .copy_from_slice(&[0, 1, 2, 3, 4, 5]);
+        let turned = |rotation, cell| {
+            let turned = turn(&photo, rotation, cell).unwrap();
+            (
+                turned.size().unwrap(),
+                turned.data_bytes().unwrap().to_vec(),
+            )
+        };
+        let portrait = Size::new(20, 30);
+        let landscape = Size::new(30, 20);
+
+        assert_eq!(
+            turned(None, portrait),
+            (Size::new(2, 3), vec![3, 0, 4, 1, 5, 2])
+        );
+        assert_eq!(
+            turned(None, landscape),
+            (Size::new(3, 2), vec![0, 1, 2, 3, 4, 5])
+        );
+        assert_eq!(
+            turned(Some(90), landscape),
+            (Size::new(2, 3), vec![3, 0, 4, 1, 5, 2])
+        );
+        assert_eq!(
+            turned(Some(180), portrait),
+            (Size::new(3, 2), vec![5, 4, 3, 2, 1, 0])
+        );
+        assert_eq!(
+            turned(Some(270), landscape),
+            (Size::new(2, 3), vec![2, 5, 1, 4, 0, 3])
+        );
+        assert_eq!(
+            turned(Some(0), portrait),
+            (Size::new(3, 2), vec![0, 1, 2, 3, 4, 5])
+        );
+    }
+}
@@ -1,76 +1,90 @@
-use core::panic;
 use std::error::Error;
-use std::sync::mpsc::channel;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread::{self};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use color::{RED, WHITE};
+use animation::{capture_burst, encode_gif, sequence, to_rgb, write_mp4};
+use best_shot::{BestShot, Pick};
+use camera::FrameSource;
+use chroma::{background_label, ChromaKey};
+use color::{DARKBLUE, DARKGRAY, DARKGREEN, DARKPURPLE, LIGHTGRAY, LIME, MAROON, RED, WHITE};
+use config::{AnimationConfig, KioskConfig, VideoConfig};
 use config_flags::FLAG_WINDOW_RESIZABLE;
+use countdown::{draw_countdown, draw_shot_label};
 use display_options::{FILL, SHOW_DEBUG_IMAGE, SHOW_DEBUG_INFO};
-use opencv::core::{flip, Vector};
-use opencv::imgcodecs::imwrite;
+use faces::{FaceDetector, Presence};
+use feedback::{
+    draw_flash, draw_print_preview, draw_printing, draw_processing, draw_recording, draw_warning,
+};
+use filter::{FilterRegistry, PhotoFilter, PreviewFilters};
+use layout::PrintLayout;
+use opencv::core::{flip, Rect, Size};
 use opencv::imgproc::{cvt_color, COLOR_BGR2RGB};
 use opencv::prelude::*;
-use opencv::videoio::{VideoCapture, CAP_ANY, CAP_PROP_FRAME_HEIGHT, CAP_PROP_FRAME_WIDTH};
+use overlay::Overlay;
 use peak_alloc::PeakAlloc;
+use print::Printer;
+use props::{palette_slot, Prop, PropTextures};
+use remote::{start_remote, RemoteCommand, RemoteStatus};
+use state::{Command, Event, KioskState, State};
+use storage::PhotoStorage;
+use strip::StripTemplate;
+use supervisor::{CameraStatus, CameraSupervisor};
+use trigger::{start_gpio_trigger, TriggerEvent};
+use ui::{bottom_left, bottom_right, bottom_row, second_row, Button, Pointer, ShutterButton};
+use video::{record_video, VideoPlayer};
 
+mod animation;
+mod best_shot;
+mod camera;
+mod chroma;
+mod config;
+mod countdown;
+mod draw;
+mod faces;
+mod feedback;
+mod filter;
+mod ipp;
+mod layout;
+mod overlay;
+mod print;
+mod props;
 mod raylib;
+mod remote;
+mod state;
+mod storage;
+mod strip;
+mod supervisor;
+mod trigger;
+mod ui;
+mod video;
+use crate::raylib::sys::gestures::GESTURE_TAP;
 use crate::raylib::*;
 
 #[global_allocator]
 static PEAK_ALLOC: PeakAlloc = PeakAlloc;
 
-enum State {
-    Startup,
-    Idle,
-    Countdown(Instant),
-    Capturing,
-    Waiting,
-    Presenting { texture: Texture, start: Instant },
-}
-
-struct KioskState {
-    state: State,
-}
-
-impl KioskState {
-    fn poll(&mut self) {
-        match &self.state {
-            State::Startup => self.state = State::Idle,
-            State::Idle => {}
-            State::Countdown(i) => {
-                let duration = i.elapsed().as_secs_f32();
-                if duration > 5. {
-                    self.state = State::Capturing
-                }
-            }
-            State::Capturing => self.state = State::Waiting,
-            State::Waiting => {}
-            State::Presenting { start, .. } => {
-                let duration = start.elapsed().as_secs_f32();
-                if duration > 5. {
-                    self.state = State::Idle;
-                }
-            }
-        };
-    }
-    fn new() -> Self {
-        Self {
-            state: State::new(),
-        }
-    }
-}
-
-impl State {
-    fn new() -> Self {
-        Self::Startup
-    }
-}
+/// How long warnings like "Nobody in frame" stay on screen
+const WARNING_DURATION: Duration = Duration::from_secs(3);
 
 struct WebcamFrame {
     frame: Mat,
     delta_times: [f32; 10],
+    status: CameraStatus,
+    source_resolution: (i32, i32),
+    source_fps: f64,
+    still_latency: Option<Duration>,
+    /// Selected filter that has no preview shader, applied to the frames in the capture thread
+    preview_filter: Option<PhotoFilter>,
+    /// Chroma key background selected in the render loop
+    background: usize,
+    /// Faces in `frame` from the latest detection
+    faces: Vec<Rect>,
+    /// Since when faces are in frame
+    faces_since: Option<Instant>,
 }
 
 impl WebcamFrame {
@@ -78,6 +92,14 @@ impl WebcamFrame {
         Self {
             frame,
             delta_times: [0.; 10],
+            status: CameraStatus::Connected,
+            source_resolution: (0, 0),
+            source_fps: 0.,
+            still_latency: None,
+            preview_filter: None,
+            background: 0,
+            faces: Vec::new(),
+            faces_since: None,
         }
     }
     fn avg_fps(&self) -> f32 {
@@ -87,30 +109,33 @@ impl WebcamFrame {
     }
 }
 
-fn get_new_display_frame(cap: &mut VideoCapture) -> Result<Option<Mat>, Box<dyn Error>> {
-    let mut frame = Mat::default();
-    let mut frame_1 = Mat::default();
-
-    let res = match cap.read(&mut frame) {
-        Err(e) => return Err(Box::new(e)),
-        Ok(b) => b,
-    };
-
-    if !res {
+/// Reads a preview frame and mirrors it, still in BGR
+fn read_mirrored_preview(source: &mut dyn FrameSource) -> Result<Option<Mat>, Box<dyn Error>> {
+    let Some(frame) = source.read_preview()? else {
         return Ok(None);
-    }
-
-    match flip(&frame, &mut frame_1, 1) {
-        Ok(_) => (),
-        Err(e) => return Err(Box::new(e)),
     };
+    let mut flipped = Mat::default();
+    flip(&frame, &mut flipped, 1)?;
+    Ok(Some(flipped))
+}
 
-    match cvt_color(&frame_1, &mut frame, COLOR_BGR2RGB, 0) {
-        Ok(_) => (),
-        Err(e) => return Err(Box::new(e)),
-    };
+/// Keys and filters the mirrored frame and converts it to RGB for raylib
+fn prepare_display_frame(
+    mut frame: Mat,
+    chroma_key: Option<(&mut ChromaKey, usize)>,
+    filter: Option<&PhotoFilter>,
+) -> Result<Mat, Box<dyn Error>> {
+    let mut rgb = Mat::default();
+
+    if let Some((chroma_key, background)) = chroma_key {
+        frame = chroma_key.apply(&frame, background)?;
+    }
+    if let Some(filter) = filter {
+        frame = filter.apply(&frame)?;
+    }
+    cvt_color(&frame, &mut rgb, COLOR_BGR2RGB, 0)?;
 
-    Ok(Some(frame))
+    Ok(rgb)
 }
 
 mod display_options {
@@ -121,69 +146,371 @@ mod display_options {
 
 enum Commands {
     Stop,
-    Capture,
+    /// Take the shot with the given index of the session
+    Capture {
+        shot: u32,
+        filter: PhotoFilter,
+        /// Chroma key background
+        background: usize,
+        prop: Option<usize>,
+    },
 }
 
-fn main() {
-    let (capture_command_tx, capture_command_rx) = channel::<Commands>();
-    let (captured_img_tx, captured_img_rx) = channel::<Image>();
+/// Sent from the capture thread to the render loop
+enum CaptureEvent {
+    /// The still was taken, saving and converting it is still running
+    Exposed(Instant),
+    /// Recording of a video message started
+    Recording(Instant),
+    /// A shot of a multi-shot session was saved, the strip is composed after the last one
+    ShotSaved,
+    /// No face was found in the still that was just taken
+    NobodyInFrame,
+    Done(Result<SavedCapture, String>),
+}
 
-    let mut state = KioskState::new();
-    let debug_img = include_bytes!("img/test.png");
+/// Result of a capture, the single photo, the composed strip or the animation
+struct SavedCapture {
+    image: Image,
+    path: PathBuf,
+    /// Other files saved with the capture like the shots of a strip, deleted together with it
+    related: Vec<PathBuf>,
+    /// RGB frames of an animation, empty for photos
+    frames: Vec<Mat>,
+    /// The page the capture is printed on, only rendered if printing is enabled
+    print_preview: Option<Image>,
+}
 
-    let debug_img = Image::new_from_memory(".png", debug_img);
+/// A saved capture while it is presented for review
+struct CapturedPhoto {
+    texture: Texture,
+    path: PathBuf,
+    related: Vec<PathBuf>,
+    /// Played in a loop by updating the texture
+    frames: Vec<Mat>,
+    print_preview: Option<Texture>,
+}
 
-    let Ok(mut display_cap) = VideoCapture::new(0, CAP_ANY) else {
-        panic!("Could not open VideoCapture");
-    };
-    if !display_cap.is_opened().unwrap() {
-        panic!("Could not open camera")
+/// Shots of the running multi-shot session
+struct StripSession {
+    template: StripTemplate,
+    /// Brands the composed strip once instead of every shot
+    overlay: Option<Overlay>,
+    shots: u32,
+    pictures: Vec<Mat>,
+    paths: Vec<PathBuf>,
+}
+
+impl StripSession {
+    /// Saves the shot and its rejected stills, returns the composed strip once all shots are taken
+    fn add(
+        &mut self,
+        storage: &mut PhotoStorage,
+        shot: u32,
+        picture: Mat,
+        rejects: &[Mat],
+    ) -> Result<Option<SavedCapture>, Box<dyn Error>> {
+        if shot == 0 {
+            self.pictures.clear();
+            self.paths.clear();
+        }
+        let path = storage.save(&picture)?;
+        println!(
+            "Saved shot {} of {} to {}",
+            shot + 1,
+            self.shots,
+            path.display()
+        );
+        save_rejects(storage, &path, rejects);
+        self.pictures.push(picture);
+        self.paths.push(path);
+        if self.pictures.len() < self.shots as usize {
+            return Ok(None);
+        }
+
+        let strip = self.template.compose(&self.pictures)?;
+        let strip = match &self.overlay {
+            Some(overlay) => overlay.apply(&strip)?,
+            None => strip,
+        };
+        self.pictures.clear();
+        let path = storage.save_strip(&strip)?;
+        println!("Saved strip to {}", path.display());
+        Ok(Some(SavedCapture {
+            image: to_image(&strip)?,
+            path,
+            related: std::mem::take(&mut self.paths),
+            frames: Vec::new(),
+            print_preview: None,
+        }))
     }
+}
 
-    let _ = display_cap.set(CAP_PROP_FRAME_WIDTH, 1024.);
-    let _ = display_cap.set(CAP_PROP_FRAME_HEIGHT, 576.);
+fn main() {
+    let config = match KioskConfig::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
 
-    let Ok(mut record_cap) = VideoCapture::new(0, CAP_ANY) else {
-        panic!("Could not open VideoCapture");
+    let (trigger_tx, trigger_rx) = channel::<TriggerEvent>();
+    let simulated_button = match start_gpio_trigger(&config.gpio, trigger_tx) {
+        Ok(line) => line,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
     };
-    if !record_cap.is_opened().unwrap() {
-        panic!("Could not open camera")
+
+    let (remote_tx, remote_rx) = channel::<RemoteCommand>();
+    let remote_status = Arc::new(Mutex::new(RemoteStatus::default()));
+    if let Err(e) = start_remote(&config.remote, remote_tx, Arc::clone(&remote_status)) {
+        eprintln!("{e}");
+        std::process::exit(1);
     }
+    let mut latest_photo: Option<PathBuf> = None;
+
+    let (capture_command_tx, capture_command_rx) = channel::<Commands>();
+    let (captured_img_tx, captured_img_rx) = channel::<CaptureEvent>();
+
+    let mut state = KioskState::<CapturedPhoto>::new(&config);
+    let overlay = match Overlay::from_config(&config.overlay, &config.output.event) {
+        Ok(overlay) => overlay,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+    let preview_overlay = overlay.clone().filter(|_| config.overlay.preview);
+    let mut strip_session = if config.strip.shots > 1 {
+        match StripTemplate::from_config(&config.strip) {
+            Ok(template) => Some(StripSession {
+                template,
+                overlay: overlay.clone(),
+                shots: config.strip.shots,
+                pictures: Vec::new(),
+                paths: Vec::new(),
+            }),
+            Err(e) => {
+                eprintln!("Invalid strip template: {e}");
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+    let debug_img = include_bytes!("img/test.png");
+
+    let debug_img = Image::new_from_memory(".png", debug_img);
 
-    let _ = record_cap.set(CAP_PROP_FRAME_WIDTH, 1920.);
-    let _ = record_cap.set(CAP_PROP_FRAME_HEIGHT, 1080.);
+    let mut camera = CameraSupervisor::new(config.camera.clone());
 
     let frame = WebcamFrame::new(Mat::default());
 
     let frame = Arc::new(Mutex::new(frame));
 
+    let animation = config.animation.enabled.then(|| config.animation.clone());
+    let video = config.video.enabled.then(|| config.video.clone());
+    let filters = match FilterRegistry::from_config(&config.filters)
+        .and_then(|registry| registry.select(&config.filters.available))
+    {
+        Ok(filters) => filters,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+    let mut chroma_key = match ChromaKey::from_config(&config.chroma_key) {
+        Ok(chroma_key) => chroma_key,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+    let chroma_preview = config.chroma_key.preview;
+    let props = match Prop::load_all(&config.props) {
+        Ok(props) => props,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+    let capture_props = props.clone();
+    let print_layout = match config
+        .print
+        .enabled
+        .then(|| {
+            PrintLayout::from_config(
+                &config.print,
+                &config.output.event,
+                &config.overlay.date_format,
+            )
+        })
+        .transpose()
+    {
+        Ok(layout) => layout,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+    let printer = match print_layout
+        .clone()
+        .map(|layout| Printer::start(&config.print, layout))
+        .transpose()
+    {
+        Ok(printer) => printer,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+    // Prints ordered of the presented capture
+    let mut session_prints = 0;
+    let background_labels: Vec<String> = match &chroma_key {
+        Some(_) => config
+            .chroma_key
+            .backgrounds
+            .iter()
+            .map(|path| background_label(path))
+            .collect(),
+        None => Vec::new(),
+    };
+    let mut face_detector = match FaceDetector::from_config(&config.faces) {
+        Ok(detector) => detector,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+    let warn_empty = config.faces.warn_empty;
+    let mut best_shot = match BestShot::from_config(&config.best_shot, &config.faces) {
+        Ok(best_shot) => best_shot,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+    let detection_interval = config.faces.interval();
+    let mut last_detection = Instant::now();
+    let mut presence = Presence::default();
     let capture_frame = Arc::clone(&frame);
+    let mut storage = PhotoStorage::new(&config.output);
     let mut start = Instant::now();
     let handle = thread::spawn(move || loop {
         if let Ok(command) = capture_command_rx.try_recv() {
             match command {
                 Commands::Stop => break,
-                Commands::Capture => {
-                    if let Ok(Some(picture)) = get_new_display_frame(&mut record_cap) {
-                        let param = Vector::new();
-                        let _ = imwrite("test.png", &picture, &param);
-                        let image: Image = picture.into();
-                        captured_img_tx.send(image).expect("Could not send");
-                    }
+                Commands::Capture { .. } if video.is_some() => {
+                    let config = video.as_ref().expect("Video is configured");
+                    let result = capture_video(
+                        &mut camera,
+                        &mut storage,
+                        config,
+                        &captured_img_tx,
+                        &capture_frame,
+                    )
+                    .map_err(|e| e.to_string());
+                    captured_img_tx
+                        .send(CaptureEvent::Done(result))
+                        .expect("Could not send");
+                }
+                Commands::Capture { .. } if animation.is_some() => {
+                    let config = animation.as_ref().expect("Animation is configured");
+                    let result =
+                        capture_animation(&mut camera, &mut storage, config, &captured_img_tx)
+                            .map_err(|e| e.to_string());
+                    captured_img_tx
+                        .send(CaptureEvent::Done(result))
+                        .expect("Could not send");
+                }
+                Commands::Capture {
+                    shot,
+                    filter,
+                    background,
+                    prop,
+                } => {
+                    let effects = Effects {
+                        chroma_key: chroma_key.as_mut().map(|key| (key, background)),
+                        prop: prop.and_then(|prop| capture_props.get(prop)),
+                        filter: &filter,
+                        // Strips are branded once they are composed
+                        overlay: overlay.as_ref().filter(|_| strip_session.is_none()),
+                    };
+                    let event = match capture_photo(
+                        &mut camera,
+                        &mut storage,
+                        strip_session.as_mut(),
+                        Shutter {
+                            best_shot: best_shot.as_mut(),
+                            face_detector: face_detector.as_mut(),
+                            warn_empty,
+                        },
+                        effects,
+                        shot,
+                        &captured_img_tx,
+                    ) {
+                        Ok(Some(mut saved)) => {
+                            if let Some(layout) = &print_layout {
+                                saved.print_preview = print_preview(layout, &saved);
+                            }
+                            CaptureEvent::Done(Ok(saved))
+                        }
+                        Ok(None) => CaptureEvent::ShotSaved,
+                        Err(e) => CaptureEvent::Done(Err(e.to_string())),
+                    };
+                    captured_img_tx.send(event).expect("Could not send");
                 }
             }
         }
-        if let Ok(Some(new_frame)) = get_new_display_frame(&mut display_cap) {
-            if new_frame.empty() {
-                panic!("Webcam frame should not be empty");
+        let (preview_filter, background) = capture_frame
+            .lock()
+            .map(|frame| (frame.preview_filter.clone(), frame.background))
+            .unwrap_or_default();
+        let preview_key = chroma_key
+            .as_mut()
+            .filter(|_| chroma_preview)
+            .map(|key| (key, background));
+        let mirrored = read_mirrored_preview(&mut camera);
+        let faces = match (&mut face_detector, &mirrored) {
+            (Some(detector), Ok(Some(frame))) if last_detection.elapsed() >= detection_interval => {
+                last_detection = Instant::now();
+                match detector.detect(frame) {
+                    Ok(faces) => {
+                        presence.update(last_detection, faces.len());
+                        Some(faces)
+                    }
+                    Err(e) => {
+                        eprintln!("Face detection failed: {e}");
+                        None
+                    }
+                }
             }
-            if let Ok(mut frame) = capture_frame.lock() {
+            _ => None,
+        };
+        let new_frame = mirrored.and_then(|frame| {
+            frame
+                .map(|frame| prepare_display_frame(frame, preview_key, preview_filter.as_ref()))
+                .transpose()
+        });
+        if let Ok(mut frame) = capture_frame.lock() {
+            if let Some(faces) = faces {
+                frame.faces = faces;
+            }
+            frame.faces_since = presence.since();
+            frame.status = camera.status().clone();
+            frame.source_resolution = camera.resolution();
+            frame.source_fps = camera.fps();
+            frame.still_latency = camera.last_still_latency();
+            if let Ok(Some(new_frame)) = new_frame {
                 frame.frame = new_frame;
 
                 frame.delta_times.copy_within(1..10, 0);
                 frame.delta_times[9] = start.elapsed().as_secs_f32();
                 start = Instant::now();
-            };
+            }
         };
     });
     let mut display_options_state = FILL;
@@ -191,142 +518,835 @@ fn main() {
     // For development in drm it dowsn't even work
     set_config_flags(FLAG_WINDOW_RESIZABLE);
 
-    init_window(1024, 600, "Susi");
+    init_window(
+        config.window.width as i32,
+        config.window.height as i32,
+        &config.window.title,
+    );
+
+    let shutter_sound = config.feedback.shutter_sound.as_ref().and_then(|path| {
+        init_audio_device();
+        let sound = Sound::new(&path.to_string_lossy());
+        if sound.is_valid() {
+            Some(sound)
+        } else {
+            eprintln!("Could not load shutter sound {}", path.display());
+            None
+        }
+    });
+    let mut exposed_at: Option<Instant> = None;
+    let mut recording_since: Option<Instant> = None;
+    // Plays the presented video message
+    let mut playback: Option<VideoPlayer> = None;
+    let mut pointer = Pointer::default();
+    set_gestures_enabled(GESTURE_TAP);
 
-    while frame.lock().unwrap().frame.typ() != 16 {}
-    {}
+    let mut selected_filter = 0;
+    let mut selected_background = 0;
+    let mut selected_prop: Option<usize> = None;
+    let prop_textures = PropTextures::load(&props);
+
+    let auto_trigger = config.faces.auto_trigger();
+    let mut faces_since: Option<Instant> = None;
+    // Presence that already had a session, people who stay in frame are not triggered again
+    let mut handled_presence: Option<Instant> = None;
+    let mut nobody_in_frame_at: Option<Instant> = None;
+    let preview_filters = PreviewFilters::load(&filters);
 
     let debug_texture = Texture::from(&debug_img);
-    let texture: Texture;
-    if let Ok(frame) = frame.lock() {
-        let img = Image::from(frame.frame.clone());
-        texture = Texture::from(&img);
-    } else {
-        panic!("Different thread panicked");
-    }
+    // Created from the first frame and recreated when the camera comes back with another resolution
+    let mut camera_texture: Option<Texture> = None;
+    // Overlay drawn over the live preview, rendered at the size of the camera texture
+    let mut overlay_texture: Option<Texture> = None;
     while !window_should_close() {
-        state.poll();
+        let now = Instant::now();
+        let mut events = vec![Event::Tick(now)];
         if is_key_pressed(KeyboardKeys::KEY_F) {
             display_options_state ^= FILL;
         }
         if is_key_pressed(KeyboardKeys::KEY_D) {
             display_options_state ^= SHOW_DEBUG_INFO;
         }
-        if is_key_pressed(KeyboardKeys::KEY_C) {
-            state.state = State::Countdown(Instant::now());
+        if is_key_pressed(KeyboardKeys::KEY_C) && camera_texture.is_some() {
+            events.push(Event::Trigger(now));
         }
         if is_key_pressed(KeyboardKeys::KEY_I) {
             display_options_state ^= SHOW_DEBUG_IMAGE;
         }
+        if is_key_pressed(KeyboardKeys::KEY_N) && matches!(state.state(), State::Idle) {
+            selected_filter = (selected_filter + 1) % filters.len();
+        }
+        if is_key_pressed(KeyboardKeys::KEY_B)
+            && matches!(state.state(), State::Idle)
+            && !background_labels.is_empty()
+        {
+            selected_background = (selected_background + 1) % background_labels.len();
+        }
+        if is_key_pressed(KeyboardKeys::KEY_P)
+            && matches!(state.state(), State::Idle)
+            && !props.is_empty()
+        {
+            selected_prop = match selected_prop {
+                None => Some(0),
+                Some(prop) if prop + 1 < props.len() => Some(prop + 1),
+                Some(_) => None,
+            };
+        }
+        if let Some(button) = &simulated_button {
+            button.set(is_key_down(KeyboardKeys::KEY_SPACE));
+        }
+        while let Ok(trigger) = trigger_rx.try_recv() {
+            match trigger {
+                TriggerEvent::Press if camera_texture.is_some() => events.push(Event::Trigger(now)),
+                TriggerEvent::Press => {}
+                TriggerEvent::LongPress => events.push(Event::Retake(now)),
+            }
+        }
+        while let Ok(command) = remote_rx.try_recv() {
+            match command {
+                RemoteCommand::Trigger if camera_texture.is_some() => {
+                    events.push(Event::Trigger(now))
+                }
+                RemoteCommand::Trigger => {}
+                RemoteCommand::ToggleDisplayOption(option) => display_options_state ^= option,
+            }
+        }
+        let mut print_pressed = is_key_pressed(KeyboardKeys::KEY_P);
+        if let Some(point) = pointer.pressed() {
+            let screen_size = Vector2(get_screen_width() as f32, get_screen_height() as f32);
+            let prop_slot = if props.is_empty() {
+                None
+            } else {
+                palette_slot(props.len(), screen_size, point)
+            };
+            match state.state() {
+                State::Idle
+                    if ShutterButton::new(screen_size).contains(point)
+                        && camera_texture.is_some() =>
+                {
+                    events.push(Event::Trigger(now));
+                }
+                State::Idle if prop_slot.is_some() => selected_prop = prop_slot.flatten(),
+                State::Idle
+                    if filters.len() > 1
+                        && filter_button(&filters[selected_filter], screen_size)
+                            .contains(point) =>
+                {
+                    selected_filter = (selected_filter + 1) % filters.len();
+                }
+                State::Idle
+                    if background_labels.len() > 1
+                        && background_button(
+                            &background_labels[selected_background],
+                            screen_size,
+                        )
+                        .contains(point) =>
+                {
+                    selected_background = (selected_background + 1) % background_labels.len();
+                }
+                State::Presenting { .. } => {
+                    let [keep, retake, delete] = review_buttons(screen_size);
+                    if keep.contains(point) {
+                        events.push(Event::Keep);
+                    } else if retake.contains(point) {
+                        events.push(Event::Retake(now));
+                    } else if delete.contains(point) {
+                        events.push(Event::Delete);
+                    } else if print_button(screen_size).contains(point) {
+                        print_pressed = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let (true, Some(printer), State::Presenting { image, .. }) =
+            (print_pressed, &printer, state.state())
+        {
+            if image.frames.is_empty() && printer.allows(session_prints) {
+                printer.print(&image.path, &image.related);
+                session_prints += 1;
+            }
+        }
+        if is_key_pressed(KeyboardKeys::KEY_K) {
+            events.push(Event::Keep);
+        }
+        if is_key_pressed(KeyboardKeys::KEY_R) {
+            events.push(Event::Retake(now));
+        }
+        if is_key_pressed(KeyboardKeys::KEY_X) || is_key_pressed(KeyboardKeys::KEY_DELETE) {
+            events.push(Event::Delete);
+        }
+        while let Ok(capture_event) = captured_img_rx.try_recv() {
+            match capture_event {
+                CaptureEvent::Exposed(instant) => {
+                    exposed_at = Some(instant);
+                    if let Some(sound) = &shutter_sound {
+                        sound.play();
+                    }
+                }
+                CaptureEvent::Recording(instant) => recording_since = Some(instant),
+                CaptureEvent::ShotSaved => events.push(Event::ShotCaptured(now)),
+                CaptureEvent::NobodyInFrame => nobody_in_frame_at = Some(now),
+                CaptureEvent::Done(Ok(saved)) => {
+                    recording_since = None;
+                    if config.video.enabled {
+                        playback = match VideoPlayer::open(&saved.path) {
+                            Ok(player) => Some(player),
+                            Err(e) => {
+                                eprintln!("Could not play back {}: {e}", saved.path.display());
+                                None
+                            }
+                        };
+                    }
+                    let photo = CapturedPhoto {
+                        texture: (&saved.image).into(),
+                        path: saved.path,
+                        related: saved.related,
+                        frames: saved.frames,
+                        print_preview: saved.print_preview.as_ref().map(Texture::from),
+                    };
+                    events.push(Event::ImageCaptured(photo, now))
+                }
+                CaptureEvent::Done(Err(e)) => {
+                    recording_since = None;
+                    eprintln!("Capture failed: {e}");
+                    events.push(Event::CaptureFailed(now));
+                }
+            }
+        }
+        if let (Some(delay), Some(since)) = (auto_trigger, faces_since) {
+            if matches!(state.state(), State::Idle)
+                && camera_texture.is_some()
+                && handled_presence != Some(since)
+                && since.elapsed() >= delay
+            {
+                events.push(Event::Trigger(now));
+            }
+        }
+        for event in events {
+            let captured = match &event {
+                Event::ImageCaptured(photo, _) => Some(photo.path.clone()),
+                _ => None,
+            };
+            for command in state.handle(event) {
+                match command {
+                    Command::Capture { shot } => {
+                        let _ = capture_command_tx.send(Commands::Capture {
+                            shot,
+                            filter: filters[selected_filter].clone(),
+                            background: selected_background,
+                            prop: selected_prop,
+                        });
+                    }
+                    Command::Discard(photo) => {
+                        if latest_photo.as_ref() == Some(&photo.path) {
+                            latest_photo = None;
+                        }
+                        for path in photo.related.iter().chain([&photo.path]) {
+                            match fs::remove_file(path) {
+                                Ok(()) => println!("Deleted capture {}", path.display()),
+                                Err(e) => eprintln!("Could not delete {}: {e}", path.display()),
+                            }
+                        }
+                    }
+                }
+            }
+            // Only a presented photo starts a new session, late ones were discarded above
+            if let (Some(path), State::Presenting { image, .. }) = (captured, state.state()) {
+                if image.path == path {
+                    session_prints = 0;
+                    latest_photo = Some(path);
+                }
+            }
+        }
+
+        if !matches!(state.state(), State::Presenting { .. }) {
+            playback = None;
+        }
+        if !matches!(state.state(), State::Idle) {
+            handled_presence = faces_since;
+        }
 
         let mut webcam_fps: f32 = 0.;
+        let mut camera_status = CameraStatus::Connected;
+        let mut source_resolution = (0, 0);
+        let mut source_fps = 0.;
+        let mut still_latency = None;
+        let mut faces = Vec::new();
         let screen_size = Vector2(get_screen_width() as f32, get_screen_height() as f32);
 
-        if let Ok(frame) = frame.lock() {
-            if display_options_state & SHOW_DEBUG_IMAGE == 0 {
-                texture.update(&frame.frame);
+        if let Ok(mut frame) = frame.lock() {
+            if display_options_state & SHOW_DEBUG_IMAGE == 0 && !frame.frame.empty() {
+                let result = match &camera_texture {
+                    Some(texture)
+                        if texture.width == frame.frame.cols()
+                            && texture.height == frame.frame.rows() =>
+                    {
+                        texture.update(&frame.frame)
+                    }
+                    _ => Texture::try_from(frame.frame.clone())
+                        .map(|texture| camera_texture = Some(texture)),
+                };
+                if let Err(e) = result {
+                    eprintln!("Could not show camera frame: {e}");
+                }
             }
             webcam_fps = frame.avg_fps();
+            camera_status = frame.status.clone();
+            source_resolution = frame.source_resolution;
+            source_fps = frame.source_fps;
+            still_latency = frame.still_latency;
+            faces.clone_from(&frame.faces);
+            faces_since = frame.faces_since;
+
+            let selected = &filters[selected_filter];
+            let wanted = preview_filters.needs_cpu(selected).then_some(selected);
+            if frame.preview_filter.as_ref().map(|filter| &filter.name)
+                != wanted.map(|filter| &filter.name)
+            {
+                frame.preview_filter = wanted.cloned();
+            }
+            frame.background = selected_background;
         }
 
-        let texture = if display_options_state & SHOW_DEBUG_IMAGE != 0 {
-            &debug_texture
-        } else {
-            &texture
+        if let Ok(mut status) = remote_status.lock() {
+            *status = RemoteStatus {
+                state: state.state().name(),
+                camera_connected: camera_status == CameraStatus::Connected,
+                latest_photo: latest_photo.clone(),
+                fill: display_options_state & FILL != 0,
+                show_debug_info: display_options_state & SHOW_DEBUG_INFO != 0,
+                show_debug_image: display_options_state & SHOW_DEBUG_IMAGE != 0,
+                filter: filters[selected_filter].name.clone(),
+                faces: faces.len(),
+            };
+        }
+
+        if let (Some(overlay), Some(camera)) = (&preview_overlay, &camera_texture) {
+            let current = matches!(&overlay_texture, Some(texture)
+                if texture.width == camera.width && texture.height == camera.height);
+            if !current {
+                match overlay
+                    .preview_layer(Size::new(camera.width, camera.height))
+                    .and_then(Texture::try_from)
+                {
+                    Ok(texture) => overlay_texture = Some(texture),
+                    Err(e) => eprintln!("Could not render the preview overlay: {e}"),
+                }
+            }
+        }
+
+        let showing_camera =
+            camera_texture.is_some() && display_options_state & SHOW_DEBUG_IMAGE == 0;
+        let texture = match &camera_texture {
+            Some(texture) if showing_camera => texture,
+            _ => &debug_texture,
         };
 
-        let scale = if (display_options_state & FILL) != 0 {
-            f32::max(
-                screen_size.0 / texture.width as f32,
-                screen_size.1 / texture.height as f32,
-            )
-        } else {
-            f32::min(
-                screen_size.0 / texture.width as f32,
-                screen_size.1 / texture.height as f32,
-            )
+        let fill = (display_options_state & FILL) != 0;
+        let (pos, scale) = fit_texture(texture, screen_size, fill);
+
+        // Draws the camera and the props with the selected filter, the presented photo already has them applied
+        let draw_preview = || {
+            let _shader = preview_filters.begin(&filters[selected_filter], texture);
+            texture.draw_ex(pos, 0., scale, WHITE);
+            if let Some(prop) = selected_prop.filter(|_| showing_camera) {
+                prop_textures.draw_on_faces(&props[prop], prop, &faces, pos, scale);
+            }
+        };
+        // Only shown over the camera, the presented photo already contains it
+        let draw_preview_overlay = || {
+            if let Some(overlay) = &overlay_texture {
+                if overlay.width == texture.width && overlay.height == texture.height {
+                    overlay.draw_ex(pos, 0., scale, WHITE);
+                }
+            }
         };
-        let pos = Vector2(
-            (screen_size.0 - texture.width as f32 * scale) / 2.,
-            (screen_size.1 - texture.height as f32 * scale) / 2.,
-        );
 
-        let mut ring_position = screen_size / 2.0;
-        let ring_outer_radius = 40.;
-        let ring_bottum_padding = 40.;
-        ring_position.1 = screen_size.1 - ring_outer_radius - ring_bottum_padding;
         begin_drawing();
         clear_background(WHITE);
-        match &state.state {
-            State::Startup => todo!(),
+        match state.state() {
+            State::Startup => {
+                draw_text("Starting...", 5, 5, 20, RED);
+            }
             State::Idle => {
-                texture.draw_ex(pos, 0., scale, WHITE);
+                draw_preview();
+                draw_preview_overlay();
 
-                draw_ring(ring_position, 30., ring_outer_radius, 0., 360., 360, WHITE);
+                let shutter = ShutterButton::new(screen_size);
+                shutter.draw(pointer.is_down() && shutter.contains(pointer.position()));
+                if filters.len() > 1 {
+                    filter_button(&filters[selected_filter], screen_size).draw();
+                }
+                if background_labels.len() > 1 {
+                    background_button(&background_labels[selected_background], screen_size).draw();
+                }
+                if !props.is_empty() {
+                    prop_textures.draw_palette(selected_prop, screen_size);
+                }
             }
-            State::Countdown(instant) => {
-                texture.draw_ex(pos, 0., scale, WHITE);
-                draw_text(
-                    &format!("{}", instant.elapsed().as_secs()),
-                    (screen_size.0 / 2. - 50.).round() as i32,
-                    (screen_size.1 / 2. - 50.).round() as i32,
-                    100,
-                    RED,
+            State::Countdown { start, shot } => {
+                draw_preview();
+                draw_preview_overlay();
+                draw_countdown(
+                    start.elapsed(),
+                    state.countdown(*shot),
+                    &config.countdown,
+                    screen_size,
                 );
+                if state.shots() > 1 {
+                    draw_shot_label(*shot, state.shots(), &config.countdown, screen_size);
+                }
             }
-            State::Capturing => {
-                let _ = capture_command_tx.send(Commands::Capture);
+            State::Capturing { since, .. } => {
+                draw_preview();
+                draw_preview_overlay();
+                if let Some(recording_since) = recording_since {
+                    draw_recording(
+                        recording_since.elapsed(),
+                        config.video.duration(),
+                        screen_size,
+                    );
+                } else if since.elapsed() >= config.feedback.processing_threshold() {
+                    draw_processing(since.elapsed(), screen_size);
+                }
             }
-            State::Waiting => {
-                if let Ok(image) = captured_img_rx.try_recv() {
-                    state.state = State::Presenting {
-                        texture: (&image).into(),
-                        start: Instant::now(),
+            State::Presenting { image, start } => {
+                if !image.frames.is_empty() {
+                    let frame = (start.elapsed().as_secs_f32() / config.animation.interval_secs)
+                        as usize
+                        % image.frames.len();
+                    if let Err(e) = image.texture.update(&image.frames[frame]) {
+                        eprintln!("Could not show animation frame: {e}");
+                    }
+                }
+                if let Some(player) = &mut playback {
+                    if let Err(e) = update_playback(player, &image.texture) {
+                        eprintln!("Could not play back video: {e}");
+                        playback = None;
                     }
                 }
+                let (pos, scale) = fit_texture(&image.texture, screen_size, fill);
+                image.texture.draw_ex(pos, 0., scale, WHITE);
+                for button in review_buttons(screen_size) {
+                    button.draw();
+                }
+                if let Some(printer) = &printer {
+                    if image.frames.is_empty() && printer.allows(session_prints) {
+                        print_button(screen_size).draw();
+                    }
+                    if let Some(preview) = &image.print_preview {
+                        draw_print_preview(preview, screen_size);
+                    }
+                    draw_printing(printer.status().queued, screen_size);
+                }
             }
-
-            State::Presenting { texture, .. } => {
-                texture.draw(0, 0, WHITE);
+            State::Failed(_) => {
+                texture.draw_ex(pos, 0., scale, WHITE);
+                draw_text(
+                    "Capture failed",
+                    (screen_size.0 / 2. - 150.).round() as i32,
+                    (screen_size.1 / 2. - 20.).round() as i32,
+                    40,
+                    RED,
+                );
             }
         }
         if display_options_state & SHOW_DEBUG_IMAGE != 0 {
             texture.draw(0, 0, WHITE);
         }
+        if !matches!(state.state(), State::Presenting { .. })
+            && (camera_status != CameraStatus::Connected || camera_texture.is_none())
+        {
+            draw_reconnecting(&camera_status, screen_size);
+        }
+
+        draw_flash(exposed_at, config.feedback.flash(), screen_size);
+        draw_warning(
+            "Nobody in frame",
+            nobody_in_frame_at,
+            WARNING_DURATION,
+            screen_size,
+        );
 
         if (display_options_state & SHOW_DEBUG_INFO) != 0 {
-            draw_debug_info(texture, webcam_fps);
+            if display_options_state & SHOW_DEBUG_IMAGE == 0
+                && !matches!(state.state(), State::Presenting { .. })
+            {
+                for face in &faces {
+                    let rectangle = Rectangle {
+                        x: pos.0 + face.x as f32 * scale,
+                        y: pos.1 + face.y as f32 * scale,
+                        width: face.width as f32 * scale,
+                        height: face.height as f32 * scale,
+                    };
+                    draw_rectangle_lines(rectangle, 3., LIME);
+                }
+            }
+            draw_debug_info(
+                texture,
+                webcam_fps,
+                source_resolution,
+                source_fps,
+                still_latency,
+            );
         }
         end_drawing();
     }
     let _ = capture_command_tx.send(Commands::Stop);
     handle.join().unwrap();
+    if let Some(sound) = shutter_sound {
+        drop(sound);
+        close_audio_device();
+    }
     close_window();
 }
 
-fn get_new_record_frame(record_cap: &mut VideoCapture) -> Result<Option<Mat>, Box<dyn Error>> {
-    let mut frame = Mat::default();
-    let mut frame_1 = Mat::default();
-
-    let res = match record_cap.read(&mut frame) {
-        Err(e) => return Err(Box::new(e)),
-        Ok(b) => b,
+fn get_new_record_frame(source: &mut dyn FrameSource) -> Result<Option<Mat>, Box<dyn Error>> {
+    let Some(frame) = source.read_still()? else {
+        return Ok(None);
     };
 
-    if !res {
-        return Ok(None);
+    let mut flipped = Mat::default();
+    flip(&frame, &mut flipped, 1)?;
+
+    Ok(Some(flipped))
+}
+
+/// Mirrored full resolution stills of a burst, fails if the camera returned none
+fn get_new_record_burst(
+    source: &mut dyn FrameSource,
+    count: u32,
+    interval: Duration,
+) -> Result<Vec<Mat>, Box<dyn Error>> {
+    let stills = source.read_stills(count, interval)?;
+    if stills.is_empty() {
+        return Err("Camera returned no frame".into());
+    }
+    stills
+        .iter()
+        .map(|still| {
+            let mut flipped = Mat::default();
+            flip(still, &mut flipped, 1)?;
+            Ok(flipped)
+        })
+        .collect()
+}
+
+/// How the still is taken and checked before the effects are applied
+struct Shutter<'a> {
+    /// Takes a burst and keeps its best still instead of taking a single still
+    best_shot: Option<&'a mut BestShot>,
+    /// Finds the faces the prop is put onto
+    face_detector: Option<&'a mut FaceDetector>,
+    /// Warns if the face detector finds nobody in the still
+    warn_empty: bool,
+}
+
+impl Shutter<'_> {
+    /// Takes the mirrored BGR still, with the faces in it and the rejected stills of the burst
+    fn take(
+        self,
+        source: &mut dyn FrameSource,
+        events: &Sender<CaptureEvent>,
+    ) -> Result<Pick, Box<dyn Error>> {
+        let pick = match self.best_shot {
+            Some(best_shot) => {
+                // The burst starts with the flash and the shutter sound, guests hold still while they last
+                let _ = events.send(CaptureEvent::Exposed(Instant::now()));
+                let stills =
+                    get_new_record_burst(source, best_shot.frames(), best_shot.interval())?;
+                best_shot.pick(stills, self.face_detector)?
+            }
+            None => {
+                let picture = get_new_record_frame(source)?.ok_or("Camera returned no frame")?;
+                let _ = events.send(CaptureEvent::Exposed(Instant::now()));
+                let faces = match self.face_detector {
+                    Some(detector) => match detector.detect(&picture) {
+                        Ok(faces) => Some(faces),
+                        Err(e) => {
+                            eprintln!("Face detection failed: {e}");
+                            None
+                        }
+                    },
+                    None => None,
+                };
+                Pick {
+                    best: picture,
+                    faces,
+                    rejects: Vec::new(),
+                }
+            }
+        };
+        if self.warn_empty && matches!(&pick.faces, Some(faces) if faces.is_empty()) {
+            let _ = events.send(CaptureEvent::NobodyInFrame);
+        }
+        Ok(pick)
+    }
+}
+
+/// Everything applied to a still between the camera and the file
+struct Effects<'a> {
+    /// Chroma key with the selected background
+    chroma_key: Option<(&'a mut ChromaKey, usize)>,
+    /// Put onto the faces in the still
+    prop: Option<&'a Prop>,
+    filter: &'a PhotoFilter,
+    overlay: Option<&'a Overlay>,
+}
+
+impl Effects<'_> {
+    /// Replaces the backdrop, puts the prop onto the `faces`, then filters and brands the BGR picture
+    fn apply(self, picture: &Mat, faces: &[Rect]) -> Result<Mat, Box<dyn Error>> {
+        let picture = match self.chroma_key {
+            Some((chroma_key, background)) => chroma_key.apply(picture, background)?,
+            None => picture.clone(),
+        };
+        let picture = match self.prop {
+            Some(prop) => prop.apply(&picture, faces)?,
+            None => picture,
+        };
+        let picture = self.filter.apply(&picture)?;
+        match self.overlay {
+            Some(overlay) => overlay.apply(&picture),
+            None => Ok(picture),
+        }
+    }
+}
+
+/// Takes a still, applies the effects and saves it as single photo or as shot of the strip session.
+/// Returns `None` while the strip session needs more shots.
+fn capture_photo(
+    source: &mut dyn FrameSource,
+    storage: &mut PhotoStorage,
+    strip_session: Option<&mut StripSession>,
+    shutter: Shutter,
+    effects: Effects,
+    shot: u32,
+    events: &Sender<CaptureEvent>,
+) -> Result<Option<SavedCapture>, Box<dyn Error>> {
+    let pick = shutter.take(source, events)?;
+    let picture = effects.apply(&pick.best, pick.faces.as_deref().unwrap_or_default())?;
+    match strip_session {
+        Some(session) => session.add(storage, shot, picture, &pick.rejects),
+        None => save_capture(storage, &picture, &pick.rejects).map(Some),
+    }
+}
+
+/// Saves the BGR capture and its rejected stills and converts it for presenting
+fn save_capture(
+    storage: &mut PhotoStorage,
+    picture: &Mat,
+    rejects: &[Mat],
+) -> Result<SavedCapture, Box<dyn Error>> {
+    let path = storage.save(picture)?;
+    println!("Saved capture to {}", path.display());
+    save_rejects(storage, &path, rejects);
+
+    Ok(SavedCapture {
+        image: to_image(picture)?,
+        path,
+        related: Vec::new(),
+        frames: Vec::new(),
+        print_preview: None,
+    })
+}
+
+/// Keeps the unprocessed rejected stills of a burst, failing to do so does not fail the capture
+fn save_rejects(storage: &PhotoStorage, kept: &Path, rejects: &[Mat]) {
+    if rejects.is_empty() {
+        return;
+    }
+    match storage.save_rejects(kept, rejects) {
+        Ok(paths) => println!("Saved {} rejected stills", paths.len()),
+        Err(e) => eprintln!("Could not save rejected stills: {e}"),
+    }
+}
+
+/// Records a video message and keeps the live preview running while recording
+fn capture_video(
+    source: &mut dyn FrameSource,
+    storage: &mut PhotoStorage,
+    config: &VideoConfig,
+    events: &Sender<CaptureEvent>,
+    preview: &Mutex<WebcamFrame>,
+) -> Result<SavedCapture, Box<dyn Error>> {
+    let _ = events.send(CaptureEvent::Recording(Instant::now()));
+    let mut first = None;
+    let path = storage.save_file("", &config.extension, |tmp_path| {
+        let frame = record_video(source, tmp_path, config, |frame| {
+            let mut rgb = Mat::default();
+            if cvt_color(frame, &mut rgb, COLOR_BGR2RGB, 0).is_ok() {
+                if let Ok(mut preview) = preview.lock() {
+                    preview.frame = rgb;
+                }
+            }
+        })?;
+        first = Some(frame);
+        Ok(())
+    })?;
+    println!("Saved video to {}", path.display());
+
+    let first = first.ok_or("No frame was recorded")?;
+    Ok(SavedCapture {
+        image: to_image(&first)?,
+        path,
+        related: Vec::new(),
+        frames: Vec::new(),
+        print_preview: None,
+    })
+}
+
+/// Shows the next frame of the video on the texture once it is due
+fn update_playback(player: &mut VideoPlayer, texture: &Texture) -> Result<(), Box<dyn Error>> {
+    if let Some(frame) = player.next_frame()? {
+        if frame.cols() == texture.width && frame.rows() == texture.height {
+            let mut rgb = Mat::default();
+            cvt_color(&frame, &mut rgb, COLOR_BGR2RGB, 0)?;
+            texture.update(&rgb)?;
+        }
+    }
+    Ok(())
+}
+
+/// Records a burst, saves it as GIF and optionally MP4 and keeps the frames for presenting
+fn capture_animation(
+    source: &mut dyn FrameSource,
+    storage: &mut PhotoStorage,
+    config: &AnimationConfig,
+    events: &Sender<CaptureEvent>,
+) -> Result<SavedCapture, Box<dyn Error>> {
+    let burst = capture_burst(source, config)?;
+    let _ = events.send(CaptureEvent::Exposed(Instant::now()));
+
+    let frames = sequence(&burst, config)?;
+    let rgb = to_rgb(&frames)?;
+    let path = storage.save_gif(&encode_gif(&rgb, config.interval())?)?;
+    println!("Saved animation to {}", path.display());
+
+    let mut related = Vec::new();
+    if config.mp4 {
+        match storage.save_sibling(&path, "mp4", |tmp_path| {
+            write_mp4(tmp_path, &frames, config.interval())
+        }) {
+            Ok(video) => {
+                println!("Saved video to {}", video.display());
+                related.push(video);
+            }
+            Err(e) => eprintln!("Could not save MP4: {e}"),
+        }
+    }
+
+    Ok(SavedCapture {
+        image: Image::try_from(rgb[0].clone())?,
+        path,
+        related,
+        frames: rgb,
+        print_preview: None,
+    })
+}
+
+/// Converts a BGR picture into an image raylib can present
+fn to_image(picture: &Mat) -> Result<Image, Box<dyn Error>> {
+    let mut rgb = Mat::default();
+    cvt_color(picture, &mut rgb, COLOR_BGR2RGB, 0)?;
+    Image::try_from(rgb)
+}
+
+/// Renders the page the capture would be printed on, logs failures
+fn print_preview(layout: &PrintLayout, saved: &SavedCapture) -> Option<Image> {
+    match layout
+        .load_photos(&saved.path, &saved.related)
+        .and_then(|photos| layout.render_preview(&photos))
+        .and_then(|page| to_image(&page))
+    {
+        Ok(preview) => Some(preview),
+        Err(e) => {
+            eprintln!("Could not render the print preview: {e}");
+            None
+        }
     }
+}
 
-    match flip(&frame, &mut frame_1, 1) {
-        Ok(_) => (),
-        Err(e) => return Err(Box::new(e)),
+/// Position and scale that center the texture on the screen.
+/// With `fill` the texture covers the whole screen, otherwise it fits inside.
+fn fit_texture(texture: &Texture, screen_size: Vector2, fill: bool) -> (Vector2, f32) {
+    let scale_x = screen_size.0 / texture.width as f32;
+    let scale_y = screen_size.1 / texture.height as f32;
+    let scale = if fill {
+        f32::max(scale_x, scale_y)
+    } else {
+        f32::min(scale_x, scale_y)
     };
+    let pos = Vector2(
+        (screen_size.0 - texture.width as f32 * scale) / 2.,
+        (screen_size.1 - texture.height as f32 * scale) / 2.,
+    );
+    (pos, scale)
+}
 
-    Ok(Some(frame_1))
+fn filter_button(filter: &PhotoFilter, screen_size: Vector2) -> Button<'_> {
+    bottom_left(&filter.label, DARKGRAY, screen_size)
+}
+
+fn background_button<'a>(label: &'a str, screen_size: Vector2) -> Button<'a> {
+    bottom_right(label, DARKGRAY, screen_size)
+}
+
+fn review_buttons(screen_size: Vector2) -> [Button<'static>; 3] {
+    bottom_row(
+        [
+            ("Keep (K)", DARKGREEN),
+            ("Retake (R)", DARKBLUE),
+            ("Delete (X)", MAROON),
+        ],
+        screen_size,
+    )
+}
+
+fn print_button(screen_size: Vector2) -> Button<'static> {
+    let [print] = second_row([("Print (P)", DARKPURPLE)], screen_size);
+    print
+}
+
+fn draw_reconnecting(status: &CameraStatus, screen_size: Vector2) {
+    draw_rectangle(0, 0, screen_size.0 as i32, screen_size.1 as i32, DARKGRAY);
+    let center = screen_size / 2.;
+    draw_text(
+        "Camera reconnecting...",
+        (center.0 - 220.).round() as i32,
+        (center.1 - 40.).round() as i32,
+        40,
+        WHITE,
+    );
+    if let CameraStatus::Reconnecting { attempt, .. } = status {
+        if *attempt > 0 {
+            draw_text(
+                &format!("Attempt {attempt}"),
+                (center.0 - 50.).round() as i32,
+                (center.1 + 20.).round() as i32,
+                20,
+                LIGHTGRAY,
+            );
+        }
+    }
 }
 
-fn draw_debug_info(texture: &Texture, webcam_fps: f32) {
+fn draw_debug_info(
+    texture: &Texture,
+    webcam_fps: f32,
+    source_resolution: (i32, i32),
+    source_fps: f64,
+    still_latency: Option<Duration>,
+) {
     draw_fps(5, 5);
+    draw_text(
+        &format!("Touch points: {}", get_touch_point_count()),
+        165,
+        5,
+        20,
+        RED,
+    );
     draw_text(
         &format!(
             "Currently uses {} KB of RAM.",
@@ -366,4 +1386,23 @@ fn draw_debug_info(texture: &Texture, webcam_fps: f32) {
         RED,
     );
     draw_text(&format!("Webcam FPS:  {}", webcam_fps), 5, 105, 20, RED);
+    draw_text(
+        &format!(
+            "Source: {}x{} @ {:.1} FPS",
+            source_resolution.0, source_resolution.1, source_fps
+        ),
+        5,
+        125,
+        20,
+        RED,
+    );
+    if let Some(latency) = still_latency {
+        draw_text(
+            &format!("Still switch latency: {} ms", latency.as_millis()),
+            5,
+            145,
+            20,
+            RED,
+        );
+    }
 }
@@ -0,0 +1,160 @@
+use std::error::Error;
+use std::path::Path;
+
+use chrono::Local;
+use opencv::core::{extract_channel, Mat, Rect, Scalar, Size, CV_16U, CV_32F, CV_8U, CV_8UC4};
+use opencv::imgcodecs::{imread, IMREAD_UNCHANGED};
+use opencv::imgproc::{
+    blend_linear, cvt_color, COLOR_BGR2BGRA, COLOR_BGRA2BGR, COLOR_BGRA2RGBA, COLOR_GRAY2BGRA,
+};
+use opencv::prelude::*;
+
+use crate::camera::resized;
+use crate::config::{OverlayConfig, TextPosition};
+use crate::draw::{bgra_scalar, draw_caption};
+
+/// Branding blended onto the photos, an image with alpha and a line of text
+#[derive(Clone)]
+pub struct Overlay {
+    /// BGRA, stretched over the whole photo
+    image: Option<Mat>,
+    text: Option<String>,
+    event: String,
+    date_format: String,
+    text_color: Scalar,
+    text_position: TextPosition,
+    text_height: f32,
+}
+
+impl Overlay {
+    /// Loads the overlay image, returns `None` if neither an image nor a text is configured
+    pub fn from_config(
+        config: &OverlayConfig,
+        event: &str,
+    ) -> Result<Option<Self>, Box<dyn Error>> {
+        if config.image.is_none() && config.text.is_none() {
+            return Ok(None);
+        }
+        let image = match &config.image {
+            Some(path) => Some(load_bgra(path)?),
+            None => None,
+        };
+        Ok(Some(Self {
+            image,
+            text: config.text.clone(),
+            event: event.to_string(),
+            date_format: config.date_format.clone(),
+            text_color: bgra_scalar(config.text_color),
+            text_position: config.text_position,
+            text_height: config.text_height,
+        }))
+    }
+
+    /// BGRA layer of `size` with the image stretched over it and the text drawn on top
+    pub fn layer(&self, size: Size) -> Result<Mat, Box<dyn Error>> {
+        let mut layer = match &self.image {
+            Some(image) => resized(image, size)?,
+            None => Mat::new_size_with_default(size, CV_8UC4, Scalar::all(0.))?,
+        };
+        if let Some(text) = &self.text {
+            let text = text.replace("{event}", &self.event).replace(
+                "{date}",
+                &Local::now().format(&self.date_format).to_string(),
+            );
+            let height = (size.height as f32 * self.text_height).round() as i32;
+            let y = match self.text_position {
+                TextPosition::Top => 0,
+                TextPosition::Bottom => size.height - height,
+            };
+            draw_caption(
+                &mut layer,
+                &text,
+                Rect::new(0, y, size.width, height),
+                self.text_color,
+            )?;
+        }
+        Ok(layer)
+    }
+
+    /// RGBA layer for a texture drawn over the live preview
+    pub fn preview_layer(&self, size: Size) -> Result<Mat, Box<dyn Error>> {
+        let mut rgba = Mat::default();
+        cvt_color(&self.layer(size)?, &mut rgba, COLOR_BGRA2RGBA, 0)?;
+        Ok(rgba)
+    }
+
+    /// Alpha blends the overlay onto the BGR image
+    pub fn apply(&self, image: &Mat) -> Result<Mat, Box<dyn Error>> {
+        blend_bgra(&self.layer(image.size()?)?, image)
+    }
+}
+
+/// Alpha blends a BGRA layer onto a BGR image of the same size
+pub fn blend_bgra(layer: &Mat, image: &Mat) -> Result<Mat, Box<dyn Error>> {
+    let mut bgr = Mat::default();
+    cvt_color(layer, &mut bgr, COLOR_BGRA2BGR, 0)?;
+    let mut alpha = Mat::default();
+    extract_channel(layer, &mut alpha, 3)?;
+    let mut layer_weights = Mat::default();
+    alpha.convert_to(&mut layer_weights, CV_32F, 1. / 255., 0.)?;
+    let mut image_weights = Mat::default();
+    layer_weights.convert_to(&mut image_weights, CV_32F, -1., 1.)?;
+
+    let mut blended = Mat::default();
+    blend_linear(&bgr, image, &layer_weights, &image_weights, &mut blended)?;
+    Ok(blended)
+}
+
+/// Alpha blends a BGRA layer scaled to `rect` onto a BGR image, cutting it off at the image border
+pub fn blend_into(image: &mut Mat, layer: &Mat, rect: Rect) -> Result<(), Box<dyn Error>> {
+    let visible = rect & Rect::new(0, 0, image.cols(), image.rows());
+    if visible.width <= 0 || visible.height <= 0 {
+        return Ok(());
+    }
+    let scaled = resized(layer, rect.size())?;
+    let layer = Mat::roi(
+        &scaled,
+        Rect::new(
+            visible.x - rect.x,
+            visible.y - rect.y,
+            visible.width,
+            visible.height,
+        ),
+    )?
+    .try_clone()?;
+    let background = Mat::roi(image, visible)?.try_clone()?;
+    let blended = blend_bgra(&layer, &background)?;
+    let mut roi = Mat::roi_mut(image, visible)?;
+    blended.copy_to(&mut roi)?;
+    Ok(())
+}
+
+/// Loads an image as BGRA, images without alpha channel are opaque
+pub fn load_bgra(path: &Path) -> Result<Mat, Box<dyn Error>> {
+    let image = imread(&path.to_string_lossy(), IMREAD_UNCHANGED)?;
+    if image.empty() {
+        return Err(format!("Could not load image {}", path.display()).into());
+    }
+    let image = if image.depth() == CV_16U {
+        let mut converted = Mat::default();
+        image.convert_to(&mut converted, CV_8U, 1. / 257., 0.)?;
+        converted
+    } else {
+        image
+    };
+    let code = match image.channels() {
+        4 => return Ok(image),
+        3 => COLOR_BGR2BGRA,
+        1 => COLOR_GRAY2BGRA,
+        channels => {
+            return Err(format!(
+                "Image {} has unsupported {channels} channels",
+                path.display()
+            )
+            .into())
+        }
+    };
+    let mut bgra = Mat::default();
+    cvt_color(&image, &mut bgra, code, 0)?;
+    Ok(bgra)
+}
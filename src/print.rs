@@ -0,0 +1,456 @@
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use opencv::core::Vector;
+use opencv::imgcodecs::{imencode, IMWRITE_JPEG_QUALITY};
+use serde::{Deserialize, Serialize};
+
+use crate::config::PrintConfig;
+use crate::ipp::{IppPrinter, JobState};
+use crate::layout::PrintLayout;
+
+const QUEUE_FILE: &str = "queue.json";
+/// How often the printer is asked about submitted jobs while no new job comes in
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const PAGE_QUALITY: i32 = 95;
+
+/// Job in the persistent queue
+#[derive(Debug, Serialize, Deserialize)]
+struct PrintJob {
+    id: u64,
+    /// Shown in the printer queue
+    name: String,
+    /// Rendered page in the spool directory
+    page: PathBuf,
+    /// Failed submissions so far
+    attempts: u32,
+    /// Id the printer assigned once it accepted the job
+    printer_job: Option<i32>,
+    /// Jobs resumed after a restart are due right away
+    #[serde(skip)]
+    retry_at: Option<Instant>,
+}
+
+/// Published by the print thread
+#[derive(Debug, Default, Clone)]
+pub struct PrintStatus {
+    /// Jobs that are not printed yet
+    pub queued: usize,
+}
+
+/// Handle of the print thread
+pub struct Printer {
+    /// The saved photo and the shots of a strip
+    tx: Sender<(PathBuf, Vec<PathBuf>)>,
+    status: Arc<Mutex<PrintStatus>>,
+    max_per_session: u32,
+}
+
+impl Printer {
+    /// Resumes the queue of the spool directory and starts the print thread
+    pub fn start(config: &PrintConfig, layout: PrintLayout) -> Result<Self, Box<dyn Error>> {
+        let mut queue = PrintQueue::open(config, layout)?;
+        let status = Arc::new(Mutex::new(PrintStatus {
+            queued: queue.jobs.len(),
+        }));
+        let (tx, rx) = channel();
+        let thread_status = Arc::clone(&status);
+        thread::spawn(move || queue.run(rx, &thread_status));
+        Ok(Self {
+            tx,
+            status,
+            max_per_session: config.max_per_session,
+        })
+    }
+
+    /// Lays out the saved photo, or the shots of a strip, onto a page and queues it
+    pub fn print(&self, photo: &Path, shots: &[PathBuf]) {
+        if self.tx.send((photo.to_path_buf(), shots.to_vec())).is_err() {
+            eprintln!("Print thread is gone, could not print {}", photo.display());
+        }
+    }
+
+    /// Whether a session that already printed `printed` times may print again
+    pub fn allows(&self, printed: u32) -> bool {
+        self.max_per_session == 0 || printed < self.max_per_session
+    }
+
+    pub fn status(&self) -> PrintStatus {
+        self.status
+            .lock()
+            .map(|status| status.clone())
+            .unwrap_or_default()
+    }
+}
+
+struct PrintQueue {
+    printer: IppPrinter,
+    dir: PathBuf,
+    layout: PrintLayout,
+    max_attempts: u32,
+    retry: Duration,
+    jobs: Vec<PrintJob>,
+    next_id: u64,
+}
+
+impl PrintQueue {
+    fn open(config: &PrintConfig, layout: PrintLayout) -> Result<Self, Box<dyn Error>> {
+        fs::create_dir_all(&config.spool_dir)?;
+        let path = config.spool_dir.join(QUEUE_FILE);
+        let jobs: Vec<PrintJob> = match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|e| format!("Could not read print queue {}: {e}", path.display()))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                return Err(format!("Could not read print queue {}: {e}", path.display()).into())
+            }
+        };
+        if !jobs.is_empty() {
+            println!("Resuming {} print jobs", jobs.len());
+        }
+        Ok(Self {
+            printer: IppPrinter::new(&config.printer_uri)?,
+            dir: config.spool_dir.clone(),
+            layout,
+            max_attempts: config.max_attempts,
+            retry: config.retry(),
+            next_id: jobs.iter().map(|job| job.id + 1).max().unwrap_or(1),
+            jobs,
+        })
+    }
+
+    /// Queues photos until the sender is dropped, the printer is checked in between
+    fn run(&mut self, rx: Receiver<(PathBuf, Vec<PathBuf>)>, status: &Mutex<PrintStatus>) {
+        loop {
+            match rx.recv_timeout(POLL_INTERVAL) {
+                Ok((photo, shots)) => {
+                    if let Err(e) = self.add(&photo, &shots) {
+                        eprintln!("Could not queue {} for printing: {e}", photo.display());
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            if self.process() {
+                if let Err(e) = self.save() {
+                    eprintln!("Could not save the print queue: {e}");
+                }
+            }
+            if let Ok(mut status) = status.lock() {
+                status.queued = self.jobs.len();
+            }
+        }
+    }
+
+    fn add(&mut self, photo: &Path, shots: &[PathBuf]) -> Result<(), Box<dyn Error>> {
+        let page = self
+            .layout
+            .render(&self.layout.load_photos(photo, shots)?)?;
+        let mut jpeg = Vector::<u8>::new();
+        let params = Vector::from_slice(&[IMWRITE_JPEG_QUALITY, PAGE_QUALITY]);
+        if !imencode(".jpg", &page, &mut jpeg, &params)? {
+            return Err("Could not encode the page".into());
+        }
+        let id = self.next_id;
+        let path = self.dir.join(format!("{id:06}.jpg"));
+        fs::write(&path, jpeg.as_slice())?;
+
+        self.next_id += 1;
+        self.jobs.push(PrintJob {
+            id,
+            name: photo
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| format!("Photo {id}")),
+            page: path,
+            attempts: 0,
+            printer_job: None,
+            retry_at: None,
+        });
+        self.save()?;
+        println!("Queued {} for printing", photo.display());
+        Ok(())
+    }
+
+    /// Submits due jobs and drops finished ones, returns whether the queue changed.
+    /// IPP has no way to tell a lost Print-Job answer from a rejected job, so a job whose answer
+    /// got lost after the printer accepted it counts as failed and is submitted again, printing it twice.
+    fn process(&mut self) -> bool {
+        let now = Instant::now();
+        let mut changed = false;
+        let mut i = 0;
+        while i < self.jobs.len() {
+            let job = &mut self.jobs[i];
+            if matches!(job.retry_at, Some(retry_at) if retry_at > now) {
+                i += 1;
+                continue;
+            }
+            let done = match job.printer_job {
+                None => match fs::read(&job.page)
+                    .map_err(Box::<dyn Error>::from)
+                    .and_then(|jpeg| self.printer.print_jpeg(&job.name, &jpeg))
+                {
+                    Ok(printer_job) => {
+                        println!("Printer accepted {} as job {printer_job}", job.name);
+                        job.printer_job = Some(printer_job);
+                        changed = true;
+                        false
+                    }
+                    Err(e) => {
+                        changed = true;
+                        self.failed(i, &e.to_string(), now)
+                    }
+                },
+                Some(printer_job) => match self.printer.job_state(printer_job) {
+                    Ok(JobState::Completed) => {
+                        println!("Printed {}", job.name);
+                        true
+                    }
+                    Ok(JobState::Canceled) => {
+                        println!("Print job {} was canceled on the printer", job.name);
+                        true
+                    }
+                    Ok(JobState::Aborted) => {
+                        job.printer_job = None;
+                        changed = true;
+                        self.failed(i, "The printer aborted the job", now)
+                    }
+                    Ok(_) => false,
+                    // The printer is busy or offline, that does not count as an attempt
+                    Err(e) => {
+                        eprintln!("Could not check print job {}: {e}", job.name);
+                        job.retry_at = Some(now + self.retry);
+                        false
+                    }
+                },
+            };
+            if done {
+                let job = self.jobs.remove(i);
+                let _ = fs::remove_file(&job.page);
+                changed = true;
+            } else {
+                i += 1;
+            }
+        }
+        changed
+    }
+
+    /// Counts a failed attempt of job `i`, returns whether the job is given up
+    fn failed(&mut self, i: usize, error: &str, now: Instant) -> bool {
+        let job = &mut self.jobs[i];
+        job.attempts += 1;
+        if job.attempts >= self.max_attempts {
+            eprintln!(
+                "Giving up printing {} after {} attempts: {error}",
+                job.name, job.attempts
+            );
+            return true;
+        }
+        eprintln!(
+            "Printing {} failed (attempt {} of {}): {error}",
+            job.name, job.attempts, self.max_attempts
+        );
+        job.retry_at = Some(now + self.retry);
+        false
+    }
+
+    /// Atomically replaces the queue file
+    fn save(&self) -> Result<(), Box<dyn Error>> {
+        let path = self.dir.join(QUEUE_FILE);
+        let tmp_path = self.dir.join(format!(".tmp-{QUEUE_FILE}"));
+        fs::write(&tmp_path, serde_json::to_string_pretty(&self.jobs)?)?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipp::tests::{http_error, job_created, job_in_state, operation, FakePrinter};
+
+    const PRINT_JOB: u16 = 0x0002;
+    const GET_JOB_ATTRIBUTES: u16 = 0x0009;
+    const ABORTED: i32 = 8;
+    const COMPLETED: i32 = 9;
+
+    /// Queue in a fresh spool directory sending to `uri`
+    fn config(name: &str, uri: &str, max_attempts: u32, retry_secs: f32) -> PrintConfig {
+        let spool_dir =
+            std::env::temp_dir().join(format!("kiosk-print-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&spool_dir);
+        PrintConfig {
+            printer_uri: uri.to_string(),
+            spool_dir,
+            max_attempts,
+            retry_secs,
+            ..PrintConfig::default()
+        }
+    }
+
+    fn open(config: &PrintConfig) -> PrintQueue {
+        let layout = PrintLayout::from_config(config, "", "%Y").unwrap();
+        PrintQueue::open(config, layout).unwrap()
+    }
+
+    /// Adds a job with an already rendered page
+    fn add_job(queue: &mut PrintQueue) -> PathBuf {
+        let id = queue.next_id;
+        let page = queue.dir.join(format!("{id:06}.jpg"));
+        fs::write(&page, b"jpeg").unwrap();
+        queue.next_id += 1;
+        queue.jobs.push(PrintJob {
+            id,
+            name: format!("{id:04}.jpg"),
+            page: page.clone(),
+            attempts: 0,
+            printer_job: None,
+            retry_at: None,
+        });
+        page
+    }
+
+    fn operations(fake: &FakePrinter) -> Vec<u16> {
+        fake.requests()
+            .iter()
+            .map(|request| operation(request))
+            .collect()
+    }
+
+    #[test]
+    fn failed_jobs_wait_for_the_retry() {
+        let fake = FakePrinter::start(vec![http_error()]);
+        let config = config("retry", &fake.uri, 3, 60.);
+        let mut queue = open(&config);
+        add_job(&mut queue);
+
+        let before = Instant::now();
+        assert!(queue.process());
+        let job = &queue.jobs[0];
+        assert_eq!(job.attempts, 1);
+        assert_eq!(job.printer_job, None);
+        let retry_at = job.retry_at.unwrap();
+        assert!(retry_at >= before + Duration::from_secs(60));
+        assert!(retry_at <= Instant::now() + Duration::from_secs(60));
+
+        // Not due yet, the printer is not asked again
+        assert!(!queue.process());
+        assert_eq!(queue.jobs[0].attempts, 1);
+        assert_eq!(operations(&fake), [PRINT_JOB]);
+        fs::remove_dir_all(&config.spool_dir).unwrap();
+    }
+
+    #[test]
+    fn jobs_are_given_up_after_max_attempts() {
+        let fake = FakePrinter::start(vec![http_error(), http_error()]);
+        let config = config("give-up", &fake.uri, 2, 0.);
+        let mut queue = open(&config);
+        let page = add_job(&mut queue);
+
+        assert!(queue.process());
+        assert_eq!(queue.jobs[0].attempts, 1);
+        assert!(queue.process());
+        assert!(queue.jobs.is_empty());
+        assert!(!page.exists());
+        assert_eq!(operations(&fake), [PRINT_JOB, PRINT_JOB]);
+        fs::remove_dir_all(&config.spool_dir).unwrap();
+    }
+
+    #[test]
+    fn aborted_jobs_are_submitted_again() {
+        let fake = FakePrinter::start(vec![
+            job_created(7),
+            job_in_state(ABORTED),
+            job_created(8),
+            job_in_state(COMPLETED),
+        ]);
+        let config = config("aborted", &fake.uri, 3, 0.);
+        let mut queue = open(&config);
+        let page = add_job(&mut queue);
+
+        assert!(queue.process());
+        assert_eq!(queue.jobs[0].printer_job, Some(7));
+        assert!(queue.process());
+        assert_eq!(queue.jobs[0].printer_job, None);
+        assert_eq!(queue.jobs[0].attempts, 1);
+        assert!(queue.process());
+        assert_eq!(queue.jobs[0].printer_job, Some(8));
+        assert!(queue.process());
+        assert!(queue.jobs.is_empty());
+        assert!(!page.exists());
+        assert_eq!(
+            operations(&fake),
+            [PRINT_JOB, GET_JOB_ATTRIBUTES, PRINT_JOB, GET_JOB_ATTRIBUTES]
+        );
+        fs::remove_dir_all(&config.spool_dir).unwrap();
+    }
+
+    #[test]
+    fn lost_answers_submit_the_job_again() {
+        // The printer took the first submission, but its answer never arrived
+        let fake = FakePrinter::start(vec![Vec::new(), job_created(9)]);
+        let config = config("lost", &fake.uri, 3, 0.);
+        let mut queue = open(&config);
+        add_job(&mut queue);
+
+        assert!(queue.process());
+        assert_eq!(queue.jobs[0].attempts, 1);
+        assert!(queue.process());
+        assert_eq!(queue.jobs[0].printer_job, Some(9));
+        assert_eq!(operations(&fake), [PRINT_JOB, PRINT_JOB]);
+        fs::remove_dir_all(&config.spool_dir).unwrap();
+    }
+
+    #[test]
+    fn unreachable_printers_do_not_count_as_attempts() {
+        // Nothing is scripted, the port closes right away
+        let fake = FakePrinter::start(Vec::new());
+        let config = config("offline", &fake.uri, 1, 60.);
+        let mut queue = open(&config);
+        add_job(&mut queue);
+        queue.jobs[0].printer_job = Some(4);
+
+        assert!(!queue.process());
+        assert_eq!(queue.jobs[0].attempts, 0);
+        assert!(queue.jobs[0].retry_at.is_some());
+        fs::remove_dir_all(&config.spool_dir).unwrap();
+    }
+
+    #[test]
+    fn queue_is_resumed_after_a_restart() {
+        let fake = FakePrinter::start(vec![job_in_state(COMPLETED), job_created(10)]);
+        let config = config("resume", &fake.uri, 3, 60.);
+        let mut queue = open(&config);
+        let submitted = add_job(&mut queue);
+        let waiting = add_job(&mut queue);
+        queue.jobs[0].printer_job = Some(3);
+        queue.jobs[1].attempts = 2;
+        queue.jobs[1].retry_at = Some(Instant::now() + Duration::from_secs(60));
+        queue.save().unwrap();
+        drop(queue);
+
+        let mut queue = open(&config);
+        assert_eq!(queue.next_id, 3);
+        assert_eq!(queue.jobs.len(), 2);
+        assert_eq!(queue.jobs[0].page, submitted);
+        assert_eq!(queue.jobs[0].printer_job, Some(3));
+        assert_eq!(queue.jobs[1].page, waiting);
+        assert_eq!(queue.jobs[1].attempts, 2);
+        // Retries are not saved, the waiting job is due right away
+        assert!(queue.jobs.iter().all(|job| job.retry_at.is_none()));
+
+        // The printer is asked about the job it already has instead of printing it again
+        assert!(queue.process());
+        assert_eq!(queue.jobs.len(), 1);
+        assert!(!submitted.exists());
+        assert_eq!(queue.jobs[0].printer_job, Some(10));
+        assert_eq!(operations(&fake), [GET_JOB_ATTRIBUTES, PRINT_JOB]);
+        fs::remove_dir_all(&config.spool_dir).unwrap();
+    }
+}
@@ -0,0 +1,185 @@
+use std::error::Error;
+
+use opencv::core::{Mat, Rect, Rect2f};
+use opencv::imgproc::{cvt_color, COLOR_BGRA2RGBA};
+use opencv::prelude::*;
+
+use crate::config::{FaceAnchor, PropConfig, PropsConfig};
+use crate::overlay::{blend_into, load_bgra};
+use crate::raylib::color::{DARKGRAY, WHITE};
+use crate::raylib::*;
+use crate::ui::palette;
+
+/// Empty space between a palette slot and the thumbnail in it
+const THUMBNAIL_PADDING: f32 = 8.;
+const NONE_FONT_SIZE: i32 = 20;
+
+/// Image that is put onto every detected face, like a hat or glasses
+#[derive(Clone)]
+pub struct Prop {
+    pub name: String,
+    /// BGRA
+    image: Mat,
+    anchor: FaceAnchor,
+    pivot: [f32; 2],
+    scale: f32,
+    offset: [f32; 2],
+}
+
+impl Prop {
+    fn load(config: &PropConfig) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            name: config.name.clone(),
+            image: load_bgra(&config.image)?,
+            anchor: config.anchor,
+            pivot: config.pivot,
+            scale: config.scale,
+            offset: config.offset,
+        })
+    }
+
+    /// Loads the images of all props, empty if props are disabled
+    pub fn load_all(config: &PropsConfig) -> Result<Vec<Self>, Box<dyn Error>> {
+        if !config.enabled {
+            return Ok(Vec::new());
+        }
+        config.items.iter().map(Self::load).collect()
+    }
+
+    /// Where the image goes for a face, in the coordinates of the face
+    pub fn placement(&self, face: Rect) -> Rect2f {
+        let width = face.width as f32 * self.scale;
+        let height = width * self.image.rows() as f32 / self.image.cols() as f32;
+        let anchor_x = face.x as f32 + face.width as f32 * (0.5 + self.offset[0]);
+        let anchor_y = face.y as f32 + face.height as f32 * (self.anchor.height() + self.offset[1]);
+        Rect2f::new(
+            anchor_x - width * self.pivot[0],
+            anchor_y - height * self.pivot[1],
+            width,
+            height,
+        )
+    }
+
+    /// Alpha blends the prop onto every face of the BGR image, cutting it off at the image border
+    pub fn apply(&self, image: &Mat, faces: &[Rect]) -> Result<Mat, Box<dyn Error>> {
+        let mut composed = image.try_clone()?;
+        for face in faces {
+            let placement = self.placement(*face);
+            let rect = Rect::new(
+                placement.x.round() as i32,
+                placement.y.round() as i32,
+                (placement.width.round() as i32).max(1),
+                (placement.height.round() as i32).max(1),
+            );
+            blend_into(&mut composed, &self.image, rect)?;
+        }
+        Ok(composed)
+    }
+}
+
+/// Textures of the props for the live preview and the palette
+pub struct PropTextures {
+    /// `None` for props whose texture could not be created, they are only missing in the preview
+    textures: Vec<Option<Texture>>,
+}
+
+impl PropTextures {
+    /// Uploads the prop images, the window has to be initialized
+    pub fn load(props: &[Prop]) -> Self {
+        let textures = props
+            .iter()
+            .map(|prop| match prop_texture(prop) {
+                Ok(texture) => Some(texture),
+                Err(e) => {
+                    eprintln!("Could not load the {} prop: {e}", prop.name);
+                    None
+                }
+            })
+            .collect();
+        Self { textures }
+    }
+
+    /// Draws the prop onto the faces of a preview that is drawn at `position` with `scale`
+    pub fn draw_on_faces(
+        &self,
+        prop: &Prop,
+        index: usize,
+        faces: &[Rect],
+        position: Vector2,
+        scale: f32,
+    ) {
+        let Some(Some(texture)) = self.textures.get(index) else {
+            return;
+        };
+        for face in faces {
+            let placement = prop.placement(*face);
+            texture.draw_ex(
+                Vector2(
+                    position.0 + placement.x * scale,
+                    position.1 + placement.y * scale,
+                ),
+                0.,
+                placement.width * scale / texture.width as f32,
+                WHITE,
+            );
+        }
+    }
+
+    /// Draws the palette, "None" first and then the props, with the `selected` prop outlined
+    pub fn draw_palette(&self, selected: Option<usize>, screen_size: Vector2) {
+        let slots = palette(self.textures.len() + 1, screen_size);
+        for (i, slot) in slots.iter().enumerate() {
+            draw_rectangle(
+                slot.x.round() as i32,
+                slot.y.round() as i32,
+                slot.width.round() as i32,
+                slot.height.round() as i32,
+                DARKGRAY.fade(0.7),
+            );
+            match i.checked_sub(1) {
+                None => {
+                    let text_width = measure_text("None", NONE_FONT_SIZE);
+                    draw_text(
+                        "None",
+                        (slot.x + (slot.width - text_width as f32) / 2.).round() as i32,
+                        (slot.y + (slot.height - NONE_FONT_SIZE as f32) / 2.).round() as i32,
+                        NONE_FONT_SIZE,
+                        WHITE,
+                    );
+                }
+                Some(prop) => {
+                    if let Some(texture) = &self.textures[prop] {
+                        let size = slot.width - 2. * THUMBNAIL_PADDING;
+                        let scale = (size / texture.width as f32).min(size / texture.height as f32);
+                        texture.draw_ex(
+                            Vector2(
+                                slot.x + (slot.width - texture.width as f32 * scale) / 2.,
+                                slot.y + (slot.height - texture.height as f32 * scale) / 2.,
+                            ),
+                            0.,
+                            scale,
+                            WHITE,
+                        );
+                    }
+                }
+            }
+            if i.checked_sub(1) == selected {
+                draw_rectangle_lines(*slot, 4., WHITE);
+            }
+        }
+    }
+}
+
+fn prop_texture(prop: &Prop) -> Result<Texture, Box<dyn Error>> {
+    let mut rgba = Mat::default();
+    cvt_color(&prop.image, &mut rgba, COLOR_BGRA2RGBA, 0)?;
+    Texture::try_from(rgba)
+}
+
+/// Palette slot at `point`, `Some(None)` for the "None" slot and `Some(Some(i))` for prop `i`
+pub fn palette_slot(props: usize, screen_size: Vector2, point: Vector2) -> Option<Option<usize>> {
+    palette(props + 1, screen_size)
+        .iter()
+        .position(|slot| slot.contains(point))
+        .map(|i| i.checked_sub(1))
+}
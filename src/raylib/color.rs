@@ -1,7 +1,7 @@
 #![allow(dead_code)]
 use super::RColor;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -20,6 +20,16 @@ impl From<u32> for Color {
     }
 }
 
+impl Color {
+    /// Same color with the alpha multiplied by `alpha` (0.0 to 1.0)
+    pub fn fade(self, alpha: f32) -> Self {
+        Self {
+            a: (self.a as f32 * alpha.clamp(0., 1.)).round() as u8,
+            ..self
+        }
+    }
+}
+
 impl From<Color> for RColor {
     fn from(value: Color) -> Self {
         Self {
@@ -3,7 +3,9 @@ pub mod color;
 pub mod config_flags;
 pub mod sys;
 use std::{
+    error::Error,
     ffi::{c_void, CString},
+    marker::PhantomData,
     mem::{self, forget, ManuallyDrop},
     ops,
     os::raw::c_uint,
@@ -60,12 +62,10 @@ pub fn set_target_fps(fps: int) {
     }
 }
 
-pub fn load_texture_mat(m: Mat) -> RTexture {
-    unsafe {
-        let img: RImage = (&m).into();
-
-        LoadTextureFromImage(img)
-    }
+pub fn load_texture_mat(m: Mat) -> Result<RTexture, Box<dyn Error>> {
+    let m = continuous(m)?;
+    let img = RImage::try_from(&m)?;
+    Ok(unsafe { LoadTextureFromImage(img) })
 }
 
 pub fn draw_texture(texture: &RTexture, pos_x: int, pos_y: int, tint: Color) {
@@ -92,9 +92,41 @@ pub fn draw_texture_ex(
     };
 }
 
-pub fn update_texture(texture: &RTexture, m: &Mat) {
-    unsafe {
-        UpdateTexture(*texture, m.data() as *mut c_void);
+/// Uploads the pixels of a Mat with the size and pixel format of the texture
+pub fn update_texture(texture: &RTexture, m: &Mat) -> Result<(), Box<dyn Error>> {
+    if m.cols() != texture.width || m.rows() != texture.height {
+        return Err(format!(
+            "Mat of {}x{} does not fit texture of {}x{}",
+            m.cols(),
+            m.rows(),
+            texture.width,
+            texture.height
+        )
+        .into());
+    }
+    let format = pixel_format(m)?;
+    if format != texture.format {
+        return Err(format!(
+            "Mat with {format:?} does not match texture with {:?}",
+            texture.format
+        )
+        .into());
+    }
+    if m.is_continuous() {
+        unsafe { UpdateTexture(*texture, m.data() as *mut c_void) };
+    } else {
+        let copy = m.try_clone()?;
+        unsafe { UpdateTexture(*texture, copy.data() as *mut c_void) };
+    }
+    Ok(())
+}
+
+/// Copies non-continuous Mats like ROIs so their pixels can be handed to raylib
+fn continuous(m: Mat) -> Result<Mat, Box<dyn Error>> {
+    if m.is_continuous() {
+        Ok(m)
+    } else {
+        Ok(m.try_clone()?)
     }
 }
 pub fn draw_text(text: &str, pos_x: int, pos_y: int, font_size: int, color: Color) {
@@ -102,6 +134,20 @@ pub fn draw_text(text: &str, pos_x: int, pos_y: int, font_size: int, color: Colo
     unsafe { DrawText(text.as_ptr(), pos_x, pos_y, font_size, color.into()) };
 }
 
+pub fn draw_rectangle(pos_x: int, pos_y: int, width: int, height: int, color: Color) {
+    unsafe { DrawRectangle(pos_x, pos_y, width, height, color.into()) };
+}
+
+/// Draws the outline of `rectangle`, the lines are drawn inside of it
+pub fn draw_rectangle_lines(rectangle: Rectangle, thickness: f32, color: Color) {
+    unsafe { DrawRectangleLinesEx(rectangle, thickness, color.into()) };
+}
+
+pub fn measure_text(text: &str, font_size: int) -> int {
+    let text = CString::new(text).unwrap();
+    unsafe { MeasureText(text.as_ptr(), font_size) }
+}
+
 pub fn draw_fps(pos_x: int, pos_y: int) {
     unsafe { DrawFPS(pos_x, pos_y) };
 }
@@ -123,6 +169,44 @@ pub fn is_key_pressed(key: KeyboardKeys) -> bool {
     unsafe { IsKeyPressed(key) }
 }
 
+pub fn is_key_down(key: KeyboardKeys) -> bool {
+    unsafe { IsKeyDown(key) }
+}
+
+pub fn is_mouse_button_pressed(button: MouseButton) -> bool {
+    unsafe { IsMouseButtonPressed(button) }
+}
+pub fn is_mouse_button_down(button: MouseButton) -> bool {
+    unsafe { IsMouseButtonDown(button) }
+}
+pub fn get_mouse_position() -> Vector2 {
+    unsafe { GetMousePosition() }.into()
+}
+
+pub fn get_touch_position(index: int) -> Vector2 {
+    unsafe { GetTouchPosition(index) }.into()
+}
+pub fn get_touch_point_count() -> int {
+    unsafe { GetTouchPointCount() }
+}
+
+/// Enables the gestures from `sys::gestures` given as bit flags
+pub fn set_gestures_enabled(flags: u32) {
+    unsafe { SetGesturesEnabled(flags) }
+}
+pub fn is_gesture_detected(gesture: u32) -> bool {
+    unsafe { IsGestureDetected(gesture) }
+}
+pub fn get_gesture_detected() -> u32 {
+    unsafe { GetGestureDetected() as u32 }
+}
+pub fn get_gesture_hold_duration() -> f32 {
+    unsafe { GetGestureHoldDuration() }
+}
+pub fn get_gesture_drag_vector() -> Vector2 {
+    unsafe { GetGestureDragVector() }.into()
+}
+
 pub fn draw_ring(
     center: Vector2,
     inner_radius: f32,
@@ -144,6 +228,16 @@ pub fn draw_ring(
         )
     };
 }
+pub fn init_audio_device() {
+    unsafe { InitAudioDevice() };
+}
+pub fn close_audio_device() {
+    unsafe { CloseAudioDevice() };
+}
+pub fn is_audio_device_ready() -> bool {
+    unsafe { IsAudioDeviceReady() }
+}
+
 pub fn load_image(file: &str) -> RImage {
     let file_type = CString::new(file).unwrap();
     unsafe { LoadImage(file_type.as_ptr()) }
@@ -398,9 +492,37 @@ pub enum KeyboardKeys {
     KEY_VOLUME_DOWN = 25,
 }
 
+/// Mouse buttons
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+#[allow(dead_code)]
+pub enum MouseButton {
+    /// Mouse button left
+    MOUSE_BUTTON_LEFT = 0,
+    /// Mouse button right
+    MOUSE_BUTTON_RIGHT = 1,
+    /// Mouse button middle (pressed wheel)
+    MOUSE_BUTTON_MIDDLE = 2,
+    /// Mouse button side (advanced mouse device)
+    MOUSE_BUTTON_SIDE = 3,
+    /// Mouse button extra (advanced mouse device)
+    MOUSE_BUTTON_EXTRA = 4,
+    /// Mouse button forward (advanced mouse device)
+    MOUSE_BUTTON_FORWARD = 5,
+    /// Mouse button back (advanced mouse device)
+    MOUSE_BUTTON_BACK = 6,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Vector2(pub f32, pub f32);
 
+impl Vector2 {
+    pub fn distance(self, other: Vector2) -> f32 {
+        ((self.0 - other.0).powi(2) + (self.1 - other.1).powi(2)).sqrt()
+    }
+}
+
 impl ops::Div<f32> for Vector2 {
     type Output = Self;
 
@@ -410,9 +532,27 @@ impl ops::Div<f32> for Vector2 {
     }
 }
 
-#[allow(non_camel_case_types)]
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
+pub struct Rectangle {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rectangle {
+    pub fn contains(&self, point: Vector2) -> bool {
+        point.0 >= self.x
+            && point.0 <= self.x + self.width
+            && point.1 >= self.y
+            && point.1 <= self.y + self.height
+    }
+}
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[allow(dead_code)]
 pub enum PixelFormat {
     /// 8 bit per pixel (no alpha)
@@ -525,16 +665,18 @@ impl From<&Image> for Texture {
     }
 }
 
-impl From<Mat> for Texture {
-    fn from(mat: Mat) -> Self {
-        let image: Image = mat.into();
-        (&image).into()
+impl TryFrom<Mat> for Texture {
+    type Error = Box<dyn Error>;
+
+    fn try_from(mat: Mat) -> Result<Self, Self::Error> {
+        let image = Image::try_from(mat)?;
+        Ok((&image).into())
     }
 }
 
 impl Texture {
-    pub fn update(&self, mat: &Mat) {
-        update_texture(&self.into(), mat);
+    pub fn update(&self, mat: &Mat) -> Result<(), Box<dyn Error>> {
+        update_texture(&self.into(), mat)
     }
 
     pub fn draw_ex(&self, position: Vector2, rotation: f32, scale: f32, tint: Color) {
@@ -553,12 +695,16 @@ pub struct Image {
 
 unsafe impl Send for Image {}
 
-impl From<Mat> for Image {
-    fn from(mat: Mat) -> Self {
-        Self {
-            image: (&mat).into(),
+/// Keeps the Mat alive as long as the image borrows its pixels
+impl TryFrom<Mat> for Image {
+    type Error = Box<dyn Error>;
+
+    fn try_from(mat: Mat) -> Result<Self, Self::Error> {
+        let mat = continuous(mat)?;
+        Ok(Self {
+            image: RImage::try_from(&mat)?,
             mat: Some(ManuallyDrop::new(mat)),
-        }
+        })
     }
 }
 
@@ -611,3 +757,181 @@ impl Image {
         }
     }
 }
+
+/// Type of a shader uniform value
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderUniformDataType {
+    SHADER_UNIFORM_FLOAT = 0,
+    SHADER_UNIFORM_VEC2,
+    SHADER_UNIFORM_VEC3,
+    SHADER_UNIFORM_VEC4,
+    SHADER_UNIFORM_INT,
+    SHADER_UNIFORM_IVEC2,
+    SHADER_UNIFORM_IVEC3,
+    SHADER_UNIFORM_IVEC4,
+    SHADER_UNIFORM_SAMPLER2D,
+}
+
+/// Compiled shader program, unloaded when dropped
+#[derive(Debug)]
+pub struct Shader {
+    shader: RShader,
+}
+
+impl Drop for Shader {
+    fn drop(&mut self) {
+        unsafe { UnloadShader(self.shader) };
+    }
+}
+
+impl Shader {
+    /// Compiles the shader from source code, `None` uses raylib's default vertex or fragment shader.
+    /// The window has to be initialized.
+    pub fn new_from_memory(
+        vertex: Option<&str>,
+        fragment: Option<&str>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let vertex = vertex.map(CString::new).transpose()?;
+        let fragment = fragment.map(CString::new).transpose()?;
+        let shader = unsafe {
+            LoadShaderFromMemory(
+                vertex
+                    .as_ref()
+                    .map_or(std::ptr::null(), |code| code.as_ptr()),
+                fragment
+                    .as_ref()
+                    .map_or(std::ptr::null(), |code| code.as_ptr()),
+            )
+        };
+        // raylib logs the compiler output and falls back to its default shader
+        if shader.id == unsafe { rlGetShaderIdDefault() } {
+            return Err("Could not compile shader, see the raylib log for details".into());
+        }
+        Ok(Self { shader })
+    }
+
+    /// Location of a uniform, `None` if the shader does not use it
+    pub fn location(&self, name: &str) -> Option<int> {
+        let name = CString::new(name).ok()?;
+        let location = unsafe { GetShaderLocation(self.shader, name.as_ptr()) };
+        (location >= 0).then_some(location)
+    }
+
+    fn set_value<T>(&self, name: &str, value: &T, uniform_type: ShaderUniformDataType) {
+        if let Some(location) = self.location(name) {
+            unsafe {
+                SetShaderValue(
+                    self.shader,
+                    location,
+                    value as *const T as *const c_void,
+                    uniform_type,
+                )
+            };
+        }
+    }
+
+    /// Sets a float uniform, ignored if the shader does not use it
+    pub fn set_float(&self, name: &str, value: f32) {
+        self.set_value(name, &value, ShaderUniformDataType::SHADER_UNIFORM_FLOAT);
+    }
+
+    /// Sets a vec2 uniform, ignored if the shader does not use it
+    pub fn set_vec2(&self, name: &str, value: Vector2) {
+        self.set_value(
+            name,
+            &[value.0, value.1],
+            ShaderUniformDataType::SHADER_UNIFORM_VEC2,
+        );
+    }
+
+    /// Draws with this shader until the returned guard is dropped
+    pub fn begin(&self) -> ShaderMode<'_> {
+        unsafe { BeginShaderMode(self.shader) };
+        ShaderMode {
+            _shader: PhantomData,
+        }
+    }
+}
+
+/// Active shader mode, ends it when dropped
+pub struct ShaderMode<'a> {
+    _shader: PhantomData<&'a Shader>,
+}
+
+impl Drop for ShaderMode<'_> {
+    fn drop(&mut self) {
+        unsafe { EndShaderMode() };
+    }
+}
+
+#[derive(Debug)]
+pub struct Sound {
+    sound: RSound,
+}
+
+impl Drop for Sound {
+    fn drop(&mut self) {
+        unsafe { UnloadSound(self.sound) };
+    }
+}
+
+impl Sound {
+    /// Loads a sound file, the audio device has to be initialized
+    pub fn new(file: &str) -> Self {
+        let file = CString::new(file).unwrap();
+        Self {
+            sound: unsafe { LoadSound(file.as_ptr()) },
+        }
+    }
+
+    /// raylib returns an empty sound if the file could not be loaded
+    pub fn is_valid(&self) -> bool {
+        self.sound.frame_count > 0
+    }
+
+    pub fn play(&self) {
+        unsafe { PlaySound(self.sound) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opencv::core::{Rect, Scalar, Size, Vec3b, CV_8UC3};
+    use opencv::prelude::*;
+
+    /// 4x3 RGB Mat where every byte holds its own index
+    fn numbered() -> Mat {
+        let mut m = Mat::new_size_with_default(Size::new(4, 3), CV_8UC3, Scalar::all(0.)).unwrap();
+        for row in 0..3 {
+            for col in 0..4 {
+                let i = ((row * 4 + col) * 3) as u8;
+                *m.at_2d_mut::<Vec3b>(row, col).unwrap() = Vec3b::from([i, i + 1, i + 2]);
+            }
+        }
+        m
+    }
+
+    #[test]
+    fn roi_is_copied_into_a_tightly_packed_buffer() {
+        let full = numbered();
+        // Shares the pixels of `full`, so its rows are 4 pixels apart
+        let roi = Mat::roi(&full, Rect::new(1, 1, 2, 2))
+            .unwrap()
+            .clone_pointee();
+        assert!(!roi.is_continuous());
+        assert!(RImage::try_from(&roi).is_err());
+
+        let packed = continuous(roi).unwrap();
+        assert!(packed.is_continuous());
+        // Rows 1 and 2, columns 1 and 2 of the numbered Mat without the gap of the skipped columns
+        assert_eq!(
+            packed.data_bytes().unwrap(),
+            [15, 16, 17, 18, 19, 20, 27, 28, 29, 30, 31, 32]
+        );
+        let image = RImage::try_from(&packed).unwrap();
+        assert_eq!((image.width, image.height), (2, 2));
+    }
+}
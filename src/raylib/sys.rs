@@ -12,10 +12,31 @@ extern "C" {
     pub(super) fn ClearBackground(color: RColor);
     pub(super) fn SetConfigFlags(config_flags: u32);
     pub(super) fn LoadShader(vsFileName: *const c_char, fsFileName: *const c_char) -> RShader;
+    pub(super) fn LoadShaderFromMemory(vsCode: *const c_char, fsCode: *const c_char) -> RShader;
+    pub(super) fn UnloadShader(shader: RShader);
+    pub(super) fn GetShaderLocation(shader: RShader, uniformName: *const c_char) -> c_int;
+    pub(super) fn SetShaderValue(
+        shader: RShader,
+        locIndex: c_int,
+        value: *const c_void,
+        uniformType: ShaderUniformDataType,
+    );
+    pub(super) fn BeginShaderMode(shader: RShader);
+    pub(super) fn EndShaderMode();
+    /// Id of the default shader raylib falls back to if a shader fails to compile
+    pub(super) fn rlGetShaderIdDefault() -> c_uint;
     pub(super) fn LoadTextureFromImage(image: RImage) -> RTexture;
     pub(super) fn DrawTexture(texture: RTexture, posX: c_int, posY: c_int, tint: RColor);
     pub(super) fn UpdateTexture(texture: RTexture, pixels: *mut c_void);
     pub(super) fn DrawFPS(posX: c_int, posY: c_int); // Draw current FPS
+    pub(super) fn DrawRectangle(
+        posX: c_int,
+        posY: c_int,
+        width: c_int,
+        height: c_int,
+        color: RColor,
+    ); // Draw a color-filled rectangle
+    pub(super) fn DrawRectangleLinesEx(rec: Rectangle, lineThick: c_float, color: RColor);
     pub(super) fn DrawText(
         text: *const c_char,
         posX: c_int,
@@ -23,6 +44,7 @@ extern "C" {
         fontSize: c_int,
         color: RColor,
     ); // Draw text (using default font)
+    pub(super) fn MeasureText(text: *const c_char, fontSize: c_int) -> c_int; // Measure string width for default font
     pub(super) fn GetScreenWidth() -> c_int;
     pub(super) fn GetScreenHeight() -> c_int;
     pub(super) fn ToggleFullscreen();
@@ -36,6 +58,8 @@ extern "C" {
     );
     // Check if a key has been pressed once
     pub(super) fn IsKeyPressed(key: KeyboardKeys) -> bool;
+    // Check if a key is being pressed
+    pub(super) fn IsKeyDown(key: KeyboardKeys) -> bool;
     // Draw ring
     pub(super) fn DrawRing(
         center: RVector2,
@@ -46,6 +70,19 @@ extern "C" {
         segments: c_int,
         color: RColor,
     );
+    // Input-related functions: mouse
+    pub(super) fn IsMouseButtonPressed(button: MouseButton) -> bool; // Check if a mouse button has been pressed once
+    pub(super) fn IsMouseButtonDown(button: MouseButton) -> bool; // Check if a mouse button is being pressed
+    pub(super) fn GetMousePosition() -> RVector2; // Get mouse position XY
+                                                  // Input-related functions: touch
+    pub(super) fn GetTouchPosition(index: c_int) -> RVector2; // Get touch position XY for a touch point index (relative to screen size)
+    pub(super) fn GetTouchPointCount() -> c_int; // Get number of touch points
+                                                 // Gestures and Touch Handling Functions (Module: rgestures)
+    pub(super) fn SetGesturesEnabled(flags: c_uint); // Enable a set of gestures using flags
+    pub(super) fn IsGestureDetected(gesture: c_uint) -> bool; // Check if a gesture have been detected
+    pub(super) fn GetGestureDetected() -> c_int; // Get latest detected gesture
+    pub(super) fn GetGestureHoldDuration() -> c_float; // Get gesture hold time in seconds
+    pub(super) fn GetGestureDragVector() -> RVector2; // Get gesture drag vector
     pub(super) fn LoadImageFromMemory(
         fileType: *const c_char,
         fileData: *const c_uchar,
@@ -53,6 +90,19 @@ extern "C" {
     ) -> RImage;
     pub(super) fn LoadImage(fileName: *const c_char) -> RImage;
 
+    /// Initialize audio device and context
+    pub(super) fn InitAudioDevice();
+    /// Close the audio device and context
+    pub(super) fn CloseAudioDevice();
+    /// Check if audio device has been initialized successfully
+    pub(super) fn IsAudioDeviceReady() -> bool;
+    /// Load sound from file
+    pub(super) fn LoadSound(fileName: *const c_char) -> RSound;
+    /// Unload sound
+    pub(super) fn UnloadSound(sound: RSound);
+    /// Play a sound
+    pub(super) fn PlaySound(sound: RSound);
+
     /// Unload texture from GPU memory (VRAM)
     pub fn UnloadTexture(texture: RTexture);
     /// Create an image duplicate (useful for transformations)
@@ -62,9 +112,11 @@ extern "C" {
 
 }
 
-use opencv::core::{Mat, MatTraitConst, CV_8UC3};
+use std::error::Error;
 
-use super::{KeyboardKeys, PixelFormat, Vector2};
+use opencv::core::{type_to_string, Mat, MatTraitConst, CV_8UC1, CV_8UC3, CV_8UC4};
+
+use super::{KeyboardKeys, MouseButton, PixelFormat, Rectangle, ShaderUniformDataType, Vector2};
 /// Gesture
 /// NOTE: Provided as bit-wise flags to enable only desired gestures2
 #[allow(non_camel_case_types)]
@@ -103,6 +155,12 @@ impl From<Vector2> for RVector2 {
     }
 }
 
+impl From<RVector2> for Vector2 {
+    fn from(value: RVector2) -> Self {
+        Self(value.0, value.1)
+    }
+}
+
 #[repr(C)]
 #[derive(Debug)]
 pub struct RImage {
@@ -139,30 +197,65 @@ pub struct RTexture {
     pub format: PixelFormat, // Data format (PixelFormat type)
 }
 
-impl From<&Mat> for RImage {
-    fn from(m: &Mat) -> Self {
-        let f = match m.typ() {
-            CV_8UC3 => PixelFormat::PIXELFORMAT_UNCOMPRESSED_R8G8B8,
-            _ => panic!("Matrix Type not supported\n {:#?}", m),
-        };
+/// raylib pixel format of an 8 bit Mat, the channels have to be in RGB(A) order already
+pub fn pixel_format(m: &Mat) -> Result<PixelFormat, Box<dyn Error>> {
+    match m.typ() {
+        CV_8UC1 => Ok(PixelFormat::PIXELFORMAT_UNCOMPRESSED_GRAYSCALE),
+        CV_8UC3 => Ok(PixelFormat::PIXELFORMAT_UNCOMPRESSED_R8G8B8),
+        CV_8UC4 => Ok(PixelFormat::PIXELFORMAT_UNCOMPRESSED_R8G8B8A8),
+        typ => Err(format!(
+            "Mat type {} is not supported",
+            type_to_string(typ).unwrap_or_else(|_| typ.to_string())
+        )
+        .into()),
+    }
+}
 
-        let size = m.size().unwrap();
-        let data = m.data() as *mut c_void;
+/// Borrows the pixels of the Mat, which has to outlive the image.
+/// Non-continuous Mats like ROIs are rejected, `Image::try_from` copies them instead.
+impl TryFrom<&Mat> for RImage {
+    type Error = Box<dyn Error>;
 
-        Self {
-            data,
-            width: size.width,
-            height: size.height,
-            mipmaps: 1,
-            format: f,
+    fn try_from(m: &Mat) -> Result<Self, Self::Error> {
+        if m.empty() {
+            return Err("Mat is empty".into());
+        }
+        let format = pixel_format(m)?;
+        if !m.is_continuous() {
+            return Err("Mat is not continuous".into());
         }
+        Ok(Self {
+            data: m.data() as *mut c_void,
+            width: m.cols(),
+            height: m.rows(),
+            mipmaps: 1,
+            format,
+        })
     }
 }
 
 #[repr(C)]
+#[derive(Debug, Clone, Copy)]
 pub struct RShader {
-    id: c_uint,       // Shader program id
-    locs: *mut c_int, // Shader locations array (RL_MAX_SHADER_LOCATIONS)
+    pub(super) id: c_uint,       // Shader program id
+    pub(super) locs: *mut c_int, // Shader locations array (RL_MAX_SHADER_LOCATIONS)
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RAudioStream {
+    buffer: *mut c_void,    // Pointer to internal data used by the audio system
+    processor: *mut c_void, // Pointer to internal data processor, useful for audio effects
+    sample_rate: c_uint,    // Frequency (samples per second)
+    sample_size: c_uint,    // Bit depth (bits per sample): 8, 16, 32 (24 not supported)
+    channels: c_uint,       // Number of channels (1-mono, 2-stereo, ...)
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RSound {
+    stream: RAudioStream,    // Audio stream
+    pub frame_count: c_uint, // Total number of frames (considering channels)
 }
 
 #[repr(C)]
@@ -172,3 +265,51 @@ pub struct RColor {
     pub b: c_uchar,
     pub a: c_uchar,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opencv::core::{Scalar, Size, CV_16UC3, CV_32FC1};
+
+    fn mat(typ: i32) -> Mat {
+        Mat::new_size_with_default(Size::new(4, 2), typ, Scalar::all(0.)).unwrap()
+    }
+
+    #[test]
+    fn pixel_format_of_8_bit_mats() {
+        assert_eq!(
+            pixel_format(&mat(CV_8UC1)).unwrap(),
+            PixelFormat::PIXELFORMAT_UNCOMPRESSED_GRAYSCALE
+        );
+        assert_eq!(
+            pixel_format(&mat(CV_8UC3)).unwrap(),
+            PixelFormat::PIXELFORMAT_UNCOMPRESSED_R8G8B8
+        );
+        assert_eq!(
+            pixel_format(&mat(CV_8UC4)).unwrap(),
+            PixelFormat::PIXELFORMAT_UNCOMPRESSED_R8G8B8A8
+        );
+    }
+
+    #[test]
+    fn pixel_format_rejects_other_depths() {
+        let error = pixel_format(&mat(CV_16UC3)).unwrap_err();
+        assert!(error.to_string().contains("CV_16UC3"), "{error}");
+        assert!(pixel_format(&mat(CV_32FC1)).is_err());
+    }
+
+    #[test]
+    fn image_borrows_continuous_mats() {
+        let m = mat(CV_8UC3);
+        let image = RImage::try_from(&m).unwrap();
+        assert_eq!(image.data as *const u8, m.data());
+        assert_eq!((image.width, image.height), (4, 2));
+        assert_eq!(image.format, PixelFormat::PIXELFORMAT_UNCOMPRESSED_R8G8B8);
+    }
+
+    #[test]
+    fn image_rejects_empty_and_unsupported_mats() {
+        assert!(RImage::try_from(&Mat::default()).is_err());
+        assert!(RImage::try_from(&mat(CV_32FC1)).is_err());
+    }
+}
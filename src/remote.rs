@@ -0,0 +1,161 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use serde::Serialize;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::config::RemoteConfig;
+use crate::display_options::{FILL, SHOW_DEBUG_IMAGE, SHOW_DEBUG_INFO};
+
+/// Sent from the HTTP server to the render loop
+pub enum RemoteCommand {
+    Trigger,
+    ToggleDisplayOption(u32),
+}
+
+/// Published by the render loop every frame, served by `GET /state`
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct RemoteStatus {
+    pub state: &'static str,
+    pub camera_connected: bool,
+    pub latest_photo: Option<PathBuf>,
+    pub fill: bool,
+    pub show_debug_info: bool,
+    pub show_debug_image: bool,
+    /// Name of the selected filter
+    pub filter: String,
+    /// Faces in the latest preview frame, 0 if face detection is disabled
+    pub faces: usize,
+}
+
+/// Starts the HTTP API in its own thread if `remote.bind` is set
+pub fn start_remote(
+    config: &RemoteConfig,
+    tx: Sender<RemoteCommand>,
+    status: Arc<Mutex<RemoteStatus>>,
+) -> Result<(), Box<dyn Error>> {
+    let Some(bind) = &config.bind else {
+        return Ok(());
+    };
+    let server =
+        Server::http(bind).map_err(|e| format!("Could not start HTTP API on {bind}: {e}"))?;
+    println!("HTTP API listening on {bind}");
+
+    let token = config.token.clone();
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let result = if is_authorized(&request, token.as_deref()) {
+                handle(request, &tx, &status)
+            } else {
+                request.respond(text(401, "Unauthorized"))
+            };
+            if let Err(e) = result {
+                eprintln!("Could not answer HTTP request: {e}");
+            }
+        }
+    });
+    Ok(())
+}
+
+fn is_authorized(request: &Request, token: Option<&str>) -> bool {
+    let Some(token) = token else {
+        return true;
+    };
+    let expected = format!("Bearer {token}");
+    request.headers().iter().any(|header| {
+        header.field.equiv("Authorization")
+            && constant_time_eq(header.value.as_str().as_bytes(), expected.as_bytes())
+    })
+}
+
+/// Compares without returning early so the time taken does not reveal how much of the token matched
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn text(status: u16, body: &str) -> Response<std::io::Cursor<Vec<u8>>> {
+    Response::from_string(body).with_status_code(status)
+}
+
+fn handle(
+    request: Request,
+    tx: &Sender<RemoteCommand>,
+    status: &Mutex<RemoteStatus>,
+) -> std::io::Result<()> {
+    let path = request.url().split('?').next().unwrap_or("").to_string();
+    let command = match (request.method(), path.as_str()) {
+        (Method::Post, "/trigger") => Some(RemoteCommand::Trigger),
+        (Method::Post, "/display/fill") => Some(RemoteCommand::ToggleDisplayOption(FILL)),
+        (Method::Post, "/display/debug-info") => {
+            Some(RemoteCommand::ToggleDisplayOption(SHOW_DEBUG_INFO))
+        }
+        (Method::Post, "/display/debug-image") => {
+            Some(RemoteCommand::ToggleDisplayOption(SHOW_DEBUG_IMAGE))
+        }
+        _ => None,
+    };
+    if let Some(command) = command {
+        return match tx.send(command) {
+            Ok(()) => request.respond(text(202, "Accepted")),
+            Err(_) => request.respond(text(503, "Kiosk is shutting down")),
+        };
+    }
+
+    match (request.method(), path.as_str()) {
+        (Method::Get, "/state") => {
+            let status = status
+                .lock()
+                .map(|status| status.clone())
+                .unwrap_or_default();
+            match serde_json::to_string(&status) {
+                Ok(json) => request.respond(
+                    Response::from_string(json).with_header(content_type("application/json")),
+                ),
+                Err(e) => request.respond(text(500, &e.to_string())),
+            }
+        }
+        (Method::Get, "/photo/latest") => {
+            let latest = status
+                .lock()
+                .ok()
+                .and_then(|status| status.latest_photo.clone());
+            let Some(path) = latest else {
+                return request.respond(text(404, "No photo taken yet"));
+            };
+            let mut data = Vec::new();
+            if let Err(e) = File::open(&path).and_then(|mut file| file.read_to_end(&mut data)) {
+                return request.respond(text(404, &e.to_string()));
+            }
+            let mime = match path.extension().and_then(|extension| extension.to_str()) {
+                Some("png") => "image/png",
+                Some("webp") => "image/webp",
+                Some("gif") => "image/gif",
+                Some("mp4") => "video/mp4",
+                _ => "image/jpeg",
+            };
+            request.respond(Response::from_data(data).with_header(content_type(mime)))
+        }
+        (
+            _,
+            "/trigger"
+            | "/state"
+            | "/photo/latest"
+            | "/display/fill"
+            | "/display/debug-info"
+            | "/display/debug-image",
+        ) => request.respond(text(405, "Method not allowed")),
+        _ => request.respond(text(404, "Not found")),
+    }
+}
+
+fn content_type(mime: &str) -> Header {
+    Header::from_bytes("Content-Type", mime).expect("Content type header is valid")
+}
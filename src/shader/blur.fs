@@ -1,30 +1,18 @@
-void mainImage( out vec4 fragColor, in vec2 fragCoord )
+// Distance between two samples in texture coordinates
+uniform vec2 blurStep;
+
+void main()
 {
-    float Pi = 6.28318530718; // Pi*2
-    
-    // GAUSSIAN BLUR SETTINGS {{{
-    float Directions = 16.0; // BLUR DIRECTIONS (Default 16.0 - More is better but slower)
-    float Quality = 3.0; // BLUR QUALITY (Default 4.0 - More is better but slower)
-    float Size = 8.0; // BLUR SIZE (Radius)
-    // GAUSSIAN BLUR SETTINGS }}}
-   
-    vec2 Radius = Size/iResolution.xy;
-    
-    // Normalized pixel coordinates (from 0 to 1)
-    vec2 uv = fragCoord/iResolution.xy;
-    // Pixel colour
-    vec4 Color = texture(iChannel0, uv);
-    
-    // Blur calculations
-    for( float d=0.0; d<Pi; d+=Pi/Directions)
-    {
-		for(float i=1.0/Quality; i<=1.0; i+=1.0/Quality)
-        {
-			Color += texture( iChannel0, uv+vec2(cos(d),sin(d))*Radius*i);		
+    // 5x5 Gaussian, loops have constant bounds for GLSL ES 1.0
+    vec4 sum = vec4(0.0);
+    float total = 0.0;
+    for (int x = -2; x <= 2; x++) {
+        for (int y = -2; y <= 2; y++) {
+            vec2 offset = vec2(float(x), float(y));
+            float weight = exp(-dot(offset, offset) / 4.0);
+            sum += TEXTURE(texture0, fragTexCoord + offset * blurStep) * weight;
+            total += weight;
         }
     }
-    
-    // Output to screen
-    Color /= Quality * Directions - 15.0;
-    fragColor =  Color;
+    OUT = sum / total * colDiffuse * fragColor;
 }
@@ -0,0 +1,7 @@
+void main()
+{
+    vec4 color = TEXTURE(texture0, fragTexCoord) * colDiffuse * fragColor;
+    // Same weights as OpenCV's BGR2GRAY so the preview matches the saved photo
+    float gray = dot(color.rgb, vec3(0.299, 0.587, 0.114));
+    OUT = vec4(vec3(gray), color.a);
+}
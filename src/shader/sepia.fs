@@ -0,0 +1,9 @@
+void main()
+{
+    vec4 color = TEXTURE(texture0, fragTexCoord) * colDiffuse * fragColor;
+    vec3 sepia = vec3(
+        dot(color.rgb, vec3(0.393, 0.769, 0.189)),
+        dot(color.rgb, vec3(0.349, 0.686, 0.168)),
+        dot(color.rgb, vec3(0.272, 0.534, 0.131)));
+    OUT = vec4(min(sepia, 1.0), color.a);
+}
@@ -0,0 +1,14 @@
+void main()
+{
+    vec4 color = TEXTURE(texture0, fragTexCoord) * colDiffuse * fragColor;
+    vec3 sepia = min(vec3(
+        dot(color.rgb, vec3(0.393, 0.769, 0.189)),
+        dot(color.rgb, vec3(0.349, 0.686, 0.168)),
+        dot(color.rgb, vec3(0.272, 0.534, 0.131))), 1.0);
+    // Half sepia, faded blacks and a bit less contrast
+    vec3 faded = mix(color.rgb, sepia, 0.5) * 0.85 + 0.08;
+    // Darken the corners, 1 at the corners
+    float distance = length(fragTexCoord - 0.5) * 1.41421356;
+    float vignette = 1.0 - 0.5 * smoothstep(0.4, 1.0, distance);
+    OUT = vec4(clamp(faded * vignette, 0.0, 1.0), color.a);
+}
@@ -0,0 +1,402 @@
+use std::time::{Duration, Instant};
+
+use crate::config::KioskConfig;
+
+/// States of the kiosk, `T` is the captured image that is shown while presenting
+pub enum State<T> {
+    Startup,
+    Idle,
+    /// Counting down to the shot with the given index of the session
+    Countdown {
+        start: Instant,
+        shot: u32,
+    },
+    /// Capture of a shot was requested, waiting for the image since the given instant
+    Capturing {
+        since: Instant,
+        shot: u32,
+    },
+    Presenting {
+        image: T,
+        start: Instant,
+    },
+    /// Capture failed or timed out, shown until the presenting duration passed
+    Failed(Instant),
+}
+
+impl<T> State<T> {
+    /// Short name used by the HTTP API
+    pub fn name(&self) -> &'static str {
+        match self {
+            State::Startup => "startup",
+            State::Idle => "idle",
+            State::Countdown { .. } => "countdown",
+            State::Capturing { .. } => "capturing",
+            State::Presenting { .. } => "presenting",
+            State::Failed(_) => "failed",
+        }
+    }
+}
+
+/// Everything that can happen to the kiosk
+pub enum Event<T> {
+    Tick(Instant),
+    Trigger(Instant),
+    /// A shot of a multi-shot session was saved and more shots are to be taken
+    ShotCaptured(Instant),
+    /// The single shot or the composed strip of the last shot is ready
+    ImageCaptured(T, Instant),
+    CaptureFailed(Instant),
+    /// Review actions while presenting
+    Keep,
+    Retake(Instant),
+    Delete,
+}
+
+/// Side effects the caller has to perform after a transition
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command<T> {
+    /// Take the shot with the given index of the session
+    Capture { shot: u32 },
+    /// The presented image was rejected, or an image arrived too late to be presented, and should be removed
+    Discard(T),
+}
+
+pub struct KioskState<T> {
+    state: State<T>,
+    countdown: Duration,
+    shot_countdown: Duration,
+    shots: u32,
+    presenting: Duration,
+    capture_timeout: Duration,
+}
+
+impl<T> KioskState<T> {
+    pub fn new(config: &KioskConfig) -> Self {
+        Self {
+            state: State::Startup,
+            countdown: config.timing.countdown(),
+            shot_countdown: config.strip.shot_countdown(),
+            shots: config.strip.shots.max(1),
+            presenting: config.timing.presenting(),
+            capture_timeout: config.capture_timeout(),
+        }
+    }
+
+    pub fn state(&self) -> &State<T> {
+        &self.state
+    }
+
+    /// Shots taken per session
+    pub fn shots(&self) -> u32 {
+        self.shots
+    }
+
+    /// Length of the countdown before the shot with the given index
+    pub fn countdown(&self, shot: u32) -> Duration {
+        if shot == 0 {
+            self.countdown
+        } else {
+            self.shot_countdown
+        }
+    }
+
+    /// Feeds an event into the state machine and returns the commands the caller has to run
+    pub fn handle(&mut self, event: Event<T>) -> Vec<Command<T>> {
+        let mut commands = Vec::new();
+        match event {
+            Event::Tick(now) => self.tick(now, &mut commands),
+            Event::Trigger(now) => {
+                if let State::Idle = self.state {
+                    self.state = State::Countdown {
+                        start: now,
+                        shot: 0,
+                    };
+                }
+            }
+            Event::ShotCaptured(now) => {
+                if let State::Capturing { shot, .. } = self.state {
+                    if shot + 1 < self.shots {
+                        self.state = State::Countdown {
+                            start: now,
+                            shot: shot + 1,
+                        };
+                    }
+                }
+            }
+            Event::ImageCaptured(image, now) => {
+                if let State::Capturing { .. } = self.state {
+                    self.state = State::Presenting { image, start: now };
+                } else {
+                    // The capture timed out, nobody saw the image
+                    commands.push(Command::Discard(image));
+                }
+            }
+            Event::CaptureFailed(now) => {
+                if let State::Capturing { .. } = self.state {
+                    self.state = State::Failed(now);
+                }
+            }
+            Event::Keep => {
+                if let State::Presenting { .. } = self.state {
+                    self.state = State::Idle;
+                }
+            }
+            Event::Retake(now) => {
+                if let Some(image) = self.take_presented(State::Countdown {
+                    start: now,
+                    shot: 0,
+                }) {
+                    commands.push(Command::Discard(image));
+                }
+            }
+            Event::Delete => {
+                if let Some(image) = self.take_presented(State::Idle) {
+                    commands.push(Command::Discard(image));
+                }
+            }
+        }
+        commands
+    }
+
+    /// Leaves the presenting state for `next` and returns the presented image
+    fn take_presented(&mut self, next: State<T>) -> Option<T> {
+        if !matches!(self.state, State::Presenting { .. }) {
+            return None;
+        }
+        match std::mem::replace(&mut self.state, next) {
+            State::Presenting { image, .. } => Some(image),
+            _ => None,
+        }
+    }
+
+    fn tick(&mut self, now: Instant, commands: &mut Vec<Command<T>>) {
+        match &self.state {
+            State::Startup => self.state = State::Idle,
+            State::Idle => {}
+            State::Countdown { start, shot } => {
+                let shot = *shot;
+                if now.saturating_duration_since(*start) >= self.countdown(shot) {
+                    self.state = State::Capturing { since: now, shot };
+                    commands.push(Command::Capture { shot });
+                }
+            }
+            State::Capturing { since, .. } => {
+                if now.saturating_duration_since(*since) >= self.capture_timeout {
+                    self.state = State::Failed(now);
+                }
+            }
+            State::Presenting { start, .. } | State::Failed(start) => {
+                if now.saturating_duration_since(*start) >= self.presenting {
+                    self.state = State::Idle;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: f32) -> Duration {
+        Duration::from_secs_f32(secs)
+    }
+
+    /// 3 s countdown, 1 s between the shots of a strip, 5 s presenting and a 10 s capture timeout
+    fn kiosk(shots: u32) -> (KioskState<&'static str>, Instant) {
+        let mut config = KioskConfig::default();
+        config.timing.countdown_secs = 3.;
+        config.timing.presenting_secs = 5.;
+        config.timing.capture_timeout_secs = 10.;
+        config.strip.shots = shots;
+        config.strip.shot_countdown_secs = 1.;
+        let mut kiosk = KioskState::new(&config);
+        let start = Instant::now();
+        assert!(kiosk.handle(Event::Tick(start)).is_empty());
+        assert!(matches!(kiosk.state(), State::Idle));
+        (kiosk, start)
+    }
+
+    /// Triggers at `now` and runs the countdown of the first shot
+    fn capture(kiosk: &mut KioskState<&'static str>, now: Instant) -> Instant {
+        kiosk.handle(Event::Trigger(now));
+        let expired = now + secs(3.);
+        assert_eq!(
+            kiosk.handle(Event::Tick(expired)),
+            vec![Command::Capture { shot: 0 }]
+        );
+        expired
+    }
+
+    fn present(kiosk: &mut KioskState<&'static str>, now: Instant) -> Instant {
+        let captured = capture(kiosk, now) + secs(0.5);
+        assert!(kiosk
+            .handle(Event::ImageCaptured("photo", captured))
+            .is_empty());
+        captured
+    }
+
+    #[test]
+    fn starts_up_into_idle() {
+        let mut kiosk = KioskState::<&str>::new(&KioskConfig::default());
+        assert!(matches!(kiosk.state(), State::Startup));
+        kiosk.handle(Event::Trigger(Instant::now()));
+        assert!(matches!(kiosk.state(), State::Startup));
+        kiosk.handle(Event::Tick(Instant::now()));
+        assert!(matches!(kiosk.state(), State::Idle));
+    }
+
+    #[test]
+    fn trigger_starts_the_countdown_once() {
+        let (mut kiosk, t0) = kiosk(1);
+        assert!(kiosk.handle(Event::Trigger(t0)).is_empty());
+        kiosk.handle(Event::Trigger(t0 + secs(1.)));
+        assert!(matches!(
+            kiosk.state(),
+            State::Countdown { start, shot: 0 } if *start == t0
+        ));
+    }
+
+    #[test]
+    fn countdown_expiry_requests_the_capture() {
+        let (mut kiosk, t0) = kiosk(1);
+        kiosk.handle(Event::Trigger(t0));
+        assert!(kiosk.handle(Event::Tick(t0 + secs(2.9))).is_empty());
+        assert!(matches!(kiosk.state(), State::Countdown { .. }));
+        assert_eq!(
+            kiosk.handle(Event::Tick(t0 + secs(3.))),
+            vec![Command::Capture { shot: 0 }]
+        );
+        assert!(matches!(
+            kiosk.state(),
+            State::Capturing { since, shot: 0 } if *since == t0 + secs(3.)
+        ));
+        // The capture is only requested once
+        assert!(kiosk.handle(Event::Tick(t0 + secs(3.1))).is_empty());
+    }
+
+    #[test]
+    fn captured_image_is_presented_until_the_timeout() {
+        let (mut kiosk, t0) = kiosk(1);
+        let captured = present(&mut kiosk, t0);
+        assert!(matches!(
+            kiosk.state(),
+            State::Presenting { image: "photo", start } if *start == captured
+        ));
+        assert!(kiosk.handle(Event::Tick(captured + secs(4.9))).is_empty());
+        assert!(matches!(kiosk.state(), State::Presenting { .. }));
+        assert!(kiosk.handle(Event::Tick(captured + secs(5.))).is_empty());
+        assert!(matches!(kiosk.state(), State::Idle));
+    }
+
+    #[test]
+    fn failed_capture_is_shown_until_the_timeout() {
+        let (mut kiosk, t0) = kiosk(1);
+        let failed = capture(&mut kiosk, t0) + secs(1.);
+        assert!(kiosk.handle(Event::CaptureFailed(failed)).is_empty());
+        assert!(matches!(kiosk.state(), State::Failed(at) if *at == failed));
+        kiosk.handle(Event::Tick(failed + secs(4.9)));
+        assert!(matches!(kiosk.state(), State::Failed(_)));
+        kiosk.handle(Event::Tick(failed + secs(5.)));
+        assert!(matches!(kiosk.state(), State::Idle));
+    }
+
+    #[test]
+    fn capture_times_out() {
+        let (mut kiosk, t0) = kiosk(1);
+        let requested = capture(&mut kiosk, t0);
+        kiosk.handle(Event::Tick(requested + secs(9.9)));
+        assert!(matches!(kiosk.state(), State::Capturing { .. }));
+        kiosk.handle(Event::Tick(requested + secs(10.)));
+        assert!(matches!(kiosk.state(), State::Failed(at) if *at == requested + secs(10.)));
+
+        // An image arriving after the timeout is not presented but discarded
+        assert_eq!(
+            kiosk.handle(Event::ImageCaptured("late", requested + secs(11.))),
+            vec![Command::Discard("late")]
+        );
+        assert!(matches!(kiosk.state(), State::Failed(_)));
+    }
+
+    #[test]
+    fn keep_returns_to_idle() {
+        let (mut kiosk, t0) = kiosk(1);
+        present(&mut kiosk, t0);
+        assert!(kiosk.handle(Event::Keep).is_empty());
+        assert!(matches!(kiosk.state(), State::Idle));
+    }
+
+    #[test]
+    fn retake_discards_and_counts_down_again() {
+        let (mut kiosk, t0) = kiosk(1);
+        let captured = present(&mut kiosk, t0);
+        let retake = captured + secs(1.);
+        assert_eq!(
+            kiosk.handle(Event::Retake(retake)),
+            vec![Command::Discard("photo")]
+        );
+        assert!(matches!(
+            kiosk.state(),
+            State::Countdown { start, shot: 0 } if *start == retake
+        ));
+    }
+
+    #[test]
+    fn delete_discards_and_returns_to_idle() {
+        let (mut kiosk, t0) = kiosk(1);
+        present(&mut kiosk, t0);
+        assert_eq!(kiosk.handle(Event::Delete), vec![Command::Discard("photo")]);
+        assert!(matches!(kiosk.state(), State::Idle));
+    }
+
+    #[test]
+    fn review_actions_only_apply_while_presenting() {
+        let (mut kiosk, t0) = kiosk(1);
+        assert!(kiosk.handle(Event::Delete).is_empty());
+        assert!(kiosk.handle(Event::Retake(t0)).is_empty());
+        assert!(matches!(kiosk.state(), State::Idle));
+
+        capture(&mut kiosk, t0);
+        assert!(kiosk.handle(Event::Keep).is_empty());
+        assert!(kiosk.handle(Event::Delete).is_empty());
+        assert!(matches!(kiosk.state(), State::Capturing { .. }));
+    }
+
+    #[test]
+    fn multi_shot_session_counts_down_between_shots() {
+        let (mut kiosk, t0) = kiosk(3);
+        let mut now = capture(&mut kiosk, t0);
+        for shot in 1..3 {
+            now += secs(0.5);
+            assert!(kiosk.handle(Event::ShotCaptured(now)).is_empty());
+            assert!(matches!(
+                kiosk.state(),
+                State::Countdown { start, shot: s } if *start == now && *s == shot
+            ));
+            assert!(kiosk.handle(Event::Tick(now + secs(0.9))).is_empty());
+            now += secs(1.);
+            assert_eq!(
+                kiosk.handle(Event::Tick(now)),
+                vec![Command::Capture { shot }]
+            );
+        }
+
+        // The last shot does not start another countdown, the strip is presented
+        kiosk.handle(Event::ShotCaptured(now + secs(0.5)));
+        assert!(matches!(kiosk.state(), State::Capturing { shot: 2, .. }));
+        kiosk.handle(Event::ImageCaptured("strip", now + secs(1.)));
+        assert!(matches!(
+            kiosk.state(),
+            State::Presenting { image: "strip", .. }
+        ));
+    }
+
+    #[test]
+    fn shot_countdowns_use_their_own_length() {
+        let (kiosk, _) = kiosk(3);
+        assert_eq!(kiosk.shots(), 3);
+        assert_eq!(kiosk.countdown(0), secs(3.));
+        assert_eq!(kiosk.countdown(1), secs(1.));
+    }
+}
@@ -0,0 +1,409 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use opencv::core::{Mat, Vector};
+use opencv::imgcodecs::{
+    imencode, IMWRITE_JPEG_QUALITY, IMWRITE_PNG_COMPRESSION, IMWRITE_WEBP_QUALITY,
+};
+use opencv::prelude::*;
+
+use crate::config::{ImageFormat, OutputConfig};
+
+/// Saves captures as `<root>/<event>/<date>/<sequence>.<ext>`
+pub struct PhotoStorage {
+    root: PathBuf,
+    event: String,
+    format: ImageFormat,
+    quality: u8,
+    png_compression: u8,
+    /// Directory of the current day and the next free sequence number in it
+    current: Option<(PathBuf, u32)>,
+}
+
+impl PhotoStorage {
+    pub fn new(config: &OutputConfig) -> Self {
+        Self {
+            root: config.root.clone(),
+            event: config.event.clone(),
+            format: config.format,
+            quality: config.quality,
+            png_compression: config.png_compression,
+            current: None,
+        }
+    }
+
+    /// Directory captures of today are saved to
+    pub fn session_dir(&self) -> PathBuf {
+        self.root
+            .join(&self.event)
+            .join(Local::now().format("%Y-%m-%d").to_string())
+    }
+
+    /// Encodes and atomically writes the BGR image, returns the path it was saved to
+    pub fn save(&mut self, image: &Mat) -> Result<PathBuf, Box<dyn Error>> {
+        self.save_with_suffix(image, "")
+    }
+
+    /// Saves a composed photo strip as `<sequence>-strip.<ext>`
+    pub fn save_strip(&mut self, image: &Mat) -> Result<PathBuf, Box<dyn Error>> {
+        self.save_with_suffix(image, "-strip")
+    }
+
+    /// Saves an encoded animated GIF as `<sequence>.gif`
+    pub fn save_gif(&mut self, data: &[u8]) -> Result<PathBuf, Box<dyn Error>> {
+        self.save_data(data, "", "gif")
+    }
+
+    /// Atomically writes a file named like the capture at `path` with another extension
+    pub fn save_sibling<F>(
+        &self,
+        path: &Path,
+        extension: &str,
+        write: F,
+    ) -> Result<PathBuf, Box<dyn Error>>
+    where
+        F: FnOnce(&Path) -> Result<(), Box<dyn Error>>,
+    {
+        let sibling = path.with_extension(extension);
+        write_atomic(&sibling, write)?;
+        Ok(sibling)
+    }
+
+    /// Saves the discarded stills of a burst as `rejects/<sequence>-<n>.<ext>` next to the kept photo
+    pub fn save_rejects(
+        &self,
+        kept: &Path,
+        images: &[Mat],
+    ) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let stem = kept
+            .file_stem()
+            .ok_or_else(|| format!("{} is not a file path", kept.display()))?
+            .to_string_lossy();
+        let dir = kept.with_file_name("rejects");
+        fs::create_dir_all(&dir)?;
+        let mut paths = Vec::with_capacity(images.len());
+        for (i, image) in images.iter().enumerate() {
+            let data = self.encode(image)?;
+            let path = dir.join(format!("{stem}-{}.{}", i + 1, self.format.extension()));
+            write_atomic(&path, |tmp_path| Ok(fs::write(tmp_path, &data)?))?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+
+    fn save_with_suffix(&mut self, image: &Mat, suffix: &str) -> Result<PathBuf, Box<dyn Error>> {
+        let data = self.encode(image)?;
+        self.save_data(&data, suffix, self.format.extension())
+    }
+
+    /// Writes `data` as `<sequence><suffix>.<extension>` with the next free sequence number
+    fn save_data(
+        &mut self,
+        data: &[u8],
+        suffix: &str,
+        extension: &str,
+    ) -> Result<PathBuf, Box<dyn Error>> {
+        self.save_file(suffix, extension, |tmp_path| {
+            let mut file = File::create(tmp_path)?;
+            file.write_all(data)?;
+            file.sync_all()?;
+            Ok(())
+        })
+    }
+
+    /// Lets `write` atomically create `<sequence><suffix>.<extension>` with the next free sequence number,
+    /// for files like videos that are written by another library
+    pub fn save_file<F>(
+        &mut self,
+        suffix: &str,
+        extension: &str,
+        write: F,
+    ) -> Result<PathBuf, Box<dyn Error>>
+    where
+        F: FnOnce(&Path) -> Result<(), Box<dyn Error>>,
+    {
+        let dir = self.session_dir();
+        let mut sequence = self.next_sequence(&dir)?;
+        // Named after the process so kiosks sharing the directory do not write into each other's temp file
+        let tmp_path = dir.join(format!(".tmp-{}{suffix}.{extension}", std::process::id()));
+        write_tmp(&tmp_path, write)?;
+
+        let path = loop {
+            let path = dir.join(format!("{sequence:04}{suffix}.{extension}"));
+            match commit(&tmp_path, &path) {
+                Ok(()) => break path,
+                // Another kiosk took the sequence number after it was picked
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    sequence += 1;
+                    while has_sequence(&dir, sequence)? {
+                        sequence += 1;
+                    }
+                }
+                Err(e) => {
+                    let _ = fs::remove_file(&tmp_path);
+                    return Err(e.into());
+                }
+            }
+        };
+
+        self.current = Some((dir, sequence + 1));
+        Ok(path)
+    }
+
+    fn encode(&self, image: &Mat) -> Result<Vec<u8>, Box<dyn Error>> {
+        let params = match self.format {
+            ImageFormat::Jpeg => [IMWRITE_JPEG_QUALITY, self.quality as i32],
+            ImageFormat::Png => [IMWRITE_PNG_COMPRESSION, self.png_compression as i32],
+            ImageFormat::Webp => [IMWRITE_WEBP_QUALITY, self.quality as i32],
+        };
+        let mut buf = Vector::<u8>::new();
+        let extension = format!(".{}", self.format.extension());
+        if !imencode(&extension, image, &mut buf, &Vector::from_slice(&params))? {
+            return Err(format!("Could not encode image as {extension}").into());
+        }
+        Ok(buf.to_vec())
+    }
+
+    /// Next sequence number in `dir`, scans the directory only when the day changed or on first use.
+    /// Numbers other kiosks took since then are skipped by `save_file` once a name collides.
+    fn next_sequence(&mut self, dir: &Path) -> Result<u32, Box<dyn Error>> {
+        match &self.current {
+            Some((current_dir, sequence)) if current_dir == dir => Ok(*sequence),
+            _ => {
+                fs::create_dir_all(dir)?;
+                Ok(highest_sequence(dir)? + 1)
+            }
+        }
+    }
+}
+
+/// Lets `write` create a hidden temp file next to `path` and moves it to `path` once it is complete,
+/// fails if `path` already exists.
+/// The temp file keeps the extension so writers that pick the format by extension work.
+fn write_atomic<F>(path: &Path, write: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnOnce(&Path) -> Result<(), Box<dyn Error>>,
+{
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("{} is not a file path", path.display()))?;
+    let tmp_path = path.with_file_name(format!(".tmp-{}", file_name.to_string_lossy()));
+    write_tmp(&tmp_path, write)?;
+    if let Err(e) = commit(&tmp_path, path) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(format!("Could not save {}: {e}", path.display()).into());
+    }
+    Ok(())
+}
+
+/// Runs `write` on the temp file and removes what it left behind if it fails
+fn write_tmp<F>(tmp_path: &Path, write: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnOnce(&Path) -> Result<(), Box<dyn Error>>,
+{
+    if let Err(e) = write(tmp_path) {
+        let _ = fs::remove_file(tmp_path);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Moves the complete temp file to `path`, fails with `AlreadyExists` instead of replacing a file.
+/// File systems without hard links like FAT fall back to a rename, which can still lose a race.
+fn commit(tmp_path: &Path, path: &Path) -> io::Result<()> {
+    match fs::hard_link(tmp_path, path) {
+        Ok(()) => fs::remove_file(tmp_path),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Err(e),
+        Err(_) if !path.exists() => fs::rename(tmp_path, path),
+        Err(e) => Err(e),
+    }
+}
+
+/// Sequence number a file name starts with, like 12 for `0012.jpg` or `0012-strip.jpg`
+fn parse_sequence(path: &Path) -> Option<u32> {
+    path.file_stem()?.to_str()?.split('-').next()?.parse().ok()
+}
+
+fn highest_sequence(dir: &Path) -> Result<u32, Box<dyn Error>> {
+    let mut highest = 0;
+    for entry in fs::read_dir(dir)? {
+        if let Some(sequence) = parse_sequence(&entry?.path()) {
+            highest = highest.max(sequence);
+        }
+    }
+    Ok(highest)
+}
+
+fn has_sequence(dir: &Path, sequence: u32) -> Result<bool, Box<dyn Error>> {
+    for entry in fs::read_dir(dir)? {
+        if parse_sequence(&entry?.path()) == Some(sequence) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opencv::core::{Scalar, Size, CV_8UC3};
+    use opencv::imgcodecs::{imdecode, IMREAD_COLOR};
+
+    /// Storage in a fresh directory below the system temp directory
+    fn storage(name: &str) -> PhotoStorage {
+        let root =
+            std::env::temp_dir().join(format!("kiosk-storage-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        PhotoStorage::new(&OutputConfig {
+            root,
+            format: ImageFormat::Png,
+            ..OutputConfig::default()
+        })
+    }
+
+    fn image(value: f64) -> Mat {
+        Mat::new_size_with_default(Size::new(8, 6), CV_8UC3, Scalar::all(value)).unwrap()
+    }
+
+    #[test]
+    fn rejects_are_saved_next_to_the_kept_photo() {
+        let mut storage = storage("rejects");
+        let kept = storage.save(&image(10.)).unwrap();
+        assert_eq!(kept.file_name().unwrap(), "0001.png");
+
+        let rejects = storage
+            .save_rejects(&kept, &[image(20.), image(30.)])
+            .unwrap();
+        let dir = storage.session_dir().join("rejects");
+        assert_eq!(rejects, [dir.join("0001-1.png"), dir.join("0001-2.png")]);
+        for (path, value) in rejects.iter().zip([20, 30]) {
+            let data = Vector::<u8>::from_slice(&fs::read(path).unwrap());
+            let decoded = imdecode(&data, IMREAD_COLOR).unwrap();
+            assert_eq!(decoded.size().unwrap(), Size::new(8, 6));
+            assert!(decoded.data_bytes().unwrap().iter().all(|&v| v == value));
+        }
+        // Only the rejects and the kept photo, no temporary files are left behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        assert_eq!(fs::read_dir(storage.session_dir()).unwrap().count(), 2);
+
+        // Rejects do not take sequence numbers of photos
+        let next = storage.save(&image(40.)).unwrap();
+        assert_eq!(next.file_name().unwrap(), "0002.png");
+        fs::remove_dir_all(&storage.root).unwrap();
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn captures_are_numbered_in_order() {
+        let mut storage = storage("numbering");
+        let name = |path: PathBuf| path.file_name().unwrap().to_string_lossy().into_owned();
+        assert_eq!(name(storage.save(&image(10.)).unwrap()), "0001.png");
+        assert_eq!(name(storage.save(&image(10.)).unwrap()), "0002.png");
+        assert_eq!(
+            name(storage.save_strip(&image(10.)).unwrap()),
+            "0003-strip.png"
+        );
+        assert_eq!(name(storage.save_gif(b"GIF89a").unwrap()), "0004.gif");
+        let sibling = storage
+            .save_sibling(&storage.session_dir().join("0004.gif"), "mp4", |tmp_path| {
+                Ok(fs::write(tmp_path, b"video")?)
+            })
+            .unwrap();
+        assert_eq!(name(sibling), "0004.mp4");
+        assert_eq!(
+            names(&storage.session_dir()),
+            [
+                "0001.png",
+                "0002.png",
+                "0003-strip.png",
+                "0004.gif",
+                "0004.mp4"
+            ]
+        );
+        fs::remove_dir_all(&storage.root).unwrap();
+    }
+
+    #[test]
+    fn numbering_resumes_after_a_restart() {
+        let mut storage = storage("resume");
+        let dir = storage.session_dir();
+        fs::create_dir_all(dir.join("rejects")).unwrap();
+        for name in ["0007.png", "0012-strip.png", "notes.txt", ".tmp-99.png"] {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+        let path = storage.save(&image(10.)).unwrap();
+        assert_eq!(path, dir.join("0013.png"));
+        fs::remove_dir_all(&storage.root).unwrap();
+    }
+
+    #[test]
+    fn numbering_restarts_on_a_new_day() {
+        let mut storage = storage("rollover");
+        let yesterday = storage.root.join(&storage.event).join("2000-01-01");
+        fs::create_dir_all(&yesterday).unwrap();
+        fs::write(yesterday.join("0041.png"), b"").unwrap();
+        storage.current = Some((yesterday.clone(), 42));
+
+        let path = storage.save(&image(10.)).unwrap();
+        assert_eq!(path, storage.session_dir().join("0001.png"));
+        assert_eq!(storage.current, Some((storage.session_dir(), 2)));
+        assert_eq!(names(&yesterday), ["0041.png"]);
+        fs::remove_dir_all(&storage.root).unwrap();
+    }
+
+    #[test]
+    fn numbers_taken_by_another_kiosk_are_skipped() {
+        let mut storage = storage("collision");
+        storage.save(&image(10.)).unwrap();
+        let dir = storage.session_dir();
+        // Another kiosk saved a photo and a strip since
+        fs::write(dir.join("0002.png"), b"other").unwrap();
+        fs::write(dir.join("0003-strip.png"), b"other").unwrap();
+
+        let path = storage.save(&image(10.)).unwrap();
+        assert_eq!(path, dir.join("0004.png"));
+        assert_eq!(fs::read(dir.join("0002.png")).unwrap(), b"other");
+        assert_eq!(
+            names(&dir),
+            ["0001.png", "0002.png", "0003-strip.png", "0004.png"]
+        );
+        assert_eq!(storage.current, Some((dir, 5)));
+        fs::remove_dir_all(&storage.root).unwrap();
+    }
+
+    #[test]
+    fn commit_never_replaces_a_file() {
+        let storage = storage("commit");
+        let dir = storage.root.clone();
+        fs::create_dir_all(&dir).unwrap();
+        let (tmp_path, path) = (dir.join(".tmp-1.png"), dir.join("0001.png"));
+        fs::write(&tmp_path, b"new").unwrap();
+        fs::write(&path, b"old").unwrap();
+        let error = commit(&tmp_path, &path).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read(&path).unwrap(), b"old");
+        assert!(tmp_path.exists());
+
+        fs::remove_file(&path).unwrap();
+        commit(&tmp_path, &path).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert!(!tmp_path.exists());
+
+        // write_atomic cleans up its temp file when the target exists
+        assert!(write_atomic(&path, |tmp_path| Ok(fs::write(tmp_path, b"newer")?)).is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(names(&dir), ["0001.png"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
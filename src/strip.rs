@@ -0,0 +1,234 @@
+use std::error::Error;
+
+use opencv::core::{Mat, Rect, Scalar, Size, CV_8UC3};
+use opencv::prelude::*;
+
+use crate::camera::{load_image, resized};
+use crate::config::{StripConfig, StripLayout};
+use crate::draw::{bgr_scalar, cover, draw_caption};
+
+/// Cell positions, background and caption of a photo strip
+pub struct StripTemplate {
+    cells: Vec<Rect>,
+    background: Mat,
+    caption: Option<(String, Rect, Scalar)>,
+}
+
+impl StripTemplate {
+    /// Computes the cells for the configured layout and loads the background image
+    pub fn from_config(config: &StripConfig) -> Result<Self, Box<dyn Error>> {
+        let (width, height) = config.size();
+        let size = Size::new(width as i32, height as i32);
+        let margin = config.margin as i32;
+
+        let caption_height = match config.caption {
+            Some(_) => config.caption_height as i32,
+            None => 0,
+        };
+        let area = Rect::new(
+            margin,
+            margin,
+            size.width - 2 * margin,
+            size.height - 2 * margin - caption_height,
+        );
+        let cells = match config.layout {
+            StripLayout::Strip => grid_cells(area, 1, config.shots, config.spacing as i32),
+            StripLayout::Grid => {
+                let columns = (config.shots as f32).sqrt().ceil() as u32;
+                let rows = config.shots.div_ceil(columns);
+                let mut cells = grid_cells(area, columns, rows, config.spacing as i32);
+                cells.truncate(config.shots as usize);
+                cells
+            }
+            StripLayout::Custom => config
+                .cells
+                .iter()
+                .map(|cell| {
+                    Rect::new(
+                        cell.x as i32,
+                        cell.y as i32,
+                        cell.width as i32,
+                        cell.height as i32,
+                    )
+                })
+                .collect(),
+        };
+        if cells.iter().any(|cell| cell.width <= 0 || cell.height <= 0) {
+            return Err(format!(
+                "strip of {width}x{height} is too small for {} shots with the configured margin and spacing",
+                config.shots
+            )
+            .into());
+        }
+
+        let background = match &config.background {
+            Some(path) => resized(&load_image(path)?, size)?,
+            None => Mat::new_size_with_default(size, CV_8UC3, bgr_scalar(config.background_color))?,
+        };
+        let caption = config.caption.as_ref().map(|caption| {
+            let rect = Rect::new(
+                margin,
+                size.height - margin - caption_height,
+                size.width - 2 * margin,
+                caption_height,
+            );
+            (caption.clone(), rect, bgr_scalar(config.caption_color))
+        });
+
+        Ok(Self {
+            cells,
+            background,
+            caption,
+        })
+    }
+
+    /// Composes the BGR shots into one image, shots are repeated if there are more cells than shots
+    pub fn compose(&self, shots: &[Mat]) -> Result<Mat, Box<dyn Error>> {
+        if shots.is_empty() {
+            return Err("Can not compose a strip without shots".into());
+        }
+        let mut strip = self.background.clone();
+        for (cell, shot) in self.cells.iter().zip(shots.iter().cycle()) {
+            let filled = cover(shot, cell.size())?;
+            let mut roi = Mat::roi_mut(&mut strip, *cell)?;
+            filled.copy_to(&mut roi)?;
+        }
+        if let Some((text, rect, color)) = &self.caption {
+            draw_caption(&mut strip, text, *rect, *color)?;
+        }
+        Ok(strip)
+    }
+}
+
+/// `columns` x `rows` equally sized cells filling `area`
+fn grid_cells(area: Rect, columns: u32, rows: u32, spacing: i32) -> Vec<Rect> {
+    let (columns, rows) = (columns as i32, rows as i32);
+    let width = (area.width - spacing * (columns - 1)) / columns;
+    let height = (area.height - spacing * (rows - 1)) / rows;
+    let mut cells = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            cells.push(Rect::new(
+                area.x + column * (width + spacing),
+                area.y + row * (height + spacing),
+                width,
+                height,
+            ));
+        }
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opencv::core::Vec3b;
+
+    const WHITE: [u8; 3] = [255, 255, 255];
+
+    /// Solid BGR shot in a 4:3 camera frame
+    fn shot(bgr: [u8; 3]) -> Mat {
+        let [b, g, r] = bgr.map(f64::from);
+        Mat::new_rows_cols_with_default(480, 640, CV_8UC3, Scalar::new(b, g, r, 0.)).unwrap()
+    }
+
+    fn pixel(image: &Mat, x: i32, y: i32) -> [u8; 3] {
+        image.at_2d::<Vec3b>(y, x).unwrap().0
+    }
+
+    fn center(cell: Rect) -> (i32, i32) {
+        (cell.x + cell.width / 2, cell.y + cell.height / 2)
+    }
+
+    /// Pixels in `rect` that differ from the background
+    fn ink(image: &Mat, rect: Rect) -> usize {
+        let mut count = 0;
+        for y in rect.y..rect.y + rect.height {
+            for x in rect.x..rect.x + rect.width {
+                count += usize::from(pixel(image, x, y) != WHITE);
+            }
+        }
+        count
+    }
+
+    #[test]
+    fn two_by_six_strip_stacks_the_shots_above_the_caption() {
+        let config = StripConfig {
+            shots: 4,
+            caption: Some("Susi & Max".into()),
+            ..StripConfig::default()
+        };
+        let template = StripTemplate::from_config(&config).unwrap();
+        assert_eq!(
+            template.cells,
+            [
+                Rect::new(30, 30, 540, 380),
+                Rect::new(30, 430, 540, 380),
+                Rect::new(30, 830, 540, 380),
+                Rect::new(30, 1230, 540, 380),
+            ]
+        );
+        let caption = Rect::new(30, 1610, 540, 160);
+        assert_eq!(template.caption.as_ref().unwrap().1, caption);
+
+        let colors = [[0, 0, 255], [0, 255, 0], [255, 0, 0], [0, 255, 255]];
+        let shots: Vec<Mat> = colors.iter().map(|&bgr| shot(bgr)).collect();
+        let strip = template.compose(&shots).unwrap();
+        assert_eq!(strip.size().unwrap(), Size::new(600, 1800));
+        for (cell, bgr) in template.cells.iter().zip(colors) {
+            let (x, y) = center(*cell);
+            assert_eq!(pixel(&strip, x, y), bgr);
+            // Shots fill their cell edge to edge
+            assert_eq!(pixel(&strip, cell.x, cell.y), bgr);
+            assert_eq!(
+                pixel(&strip, cell.x + cell.width - 1, cell.y + cell.height - 1),
+                bgr
+            );
+        }
+        // Margin and spacing keep the background
+        assert_eq!(pixel(&strip, 10, 10), WHITE);
+        assert_eq!(pixel(&strip, 300, 420), WHITE);
+        // Caption is drawn inside its area only
+        assert!(ink(&strip, caption) > 100);
+        assert_eq!(ink(&strip, Rect::new(0, 1770, 600, 30)), 0);
+    }
+
+    #[test]
+    fn two_by_two_grid_repeats_shots_and_has_no_caption() {
+        let config = StripConfig {
+            shots: 4,
+            layout: StripLayout::Grid,
+            ..StripConfig::default()
+        };
+        let template = StripTemplate::from_config(&config).unwrap();
+        assert_eq!(
+            template.cells,
+            [
+                Rect::new(30, 30, 860, 560),
+                Rect::new(910, 30, 860, 560),
+                Rect::new(30, 610, 860, 560),
+                Rect::new(910, 610, 860, 560),
+            ]
+        );
+        assert!(template.caption.is_none());
+
+        let colors = [[0, 0, 255], [0, 255, 0], [255, 0, 0]];
+        let shots: Vec<Mat> = colors.iter().map(|&bgr| shot(bgr)).collect();
+        let grid = template.compose(&shots).unwrap();
+        assert_eq!(grid.size().unwrap(), Size::new(1800, 1200));
+        // The fourth cell repeats the first shot
+        for (cell, bgr) in template.cells.iter().zip(colors.iter().cycle()) {
+            let (x, y) = center(*cell);
+            assert_eq!(pixel(&grid, x, y), *bgr);
+        }
+        assert_eq!(pixel(&grid, 900, 300), WHITE);
+        assert_eq!(pixel(&grid, 450, 600), WHITE);
+        assert_eq!(ink(&grid, Rect::new(0, 1170, 1800, 30)), 0);
+    }
+
+    #[test]
+    fn composing_without_shots_fails() {
+        let template = StripTemplate::from_config(&StripConfig::default()).unwrap();
+        assert!(template.compose(&[]).is_err());
+    }
+}
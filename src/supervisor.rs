@@ -0,0 +1,272 @@
+use std::error::Error;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use opencv::core::Mat;
+
+use crate::camera::{open_source, FrameSource};
+use crate::config::CameraConfig;
+
+/// Longest time `read_preview` sleeps while waiting for the next reconnect attempt,
+/// keeps the capture thread responsive to commands
+const MAX_IDLE_SLEEP: Duration = Duration::from_millis(100);
+
+/// Read run on the worker thread, returns no frame if none is available
+type Read = Box<dyn FnOnce(&mut dyn FrameSource) -> Result<Vec<Mat>, Box<dyn Error>> + Send>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CameraStatus {
+    Connected,
+    Reconnecting { attempt: u32, error: String },
+}
+
+/// Properties of the source, reported after every read since the source lives on the worker thread
+#[derive(Debug, Clone, Copy, Default)]
+struct SourceInfo {
+    resolution: (i32, i32),
+    fps: f64,
+    last_still_latency: Option<Duration>,
+}
+
+impl SourceInfo {
+    fn of(source: &dyn FrameSource) -> Self {
+        Self {
+            resolution: source.resolution(),
+            fps: source.fps(),
+            last_still_latency: source.last_still_latency(),
+        }
+    }
+}
+
+/// Thread owning the source, so a read that never returns, like one of a stalled USB camera, can be abandoned
+struct ReadWorker {
+    tx: Sender<Read>,
+    rx: Receiver<(Result<Vec<Mat>, String>, SourceInfo)>,
+    info: SourceInfo,
+    thread: JoinHandle<()>,
+}
+
+impl ReadWorker {
+    fn spawn(mut source: Box<dyn FrameSource>) -> Self {
+        let info = SourceInfo::of(source.as_ref());
+        let (tx, read_rx) = channel::<Read>();
+        let (result_tx, rx) = channel();
+        // Ends and drops the source once the worker is closed, a blocked read first has to return
+        let thread = thread::spawn(move || {
+            for read in read_rx {
+                let result = read(source.as_mut()).map_err(|e| e.to_string());
+                if result_tx
+                    .send((result, SourceInfo::of(source.as_ref())))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+        Self {
+            tx,
+            rx,
+            info,
+            thread,
+        }
+    }
+
+    /// Stops taking reads, the returned thread finishes once the source is released
+    fn close(self) -> JoinHandle<()> {
+        self.thread
+    }
+
+    /// Runs `read` on the worker thread, `None` if it did not finish within `timeout`
+    fn read(&mut self, read: Read, timeout: Duration) -> Option<Result<Vec<Mat>, String>> {
+        self.tx.send(read).ok()?;
+        let (result, info) = self.rx.recv_timeout(timeout).ok()?;
+        self.info = info;
+        Some(result)
+    }
+}
+
+/// Wraps the configured frame source, detects failed reads and stalls and reopens the source with backoff.
+/// Reads run on a worker thread, a read that blocks longer than the stall timeout counts as a stall.
+pub struct CameraSupervisor {
+    config: CameraConfig,
+    worker: Option<ReadWorker>,
+    /// Thread of the dropped source, the camera is only reopened once it ended and released the device
+    closing: Option<JoinHandle<()>>,
+    status: CameraStatus,
+    failures: u32,
+    last_frame: Instant,
+    next_attempt: Instant,
+}
+
+impl CameraSupervisor {
+    /// Tries to open the source right away, a failure is handled like a later disconnect
+    pub fn new(config: CameraConfig) -> Self {
+        let now = Instant::now();
+        let mut supervisor = Self {
+            config,
+            worker: None,
+            closing: None,
+            status: CameraStatus::Reconnecting {
+                attempt: 0,
+                error: String::new(),
+            },
+            failures: 0,
+            last_frame: now,
+            next_attempt: now,
+        };
+        supervisor.try_open();
+        supervisor
+    }
+
+    pub fn status(&self) -> &CameraStatus {
+        &self.status
+    }
+
+    fn try_open(&mut self) {
+        let attempt = match &self.status {
+            CameraStatus::Connected => 0,
+            CameraStatus::Reconnecting { attempt, .. } => *attempt,
+        };
+        match open_source(&self.config) {
+            Ok(source) => {
+                if attempt > 0 {
+                    println!("Camera reconnected after {attempt} attempts");
+                }
+                self.worker = Some(ReadWorker::spawn(source));
+                self.status = CameraStatus::Connected;
+                self.failures = 0;
+                self.last_frame = Instant::now();
+            }
+            Err(e) => {
+                let attempt = attempt + 1;
+                eprintln!("Could not open camera (attempt {attempt}): {e}");
+                self.next_attempt = Instant::now() + self.backoff(attempt);
+                self.status = CameraStatus::Reconnecting {
+                    attempt,
+                    error: e.to_string(),
+                };
+            }
+        }
+    }
+
+    /// Exponential backoff starting at `reconnect_initial_secs`, capped at `reconnect_max_secs`
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2f32.powi(attempt.saturating_sub(1).min(16) as i32);
+        let secs =
+            (self.config.reconnect_initial_secs * factor).min(self.config.reconnect_max_secs);
+        Duration::from_secs_f32(secs)
+    }
+
+    fn disconnect(&mut self, error: String) {
+        eprintln!("Camera lost: {error}");
+        self.closing = self.worker.take().map(ReadWorker::close);
+        self.status = CameraStatus::Reconnecting { attempt: 0, error };
+        self.next_attempt = Instant::now();
+    }
+
+    /// Counts a failed read and drops the source once too many failed in a row or no frame arrived for too long
+    fn record_failure(&mut self, error: String) {
+        self.failures += 1;
+        let stalled = self.last_frame.elapsed() >= self.config.stall_timeout();
+        if self.failures >= self.config.max_failures || stalled {
+            self.disconnect(error);
+        }
+    }
+
+    /// Runs `read` on the worker, a read that takes longer than `timeout` drops the source right away
+    fn read(&mut self, read: Read, timeout: Duration) -> Result<Vec<Mat>, Box<dyn Error>> {
+        let Some(worker) = self.worker.as_mut() else {
+            return Ok(Vec::new());
+        };
+        match worker.read(read, timeout) {
+            Some(Ok(frames)) if frames.is_empty() => {
+                self.record_failure("Camera returned an empty frame".to_string());
+                Ok(frames)
+            }
+            Some(Ok(frames)) => {
+                self.failures = 0;
+                self.last_frame = Instant::now();
+                Ok(frames)
+            }
+            Some(Err(e)) => {
+                self.record_failure(e.clone());
+                Err(e.into())
+            }
+            None => {
+                let error = format!(
+                    "Camera did not answer within {:.1} s",
+                    timeout.as_secs_f32()
+                );
+                self.disconnect(error.clone());
+                Err(error.into())
+            }
+        }
+    }
+}
+
+impl FrameSource for CameraSupervisor {
+    /// Never fails, returns `None` while the camera is reconnecting
+    fn read_preview(&mut self) -> Result<Option<Mat>, Box<dyn Error>> {
+        if self.worker.is_none() {
+            // Opening the device again while the blocked read still holds it would fail or get a second handle
+            if let Some(closing) = self.closing.take() {
+                if !closing.is_finished() {
+                    self.closing = Some(closing);
+                    thread::sleep(MAX_IDLE_SLEEP);
+                    return Ok(None);
+                }
+                let _ = closing.join();
+            }
+            let now = Instant::now();
+            if now < self.next_attempt {
+                thread::sleep((self.next_attempt - now).min(MAX_IDLE_SLEEP));
+                return Ok(None);
+            }
+            self.try_open();
+            return Ok(None);
+        }
+        let frames = self.read(
+            Box::new(|source| Ok(source.read_preview()?.into_iter().collect())),
+            self.config.stall_timeout(),
+        );
+        Ok(frames.unwrap_or_default().pop())
+    }
+
+    fn read_still(&mut self) -> Result<Option<Mat>, Box<dyn Error>> {
+        if self.worker.is_none() {
+            return Err("Camera is not connected".into());
+        }
+        let frames = self.read(
+            Box::new(|source| Ok(source.read_still()?.into_iter().collect())),
+            self.config.still_timeout(),
+        )?;
+        Ok(frames.into_iter().next())
+    }
+
+    fn read_stills(&mut self, count: u32, interval: Duration) -> Result<Vec<Mat>, Box<dyn Error>> {
+        if self.worker.is_none() {
+            return Err("Camera is not connected".into());
+        }
+        self.read(
+            Box::new(move |source| source.read_stills(count, interval)),
+            self.config.still_timeout() + interval * count,
+        )
+    }
+
+    fn resolution(&self) -> (i32, i32) {
+        self.worker
+            .as_ref()
+            .map_or((0, 0), |worker| worker.info.resolution)
+    }
+
+    fn fps(&self) -> f64 {
+        self.worker.as_ref().map_or(0., |worker| worker.info.fps)
+    }
+
+    fn last_still_latency(&self) -> Option<Duration> {
+        self.worker
+            .as_ref()
+            .and_then(|worker| worker.info.last_still_latency)
+    }
+}
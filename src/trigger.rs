@@ -0,0 +1,280 @@
+use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::config::GpioConfig;
+
+/// How often the input line is sampled
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerEvent {
+    /// Button was pressed and released before the long press duration
+    Press,
+    /// Button is held down for the long press duration
+    LongPress,
+}
+
+/// A digital input the trigger button is connected to
+pub trait InputLine: Send {
+    /// Logical level of the line, active low lines are already inverted
+    fn is_pressed(&mut self) -> Result<bool, Box<dyn Error>>;
+}
+
+/// Line of a `/dev/gpiochip*` character device
+#[cfg(target_os = "linux")]
+pub struct GpioLine {
+    handle: gpio_cdev::LineHandle,
+}
+
+#[cfg(target_os = "linux")]
+impl GpioLine {
+    pub fn open(config: &GpioConfig, chip: &std::path::Path) -> Result<Self, Box<dyn Error>> {
+        use gpio_cdev::{Chip, LineRequestFlags};
+
+        let mut chip = Chip::new(chip)
+            .map_err(|e| format!("Could not open GPIO chip {}: {e}", chip.display()))?;
+        let mut flags = LineRequestFlags::INPUT;
+        if config.active_low {
+            flags |= LineRequestFlags::ACTIVE_LOW;
+        }
+        let handle = chip
+            .get_line(config.line)?
+            .request(flags, 0, "photo-kiosk")
+            .map_err(|e| format!("Could not request GPIO line {}: {e}", config.line))?;
+        Ok(Self { handle })
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl InputLine for GpioLine {
+    fn is_pressed(&mut self) -> Result<bool, Box<dyn Error>> {
+        Ok(self.handle.get_value()? != 0)
+    }
+}
+
+/// Simulated line, clones share the same level so one can be driven while the other is polled
+#[derive(Debug, Clone, Default)]
+pub struct MockLine {
+    level: Arc<AtomicBool>,
+}
+
+impl MockLine {
+    pub fn set(&self, pressed: bool) {
+        self.level.store(pressed, Ordering::SeqCst);
+    }
+}
+
+impl InputLine for MockLine {
+    fn is_pressed(&mut self) -> Result<bool, Box<dyn Error>> {
+        Ok(self.level.load(Ordering::SeqCst))
+    }
+}
+
+/// Debounces raw samples and detects short and long presses
+pub struct Debouncer {
+    debounce: Duration,
+    long_press: Duration,
+    /// Debounced level
+    pressed: bool,
+    /// Last raw level and since when it is stable
+    raw: bool,
+    raw_since: Option<Instant>,
+    pressed_since: Option<Instant>,
+    long_press_sent: bool,
+}
+
+impl Debouncer {
+    pub fn new(debounce: Duration, long_press: Duration) -> Self {
+        Self {
+            debounce,
+            long_press,
+            pressed: false,
+            raw: false,
+            raw_since: None,
+            pressed_since: None,
+            long_press_sent: false,
+        }
+    }
+
+    /// Feeds a raw sample taken at `now`
+    pub fn update(&mut self, raw: bool, now: Instant) -> Option<TriggerEvent> {
+        if raw != self.raw || self.raw_since.is_none() {
+            self.raw = raw;
+            self.raw_since = Some(now);
+        }
+        let stable_for = now.saturating_duration_since(self.raw_since.unwrap_or(now));
+
+        if self.raw != self.pressed && stable_for >= self.debounce {
+            self.pressed = self.raw;
+            if self.pressed {
+                self.pressed_since = self.raw_since;
+                self.long_press_sent = false;
+            } else {
+                self.pressed_since = None;
+                if !self.long_press_sent {
+                    return Some(TriggerEvent::Press);
+                }
+            }
+        }
+
+        if let Some(pressed_since) = self.pressed_since {
+            if !self.long_press_sent
+                && now.saturating_duration_since(pressed_since) >= self.long_press
+            {
+                self.long_press_sent = true;
+                return Some(TriggerEvent::LongPress);
+            }
+        }
+        None
+    }
+}
+
+/// Polls the line in a thread and sends trigger events until the receiver is dropped
+pub fn spawn_trigger_thread<L>(
+    mut line: L,
+    mut debouncer: Debouncer,
+    tx: Sender<TriggerEvent>,
+) -> JoinHandle<()>
+where
+    L: InputLine + 'static,
+{
+    thread::spawn(move || loop {
+        match line.is_pressed() {
+            Ok(raw) => {
+                if let Some(event) = debouncer.update(raw, Instant::now()) {
+                    if tx.send(event).is_err() {
+                        break;
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("Could not read trigger line: {e}");
+                thread::sleep(Duration::from_secs(1));
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+    })
+}
+
+/// Starts the GPIO trigger if a chip is configured.
+/// With `gpio.simulate` a mock line is polled instead and returned so the caller can drive it.
+pub fn start_gpio_trigger(
+    config: &GpioConfig,
+    tx: Sender<TriggerEvent>,
+) -> Result<Option<MockLine>, Box<dyn Error>> {
+    let debouncer = Debouncer::new(config.debounce(), config.long_press());
+    if config.simulate {
+        let line = MockLine::default();
+        spawn_trigger_thread(line.clone(), debouncer, tx);
+        return Ok(Some(line));
+    }
+    let Some(chip) = &config.chip else {
+        return Ok(None);
+    };
+
+    #[cfg(target_os = "linux")]
+    {
+        let line = GpioLine::open(config, chip)?;
+        spawn_trigger_thread(line, debouncer, tx);
+        Ok(None)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (debouncer, tx);
+        Err(format!(
+            "gpio.chip {} is set, but GPIO triggers are only supported on Linux",
+            chip.display()
+        )
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    const SAMPLE: Duration = Duration::from_millis(5);
+
+    /// Samples every 5 ms, holding each level for the given milliseconds, returns the events
+    fn feed(debouncer: &mut Debouncer, levels: &[(bool, u64)]) -> Vec<TriggerEvent> {
+        let mut now = Instant::now();
+        let mut events = Vec::new();
+        for &(level, millis) in levels {
+            let until = now + Duration::from_millis(millis);
+            while now < until {
+                events.extend(debouncer.update(level, now));
+                now += SAMPLE;
+            }
+        }
+        events
+    }
+
+    fn debouncer() -> Debouncer {
+        Debouncer::new(Duration::from_millis(20), Duration::from_millis(500))
+    }
+
+    #[test]
+    fn bounces_shorter_than_the_debounce_are_ignored() {
+        let mut debouncer = debouncer();
+        let events = feed(
+            &mut debouncer,
+            &[
+                (false, 50),
+                (true, 10),
+                (false, 10),
+                (true, 15),
+                (false, 100),
+            ],
+        );
+        assert_eq!(events, []);
+    }
+
+    #[test]
+    fn stable_press_is_one_press() {
+        let mut debouncer = debouncer();
+        let events = feed(
+            &mut debouncer,
+            &[
+                (false, 50),
+                // Contact bounce on both edges
+                (true, 5),
+                (false, 5),
+                (true, 100),
+                (false, 5),
+                (true, 5),
+                (false, 100),
+            ],
+        );
+        assert_eq!(events, [TriggerEvent::Press]);
+    }
+
+    #[test]
+    fn holding_is_one_long_press_without_press_on_release() {
+        let mut debouncer = debouncer();
+        let events = feed(&mut debouncer, &[(false, 50), (true, 1500), (false, 100)]);
+        assert_eq!(events, [TriggerEvent::LongPress]);
+    }
+
+    #[test]
+    fn trigger_thread_polls_the_mock_line() {
+        let line = MockLine::default();
+        let (tx, rx) = channel();
+        spawn_trigger_thread(
+            line.clone(),
+            Debouncer::new(Duration::from_millis(10), Duration::from_secs(5)),
+            tx,
+        );
+        line.set(true);
+        thread::sleep(Duration::from_millis(100));
+        line.set(false);
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(2)),
+            Ok(TriggerEvent::Press)
+        );
+    }
+}
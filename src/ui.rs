@@ -0,0 +1,212 @@
+use crate::raylib::color::{Color, WHITE};
+use crate::raylib::sys::gestures::GESTURE_TAP;
+use crate::raylib::*;
+
+const BUTTON_WIDTH: f32 = 200.;
+const BUTTON_HEIGHT: f32 = 64.;
+const BUTTON_SPACING: f32 = 24.;
+const BUTTON_BOTTOM_PADDING: f32 = 32.;
+const BUTTON_FONT_SIZE: i32 = 28;
+
+const PALETTE_SLOT_SIZE: f32 = 96.;
+const PALETTE_SPACING: f32 = 12.;
+
+const SHUTTER_INNER_RADIUS: f32 = 30.;
+const SHUTTER_OUTER_RADIUS: f32 = 40.;
+const SHUTTER_BOTTOM_PADDING: f32 = 40.;
+/// Extra radius around the shutter ring that still counts as a hit, fingers are not precise
+const SHUTTER_HIT_SLOP: f32 = 20.;
+
+/// Turns mouse clicks and touch taps into single presses
+#[derive(Default)]
+pub struct Pointer {
+    tap_down: bool,
+}
+
+impl Pointer {
+    /// Position of a click or tap that started in this frame.
+    /// raylib keeps reporting a tap while the finger rests on the screen, so only the first frame counts.
+    pub fn pressed(&mut self) -> Option<Vector2> {
+        let tap = is_gesture_detected(GESTURE_TAP);
+        let new_tap = tap && !self.tap_down;
+        self.tap_down = tap;
+        if !new_tap && !is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
+            return None;
+        }
+        if get_touch_point_count() > 0 {
+            Some(get_touch_position(0))
+        } else {
+            Some(get_mouse_position())
+        }
+    }
+
+    /// Whether a finger or the left mouse button is currently down
+    pub fn is_down(&self) -> bool {
+        get_touch_point_count() > 0 || is_mouse_button_down(MouseButton::MOUSE_BUTTON_LEFT)
+    }
+
+    /// Current finger or mouse position
+    pub fn position(&self) -> Vector2 {
+        if get_touch_point_count() > 0 {
+            get_touch_position(0)
+        } else {
+            get_mouse_position()
+        }
+    }
+}
+
+/// Round shutter button at the bottom center of the screen
+pub struct ShutterButton {
+    pub center: Vector2,
+}
+
+impl ShutterButton {
+    pub fn new(screen_size: Vector2) -> Self {
+        Self {
+            center: Vector2(
+                screen_size.0 / 2.,
+                screen_size.1 - SHUTTER_OUTER_RADIUS - SHUTTER_BOTTOM_PADDING,
+            ),
+        }
+    }
+
+    pub fn contains(&self, point: Vector2) -> bool {
+        point.distance(self.center) <= SHUTTER_OUTER_RADIUS + SHUTTER_HIT_SLOP
+    }
+
+    /// Draws the ring, filled while `pressed`
+    pub fn draw(&self, pressed: bool) {
+        draw_ring(
+            self.center,
+            SHUTTER_INNER_RADIUS,
+            SHUTTER_OUTER_RADIUS,
+            0.,
+            360.,
+            360,
+            WHITE,
+        );
+        if pressed {
+            draw_ring(
+                self.center,
+                0.,
+                SHUTTER_INNER_RADIUS - 4.,
+                0.,
+                360.,
+                360,
+                WHITE.fade(0.5),
+            );
+        }
+    }
+}
+
+pub struct Button<'a> {
+    pub label: &'a str,
+    pub rect: Rectangle,
+    pub color: Color,
+}
+
+impl Button<'_> {
+    pub fn contains(&self, point: Vector2) -> bool {
+        self.rect.contains(point)
+    }
+
+    pub fn draw(&self) {
+        let Rectangle {
+            x,
+            y,
+            width,
+            height,
+        } = self.rect;
+        draw_rectangle(
+            x.round() as i32,
+            y.round() as i32,
+            width.round() as i32,
+            height.round() as i32,
+            self.color,
+        );
+        let text_width = measure_text(self.label, BUTTON_FONT_SIZE);
+        draw_text(
+            self.label,
+            (x + (width - text_width as f32) / 2.).round() as i32,
+            (y + (height - BUTTON_FONT_SIZE as f32) / 2.).round() as i32,
+            BUTTON_FONT_SIZE,
+            WHITE,
+        );
+    }
+}
+
+/// Lays the buttons out in a centered row at the bottom of the screen
+pub fn bottom_row<const N: usize>(
+    buttons: [(&'static str, Color); N],
+    screen_size: Vector2,
+) -> [Button<'static>; N] {
+    let row_width = N as f32 * BUTTON_WIDTH + N.saturating_sub(1) as f32 * BUTTON_SPACING;
+    let left = (screen_size.0 - row_width) / 2.;
+    let y = screen_size.1 - BUTTON_HEIGHT - BUTTON_BOTTOM_PADDING;
+    let mut i = 0.;
+    buttons.map(|(label, color)| {
+        let x = left + i * (BUTTON_WIDTH + BUTTON_SPACING);
+        i += 1.;
+        Button {
+            label,
+            rect: Rectangle {
+                x,
+                y,
+                width: BUTTON_WIDTH,
+                height: BUTTON_HEIGHT,
+            },
+            color,
+        }
+    })
+}
+
+/// Lays the buttons out in a centered row above the bottom row
+pub fn second_row<const N: usize>(
+    buttons: [(&'static str, Color); N],
+    screen_size: Vector2,
+) -> [Button<'static>; N] {
+    bottom_row(buttons, screen_size).map(|mut button| {
+        button.rect.y -= BUTTON_HEIGHT + BUTTON_SPACING;
+        button
+    })
+}
+
+/// Button in the bottom left corner, for options next to the shutter
+pub fn bottom_left(label: &str, color: Color, screen_size: Vector2) -> Button<'_> {
+    Button {
+        label,
+        rect: Rectangle {
+            x: BUTTON_SPACING,
+            y: screen_size.1 - BUTTON_HEIGHT - BUTTON_BOTTOM_PADDING,
+            width: BUTTON_WIDTH,
+            height: BUTTON_HEIGHT,
+        },
+        color,
+    }
+}
+
+/// Button in the bottom right corner
+pub fn bottom_right(label: &str, color: Color, screen_size: Vector2) -> Button<'_> {
+    let mut button = bottom_left(label, color, screen_size);
+    button.rect.x = screen_size.0 - BUTTON_WIDTH - BUTTON_SPACING;
+    button
+}
+
+/// Square slots in a column along the right edge above the bottom right button, shrunk to fit the screen
+pub fn palette(count: usize, screen_size: Vector2) -> Vec<Rectangle> {
+    let top = BUTTON_SPACING;
+    let bottom = screen_size.1 - BUTTON_HEIGHT - BUTTON_BOTTOM_PADDING - BUTTON_SPACING;
+    let n = count as f32;
+    let size = ((bottom - top - (n - 1.) * PALETTE_SPACING) / n).clamp(0., PALETTE_SLOT_SIZE);
+    let column_height = n * size + (n - 1.) * PALETTE_SPACING;
+    let y = top + (bottom - top - column_height) / 2.;
+    let x = screen_size.0 - BUTTON_SPACING - size;
+    (0..count)
+        .map(|i| Rectangle {
+            x,
+            y: y + i as f32 * (size + PALETTE_SPACING),
+            width: size,
+            height: size,
+        })
+        .collect()
+}
@@ -0,0 +1,122 @@
+use std::error::Error;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use opencv::core::{flip, Mat};
+use opencv::prelude::*;
+use opencv::videoio::{VideoCapture, VideoWriter, CAP_ANY, CAP_PROP_FPS, CAP_PROP_POS_FRAMES};
+
+use crate::camera::{read_frame, resized, FrameSource};
+use crate::config::VideoConfig;
+
+/// Records mirrored BGR preview frames for the configured duration into a video file at `path`.
+/// `on_frame` gets every recorded frame, e.g. to keep the live preview running.
+/// Returns the first frame.
+pub fn record_video<F>(
+    source: &mut dyn FrameSource,
+    path: &Path,
+    config: &VideoConfig,
+    mut on_frame: F,
+) -> Result<Mat, Box<dyn Error>>
+where
+    F: FnMut(&Mat),
+{
+    let fourcc: Vec<char> = config.fourcc.chars().collect();
+    let fourcc = VideoWriter::fourcc(fourcc[0], fourcc[1], fourcc[2], fourcc[3])?;
+    let frame_time = 1. / config.fps;
+    let total = (config.duration_secs * config.fps).round() as u32;
+
+    let mut writer: Option<VideoWriter> = None;
+    let mut first: Option<Mat> = None;
+    let mut written = 0;
+    let start = Instant::now();
+    while written < total {
+        let Some(frame) = source.read_preview()? else {
+            if start.elapsed() > config.duration() * 2 {
+                return Err("Camera stopped delivering frames while recording".into());
+            }
+            continue;
+        };
+        let mut flipped = Mat::default();
+        flip(&frame, &mut flipped, 1)?;
+
+        let writer = match (&mut writer, &first) {
+            (Some(writer), Some(first)) => {
+                // The camera may come back with another resolution after a reconnect
+                if flipped.size()? != first.size()? {
+                    flipped = resized(&flipped, first.size()?)?;
+                }
+                writer
+            }
+            _ => {
+                let new_writer = VideoWriter::new(
+                    &path.to_string_lossy(),
+                    fourcc,
+                    config.fps as f64,
+                    flipped.size()?,
+                    true,
+                )?;
+                if !new_writer.is_opened()? {
+                    return Err(
+                        format!("Could not open video writer for {}", path.display()).into(),
+                    );
+                }
+                first = Some(flipped.clone());
+                writer.insert(new_writer)
+            }
+        };
+
+        // Frames due by the wall clock, a slow camera repeats frames and a fast one skips them
+        let due = ((start.elapsed().as_secs_f32() / frame_time) as u32 + 1).min(total);
+        while written < due {
+            writer.write(&flipped)?;
+            written += 1;
+        }
+        on_frame(&flipped);
+    }
+    if let Some(writer) = &mut writer {
+        writer.release()?;
+    }
+    first.ok_or_else(|| "No frame was recorded".into())
+}
+
+/// Plays a video file in a loop at its own frame rate
+pub struct VideoPlayer {
+    cap: VideoCapture,
+    frame_time: Duration,
+    next_frame: Instant,
+}
+
+impl VideoPlayer {
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let cap = VideoCapture::from_file(&path.to_string_lossy(), CAP_ANY)?;
+        if !cap.is_opened()? {
+            return Err(format!("Could not open video {}", path.display()).into());
+        }
+        let fps = cap.get(CAP_PROP_FPS).unwrap_or(0.);
+        let fps = if fps > 0. { fps } else { 25. };
+        Ok(Self {
+            cap,
+            frame_time: Duration::from_secs_f64(1. / fps),
+            next_frame: Instant::now(),
+        })
+    }
+
+    /// Next BGR frame if it is due, starts over at the end of the video
+    pub fn next_frame(&mut self) -> Result<Option<Mat>, Box<dyn Error>> {
+        let now = Instant::now();
+        if now < self.next_frame {
+            return Ok(None);
+        }
+        // Drop behind instead of catching up after a slow render frame
+        self.next_frame = (self.next_frame + self.frame_time).max(now);
+
+        if let Some(frame) = read_frame(&mut self.cap)? {
+            return Ok(Some(frame));
+        }
+        self.cap.set(CAP_PROP_POS_FRAMES, 0.)?;
+        read_frame(&mut self.cap)?
+            .map(Some)
+            .ok_or_else(|| "Video does not contain any frames".into())
+    }
+}